@@ -0,0 +1,298 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Component, Path, PathBuf, Prefix};
+
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::filter::RemarkFilter;
+use crate::remark::{
+    find_remark_files, load_remarks_from_dir, remap_path, MessagePart, PathMapping,
+    RemarkLoadOptions, FINGERPRINTS_FILE_NAME,
+};
+use crate::utils::callback::LoadCallback;
+use crate::utils::data_structures::Set;
+use crate::RustcSourceRoot;
+
+/// Default file name of remark archives.
+pub const ARCHIVE_FILE_NAME: &str = "remarks.tar.gz";
+
+const MANIFEST_PATH: &str = "manifest.json";
+const YAML_DIR: &str = "yaml";
+/// Sources with a path relative to the source directory.
+const WORKSPACE_SOURCE_DIR: &str = "sources/workspace";
+/// Sources with an absolute path (dependencies, standard library).
+const EXTERNAL_SOURCE_DIR: &str = "sources/external";
+
+/// Metadata describing how were the remarks inside an archive generated and loaded.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct ArchiveManifest {
+    /// Version of `cargo-remark` that has created the archive.
+    pub version: String,
+    /// Verbose version (`rustc -vV`) of the compiler that has generated the remarks.
+    pub rustc_version: Option<String>,
+    /// Original source directory of the crate.
+    pub source_dir: PathBuf,
+    /// Original root path of rustc toolchain sources.
+    pub rustc_source_root: Option<PathBuf>,
+    /// Were remarks from external crates included?
+    pub external: bool,
+    /// Remark kinds that were ignored.
+    pub filter_kind: Vec<String>,
+    /// Filter expressions that were used to select remarks.
+    #[serde(default)]
+    pub filter: RemarkFilter,
+    /// Path mappings that were used to load the remarks.
+    pub path_mappings: Vec<PathMapping>,
+    /// Root prefixes of absolute source paths, together with the archive directory that contains
+    /// sources found under them.
+    pub external_roots: Vec<ExternalRoot>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExternalRoot {
+    pub prefix: String,
+    pub dir: String,
+}
+
+pub struct PackSummary {
+    pub remark_count: usize,
+    pub remark_file_count: usize,
+    pub source_file_count: usize,
+}
+
+/// Packs remark files from `yaml_dir`, together with all source files referenced by the
+/// remarks, into a gzipped tarball located at `output`.
+pub fn pack_remarks(
+    yaml_dir: &Path,
+    options: RemarkLoadOptions,
+    rustc_version: Option<String>,
+    output: &Path,
+    callback: Option<&(dyn LoadCallback + Send + Sync)>,
+) -> anyhow::Result<PackSummary> {
+    let mut manifest = ArchiveManifest {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        rustc_version,
+        source_dir: options.source_dir.clone(),
+        rustc_source_root: options
+            .rustc_source_root
+            .as_ref()
+            .map(|root| root.0.clone()),
+        external: options.external,
+        filter_kind: options.filter_kind.clone(),
+        filter: options.filter.clone(),
+        path_mappings: options.path_mappings.clone(),
+        external_roots: vec![],
+    };
+    let source_dir = options.source_dir.clone();
+
+    let yaml_files = find_remark_files(yaml_dir)?;
+    let remarks = load_remarks_from_dir(yaml_dir, options, callback)?;
+
+    let mut referenced_files: Set<&str> = Set::default();
+    for remark in &remarks {
+        if let Some(ref location) = remark.function.location {
+            referenced_files.insert(&location.file);
+        }
+        for part in &remark.message {
+            if let MessagePart::AnnotatedString { location, .. } = part {
+                referenced_files.insert(&location.file);
+            }
+        }
+    }
+
+    let file = File::create(output)
+        .with_context(|| format!("Cannot create archive {}", output.display()))?;
+    let mut builder =
+        tar::Builder::new(GzEncoder::new(BufWriter::new(file), Compression::default()));
+
+    for path in &yaml_files {
+        let name = path.file_name().expect("Remark file without a name");
+        builder
+            .append_path_with_name(path, Path::new(YAML_DIR).join(name))
+            .with_context(|| format!("Cannot add {} to archive", path.display()))?;
+    }
+    // Stored fingerprints were computed from the sources at build time, which may have changed
+    let fingerprints = yaml_dir.join(FINGERPRINTS_FILE_NAME);
+    if fingerprints.is_file() {
+        builder
+            .append_path_with_name(
+                &fingerprints,
+                Path::new(YAML_DIR).join(FINGERPRINTS_FILE_NAME),
+            )
+            .context("Cannot add fingerprints to archive")?;
+    }
+
+    let mut external_roots: Set<ExternalRoot> = Set::default();
+    let mut source_file_count = 0;
+    for file in referenced_files {
+        let path = Path::new(file);
+        let (original_path, archive_path) = if path.is_absolute() {
+            let (root, relative) = split_root(path);
+            let archive_dir = format!("{EXTERNAL_SOURCE_DIR}/{}", root.dir);
+            let archive_path = Path::new(&archive_dir).join(relative);
+            external_roots.insert(root);
+            (path.to_path_buf(), archive_path)
+        } else {
+            (
+                source_dir.join(path),
+                Path::new(WORKSPACE_SOURCE_DIR).join(path),
+            )
+        };
+        if !original_path.is_file() {
+            log::debug!(
+                "Source file {} not found, it will not be archived",
+                original_path.display()
+            );
+            continue;
+        }
+        builder
+            .append_path_with_name(&original_path, archive_path)
+            .with_context(|| format!("Cannot add {} to archive", original_path.display()))?;
+        source_file_count += 1;
+    }
+
+    manifest.external_roots = external_roots.into_iter().collect();
+    let manifest = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, MANIFEST_PATH, manifest.as_slice())
+        .context("Cannot add manifest to archive")?;
+
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .with_context(|| format!("Cannot write archive {}", output.display()))?;
+
+    Ok(PackSummary {
+        remark_count: remarks.len(),
+        remark_file_count: yaml_files.len(),
+        source_file_count,
+    })
+}
+
+/// Remark archive extracted into a directory.
+pub struct UnpackedArchive {
+    pub manifest: ArchiveManifest,
+    /// Directory containing the remark files.
+    pub yaml_dir: PathBuf,
+    /// Directory containing sources of the crate.
+    pub source_dir: PathBuf,
+    /// Directory into which the archive was extracted.
+    dir: PathBuf,
+}
+
+impl UnpackedArchive {
+    /// Creates load options that resolve all archived sources inside the extracted directory.
+    pub fn load_options(&self) -> RemarkLoadOptions {
+        let external_mappings: Vec<PathMapping> = self
+            .manifest
+            .external_roots
+            .iter()
+            .map(|root| PathMapping {
+                from: root.prefix.clone(),
+                to: self.dir.join(EXTERNAL_SOURCE_DIR).join(&root.dir),
+            })
+            .collect();
+        // Only a single mapping is applied to each path, so the original mappings have to point
+        // directly to the extracted sources
+        let mut path_mappings: Vec<PathMapping> = self
+            .manifest
+            .path_mappings
+            .iter()
+            .map(|mapping| PathMapping {
+                from: mapping.from.clone(),
+                to: remap_path(&external_mappings, &mapping.to)
+                    .unwrap_or_else(|| mapping.to.clone()),
+            })
+            .collect();
+        path_mappings.extend(external_mappings);
+
+        RemarkLoadOptions {
+            external: self.manifest.external,
+            source_dir: self.source_dir.clone(),
+            filter_kind: self.manifest.filter_kind.clone(),
+            filter: self.manifest.filter.clone(),
+            rustc_source_root: self.manifest.rustc_source_root.clone().map(RustcSourceRoot),
+            path_mappings,
+        }
+    }
+}
+
+/// Extracts an archive created by [`pack_remarks`] into `dir`.
+pub fn unpack_remarks(archive: &Path, dir: &Path) -> anyhow::Result<UnpackedArchive> {
+    let file = File::open(archive)
+        .with_context(|| format!("Cannot open archive {}", archive.display()))?;
+    tar::Archive::new(GzDecoder::new(BufReader::new(file)))
+        .unpack(dir)
+        .with_context(|| format!("Cannot extract archive {}", archive.display()))?;
+
+    let manifest_path = dir.join(MANIFEST_PATH);
+    let manifest = File::open(&manifest_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?))
+        .with_context(|| format!("Cannot read manifest of archive {}", archive.display()))?;
+
+    Ok(UnpackedArchive {
+        manifest,
+        yaml_dir: dir.join(YAML_DIR),
+        source_dir: dir.join(WORKSPACE_SOURCE_DIR),
+        dir: dir.to_path_buf(),
+    })
+}
+
+/// Splits an absolute path into its root (e.g. `/` or `C:\`) and the rest of the path.
+fn split_root(path: &Path) -> (ExternalRoot, PathBuf) {
+    let mut prefix = PathBuf::new();
+    let mut dir = String::from("root");
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(value) => {
+                prefix.push(component);
+                dir = match value.kind() {
+                    Prefix::Disk(disk) | Prefix::VerbatimDisk(disk) => (disk as char).to_string(),
+                    _ => "prefix".to_string(),
+                };
+            }
+            Component::RootDir => prefix.push(component),
+            _ => relative.push(component),
+        }
+    }
+    (
+        ExternalRoot {
+            prefix: prefix.to_string_lossy().into_owned(),
+            dir,
+        },
+        relative,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::split_root;
+    use std::path::Path;
+
+    #[cfg(unix)]
+    #[test]
+    fn split_unix_root() {
+        let (root, relative) = split_root(Path::new("/home/foo/src/lib.rs"));
+        assert_eq!(root.prefix, "/");
+        assert_eq!(root.dir, "root");
+        assert_eq!(relative, Path::new("home/foo/src/lib.rs"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn split_windows_root() {
+        let (root, relative) = split_root(Path::new(r#"C:\foo\src\lib.rs"#));
+        assert_eq!(root.prefix, r#"C:\"#);
+        assert_eq!(root.dir, "C");
+        assert_eq!(relative, Path::new(r#"foo\src\lib.rs"#));
+    }
+}
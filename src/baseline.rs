@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::remark::{Fingerprint, Line, Remark};
+use crate::utils::data_structures::Map;
+
+const BASELINE_VERSION: u32 = 1;
+
+/// Set of known remarks, identified by their fingerprints.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Baseline {
+    version: u32,
+    pub remarks: Vec<BaselineEntry>,
+}
+
+/// Remark stored in a baseline.
+/// Only the fingerprint is used for comparison, the rest makes the file readable.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct BaselineEntry {
+    pub fingerprint: Fingerprint,
+    pub pass: String,
+    pub name: String,
+    pub function: String,
+    pub file: Option<String>,
+    pub line: Option<Line>,
+}
+
+impl Baseline {
+    pub fn from_remarks(remarks: &[Remark]) -> Self {
+        let mut entries: Vec<BaselineEntry> = remarks
+            .iter()
+            .map(|remark| BaselineEntry {
+                fingerprint: remark.fingerprint,
+                pass: remark.pass.clone(),
+                name: remark.name.clone(),
+                function: remark.function.name.clone(),
+                file: remark.function.location.as_ref().map(|l| l.file.clone()),
+                line: remark.function.location.as_ref().map(|l| l.line),
+            })
+            .collect();
+        // Sort the entries, so that the file produces readable diffs
+        entries.sort_by(|a, b| {
+            (&a.file, a.line, a.fingerprint).cmp(&(&b.file, b.line, b.fingerprint))
+        });
+        Self {
+            version: BASELINE_VERSION,
+            remarks: entries,
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read(path)
+            .with_context(|| format!("Cannot read baseline {}", path.display()))?;
+        let baseline: Baseline = serde_json::from_slice(&content)
+            .with_context(|| format!("Cannot parse baseline {}", path.display()))?;
+        if baseline.version != BASELINE_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported baseline version {}, regenerate the baseline",
+                baseline.version
+            ));
+        }
+        Ok(baseline)
+    }
+
+    pub fn store(&self, path: &Path) -> anyhow::Result<()> {
+        let mut content = serde_json::to_string_pretty(self)?;
+        content.push('\n');
+        std::fs::write(path, content)
+            .with_context(|| format!("Cannot write baseline {}", path.display()))
+    }
+
+    /// Returns remarks that are not present in the baseline.
+    ///
+    /// The same fingerprint can appear multiple times, so remarks are compared as a multiset.
+    pub fn find_new_remarks<'a>(&self, remarks: &'a [Remark]) -> Vec<&'a Remark> {
+        let mut known: Map<Fingerprint, usize> = Map::default();
+        for entry in &self.remarks {
+            *known.entry(entry.fingerprint).or_default() += 1;
+        }
+        remarks
+            .iter()
+            .filter(|remark| match known.get_mut(&remark.fingerprint) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::baseline::Baseline;
+    use crate::remark::{Remark, RemarkBuilder};
+
+    #[test]
+    fn find_new_remarks() {
+        let baseline = Baseline::from_remarks(&[remark("a"), remark("b")]);
+        let remarks = vec![remark("a"), remark("b"), remark("b"), remark("c")];
+        let new: Vec<String> = baseline
+            .find_new_remarks(&remarks)
+            .into_iter()
+            .map(|remark| remark.fingerprint.to_string())
+            .collect();
+        assert_eq!(new, vec![fingerprint("b"), fingerprint("c")]);
+    }
+
+    #[test]
+    fn store_and_load() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("baseline.json");
+        Baseline::from_remarks(&[remark("a")]).store(&path).unwrap();
+        let baseline = Baseline::load(&path).unwrap();
+        assert_eq!(baseline.remarks.len(), 1);
+        assert_eq!(
+            baseline.remarks[0].fingerprint.to_string(),
+            fingerprint("a")
+        );
+        assert!(baseline.find_new_remarks(&[remark("a")]).is_empty());
+    }
+
+    fn fingerprint(id: &str) -> String {
+        format!("{:0>16}", id)
+    }
+
+    fn remark(id: &str) -> Remark {
+        RemarkBuilder::new("gvn", "LoadClobbered")
+            .without_location()
+            .fingerprint(&fingerprint(id))
+            .build()
+    }
+}
@@ -1,6 +1,13 @@
-use cargo_remark::remark::{load_remarks_from_dir, RemarkLoadOptions};
-use cargo_remark::render::render_remarks;
+use anyhow::Context;
+use cargo_remark::archive::unpack_remarks;
+use cargo_remark::config::load_config_from_dir;
+use cargo_remark::filter::FilterExpr;
+use cargo_remark::remark::locate::{check_source_dir, infer_source_dir};
+use cargo_remark::remark::{load_remarks_from_dir, PathMapping, RemarkLoadOptions};
+use cargo_remark::render::{render_remarks, RenderOptions};
+use cargo_remark::triage::load_triage;
 use cargo_remark::utils::callback::ProgressBarCallback;
+use cargo_remark::utils::cli::{cli_format_path, flag_value};
 use cargo_remark::utils::open_result;
 use cargo_remark::utils::timing::time_block_print;
 use cargo_remark::RustcSourceRoot;
@@ -15,14 +22,18 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 /// Analyze a directory containing YAML files with LLVM optimization remarks
 #[derive(clap::Parser, Debug)]
 struct Args {
-    /// Directory containing remark files in YAML format.
-    /// They have to end with the `.opt.yaml` extension.
-    #[arg()]
-    remark_dir: PathBuf,
+    /// Directory containing remark files in YAML format, or an archive created by
+    /// `cargo remark pack`.
+    /// Remark files have to end with the `.opt.yaml` extension.
+    #[arg(required_unless_present = "print_config")]
+    remark_dir: Option<PathBuf>,
 
     /// Root directory of source (crate) from which the remarks were generated.
+    /// If not specified, it will be inferred from the location of the remark directory
+    /// and the paths found in the remarks.
+    /// Not needed when loading remarks from an archive.
     #[arg(long)]
-    source_dir: PathBuf,
+    source_dir: Option<PathBuf>,
 
     /// Output directory into which a HTML website with remark information will be generated.
     #[arg(long, default_value = "out")]
@@ -30,25 +41,57 @@ struct Args {
 
     /// Load remarks from external code (i.e. crate dependencies).
     /// Note that this may produce a large amount of data!
-    #[arg(long)]
+    /// Defaults to the `external` configuration option.
+    #[arg(long, overrides_with = "no_external")]
     external: bool,
 
+    /// Do not load remarks from external code, even if the `external` configuration option is
+    /// set.
+    #[arg(long, overrides_with = "external")]
+    no_external: bool,
+
     /// Sysroot directory of Rust toolchain which generated the remarks.
     /// Used to resolve standard library sources.
     /// Can be found with `rustc --print=sysroot`.
+    /// Defaults to the `sysroot` configuration option.
     #[arg(long)]
     sysroot: Option<PathBuf>,
 
     /// Optimization remark kinds that should be ignored.
-    #[arg(
-        long = "filter",
-        default_values = cargo_remark::DEFAULT_KIND_FILTER
-    )]
+    /// Defaults to the `filter` configuration option, or to `FastISelFailure`, `NeverInline` and
+    /// `SpillReloadCopies`.
+    #[arg(long = "filter")]
     filter_kind: Vec<String>,
 
+    /// Only load remarks that match this filter expression, e.g.
+    /// `pass == "gvn" && file ~ "src/hot/**" && hotness > 1000`.
+    /// If passed multiple times, remarks that match any of the expressions are loaded.
+    #[arg(long, value_name = "EXPR")]
+    include: Vec<FilterExpr>,
+
+    /// Ignore remarks that match this filter expression.
+    #[arg(long, value_name = "EXPR")]
+    exclude: Vec<FilterExpr>,
+
+    /// Replace a prefix of source paths found in the remarks, in the `FROM=TO` format.
+    /// Useful when the remarks were generated on a different machine.
+    /// Defaults to the `remap-path-prefix` configuration option.
+    #[arg(long = "remap-path-prefix")]
+    path_mappings: Vec<PathMapping>,
+
     /// Open the generated website after the build finishes.
-    #[arg(long)]
+    /// Defaults to the `open` configuration option.
+    #[arg(long, overrides_with = "no_open")]
     open: bool,
+
+    /// Do not open the generated website, even if the `open` configuration option is set.
+    #[arg(long, overrides_with = "open")]
+    no_open: bool,
+
+    /// Print the effective configuration, loaded from `Cargo.toml` and `remark.toml` in the
+    /// current directory, and exit.
+    #[arg(long)]
+    print_config: bool,
 }
 
 fn analyze(args: Args) -> anyhow::Result<()> {
@@ -57,31 +100,114 @@ fn analyze(args: Args) -> anyhow::Result<()> {
         source_dir,
         output_dir,
         external,
+        no_external,
         sysroot,
         filter_kind,
+        include,
+        exclude,
+        path_mappings,
         open,
+        no_open,
+        print_config,
     } = args;
 
-    let rustc_source_root = sysroot
-        .map(|sysroot| RustcSourceRoot::from_sysroot(sysroot).expect("Cannot find Rust sources"));
+    let loaded_config = load_config_from_dir(&std::env::current_dir()?)?;
+    if print_config {
+        print!("{}", loaded_config.to_toml()?);
+        return Ok(());
+    }
+    let config = loaded_config.config;
+    let remark_dir = remark_dir.context("Missing remark directory")?;
+    let external = flag_value(external, no_external);
+    let open = flag_value(open, no_open).unwrap_or(config.open);
+
+    // The extracted archive has to live until the remarks are rendered.
+    let archive_dir;
+    let (remark_dir, options) = if remark_dir.is_file() {
+        if source_dir.is_some() || sysroot.is_some() || !path_mappings.is_empty() {
+            log::warn!("`--source-dir`, `--sysroot` and `--remap-path-prefix` are ignored when loading an archive");
+        }
+        archive_dir = tempfile::TempDir::new()?;
+        let archive = unpack_remarks(&remark_dir, archive_dir.path())?;
+        if let Some(version) = archive
+            .manifest
+            .rustc_version
+            .as_deref()
+            .and_then(|version| version.lines().next())
+        {
+            log::info!("Remarks were generated by {version}");
+        }
+        // Flags passed on the command line take precedence over the options used for packing
+        let mut options = archive.load_options();
+        if let Some(external) = external {
+            options.external = external;
+        }
+        if !filter_kind.is_empty() {
+            options.filter_kind = filter_kind;
+        }
+        if !include.is_empty() {
+            options.filter.include = include;
+        }
+        if !exclude.is_empty() {
+            options.filter.exclude = exclude;
+        }
+        (archive.yaml_dir, options)
+    } else {
+        let external = external.unwrap_or(config.external);
+        let resolution = match source_dir {
+            Some(source_dir) => check_source_dir(&remark_dir, &source_dir)?,
+            None => {
+                let resolution = infer_source_dir(&remark_dir)
+                    .context("Cannot infer source directory, please use `--source-dir`")?;
+                log::info!(
+                    "Inferred source directory {}",
+                    cli_format_path(&resolution.source_dir)
+                );
+                resolution
+            }
+        };
+        log::info!(
+            "{}/{} remark location(s) resolved under {}",
+            resolution.resolved,
+            resolution.total,
+            cli_format_path(&resolution.source_dir)
+        );
+        if resolution.resolved == 0 && resolution.total > 0 && !external {
+            log::warn!("No remark locations were found in the source directory, all remarks will be ignored. Use `--source-dir` to select the directory from which the remarks were generated.");
+        }
+        let source_dir = resolution.source_dir;
+        let rustc_source_root = sysroot.or(config.sysroot.clone()).map(|sysroot| {
+            RustcSourceRoot::from_sysroot(sysroot).expect("Cannot find Rust sources")
+        });
+        let path_mappings = if path_mappings.is_empty() {
+            config.path_mappings()?
+        } else {
+            path_mappings
+        };
+        let options = RemarkLoadOptions {
+            external,
+            source_dir,
+            filter_kind: config.filter_kind(filter_kind),
+            filter: config.remark_filter(include, exclude),
+            rustc_source_root,
+            path_mappings,
+        };
+        (remark_dir, options)
+    };
+    let source_dir = options.source_dir.clone();
 
     let remarks = time_block_print("Remark loading", || {
-        load_remarks_from_dir(
-            remark_dir,
-            RemarkLoadOptions {
-                external,
-                source_dir: source_dir.clone(),
-                filter_kind,
-                rustc_source_root,
-            },
-            Some(&ProgressBarCallback::default()),
-        )
+        load_remarks_from_dir(remark_dir, options, Some(&ProgressBarCallback::default()))
     })?;
     time_block_print("Render", || {
         render_remarks(
             remarks,
             &source_dir,
             &output_dir,
+            RenderOptions {
+                triage: load_triage(&source_dir),
+                ..Default::default()
+            },
             Some(&ProgressBarCallback::default()),
         )
     })?;
@@ -91,7 +217,10 @@ fn analyze(args: Args) -> anyhow::Result<()> {
 }
 
 fn main() -> anyhow::Result<()> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("cargo_remark=info")).init();
+    env_logger::Builder::from_env(
+        Env::default().default_filter_or("cargo_remark=info,analyze_remarks=info"),
+    )
+    .init();
 
     let args = Args::parse();
     analyze(args)?;
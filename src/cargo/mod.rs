@@ -1,30 +1,302 @@
 use anyhow::Context;
+use cargo_remark::config::{ConfigLoader, LoadedConfig, RemarkConfig, CONFIG_FILE_NAME};
+use cargo_remark::remark::diagnostic::{DiagnosticRemark, DIAGNOSTICS_FILE_NAME};
+use cargo_remark::remark::{store_fingerprints, RemarkLoadOptions};
+use cargo_remark::utils::timing::time_block_log_info;
 use cargo_remark::RustcSourceRoot;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitStatus, Stdio};
 
 use cargo_remark::utils::io::ensure_directory;
 
+use crate::cargo::run::{
+    create_run_dir, find_latest_run, lock_remark_dir, mark_latest_run, prune_runs,
+};
+use crate::cargo::version::get_host_triple;
 use cargo_remark::utils::cli::cli_format_path;
+use wrapper::{configure_wrapper, WrapperConfig};
 
+pub mod pgo;
+mod run;
+pub mod toolchain;
 pub mod version;
+pub mod watch;
+pub mod wrapper;
+
+/// Makes Cargo print compiler messages as JSON, so that remarks can be collected from them.
+const MESSAGE_FORMAT_ARG: &str = "--message-format=json";
+
+/// Cargo subcommands (and their aliases) that accept `--message-format`, and which can thus be
+/// wrapped when remarks are collected from diagnostics.
+const MESSAGE_FORMAT_SUBCOMMANDS: &[&str] =
+    &["build", "b", "check", "c", "test", "t", "run", "r", "bench"];
+
+/// Values of the `reason` field of JSON messages printed by Cargo.
+const CARGO_MESSAGE_REASONS: &[&str] = &[
+    "compiler-message",
+    "compiler-artifact",
+    "build-script-executed",
+    "build-finished",
+];
+
+/// Directory inside the target directory that contains matrix configurations.
+const MATRIX_DIR: &str = "remarks/matrix";
+
+/// Directory inside the target directory that contains the website of `cargo remark watch`.
+const WATCH_DIR: &str = "remarks/watch";
+
+/// Profile used to build the crate if no profile is specified.
+const DEFAULT_PROFILE: &str = "release";
 
 pub enum CargoSubcommand {
     Build,
     Wrap,
 }
 
+#[derive(Clone)]
 pub struct BuildOutput {
+    /// Target directory used by Cargo to build the crate.
+    pub target_dir: PathBuf,
+    /// Directory that contains the runs of the selected profile and target.
+    output_dir: PathBuf,
+    /// Directory of the current (or latest) run.
+    pub remark_dir: PathBuf,
     pub web_dir: PathBuf,
     pub source_dir: PathBuf,
     pub yaml_dir: PathBuf,
+    /// Directory containing the compiler flags used to compile crates that generated remarks.
+    pub flags_dir: PathBuf,
+    /// Directory containing the history of all runs.
+    pub history_dir: PathBuf,
+    pub profile: String,
+    /// Target triple.
+    pub target: String,
+    /// Configuration of the workspace.
+    pub config: RemarkConfig,
+}
+
+impl BuildOutput {
+    fn new(ctx: &CargoContext, target_dir: PathBuf, output: OutputDir, run_dir: PathBuf) -> Self {
+        Self {
+            target_dir,
+            output_dir: output.path,
+            web_dir: run_dir.join("web"),
+            source_dir: ctx.root_directory.clone(),
+            yaml_dir: run_dir.join("yaml"),
+            flags_dir: run_dir.join("flags"),
+            remark_dir: run_dir,
+            history_dir: ctx.target_directory.join("remarks").join("history"),
+            profile: output.profile,
+            target: output.target,
+            config: ctx.config.config.clone(),
+        }
+    }
+
+    /// Returns the output of the latest run stored in the same directory as this run.
+    /// Unlike [`get_build_output`], it does not execute Cargo, so it can be called repeatedly.
+    pub fn latest_run(&self) -> anyhow::Result<BuildOutput> {
+        let run_dir = find_latest_run(&self.output_dir)?;
+        Ok(Self {
+            web_dir: run_dir.join("web"),
+            yaml_dir: run_dir.join("yaml"),
+            flags_dir: run_dir.join("flags"),
+            remark_dir: run_dir,
+            ..self.clone()
+        })
+    }
 }
 
-pub fn run_cargo(subcmd: CargoSubcommand, cargo_args: Vec<String>) -> anyhow::Result<BuildOutput> {
-    let ctx = get_cargo_ctx()?;
-    let remark_dir = ctx.get_target_directory(Path::new("remarks"))?;
+#[derive(Default)]
+pub struct RemarkBuildOptions {
+    /// Packages outside of the workspace (dependencies) that should also generate remarks.
+    /// If empty, the packages from the configuration are used.
+    pub remark_packages: Vec<String>,
+    /// LLVM passes that should generate remarks.
+    /// If empty, the passes from the configuration are used, or all passes if none are configured.
+    pub passes: Vec<String>,
+    /// Build in the normal Cargo target directory instead of a dedicated one.
+    pub shared_target_dir: bool,
+    pub collection: RemarkCollection,
+    /// Name of a `cargo remark matrix` configuration. Its remarks and build artifacts are stored
+    /// separately from the remarks of normal runs.
+    pub configuration: Option<String>,
+    /// Additional rustc flags passed to the crates that generate remarks.
+    pub rustflags: Vec<String>,
+}
 
-    let yaml_dir = ensure_directory(&remark_dir.join("yaml"))?;
+/// Describes how are remarks collected from rustc.
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+pub enum RemarkCollection {
+    /// rustc writes remark files into a directory (`-Zremark-dir`).
+    #[default]
+    RemarkDir,
+    /// rustc prints remarks as diagnostics, which are collected from the JSON messages of Cargo.
+    /// This also works with stable toolchains, but the remarks have a reduced fidelity.
+    Diagnostics,
+}
+
+/// Cargo options that determine which crate is built, how, and where are its remarks stored.
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct BuildSelection {
+    /// Path to `Cargo.toml` of the crate.
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+    /// Cargo profile used to build the crate.
+    /// Defaults to `release`.
+    #[arg(long)]
+    pub profile: Option<String>,
+    /// Target triple for which the crate is built.
+    /// Defaults to the host triple.
+    #[arg(long)]
+    pub target: Option<String>,
+    /// Cargo target directory.
+    #[arg(long)]
+    pub target_dir: Option<PathBuf>,
+}
+
+impl BuildSelection {
+    fn profile(&self) -> &str {
+        self.profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    /// Returns Cargo arguments that select the same crate, profile, target and target directory.
+    pub fn to_cargo_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(ref manifest_path) = self.manifest_path {
+            args.push(format!("--manifest-path={}", manifest_path.display()));
+        }
+        if let Some(ref profile) = self.profile {
+            args.push(format!("--profile={profile}"));
+        }
+        if let Some(ref target) = self.target {
+            args.push(format!("--target={target}"));
+        }
+        if let Some(ref target_dir) = self.target_dir {
+            args.push(format!("--target-dir={}", target_dir.display()));
+        }
+        args
+    }
+}
+
+/// Finds the directories of the latest `cargo remark` run of the selected crate.
+pub fn get_build_output(selection: &BuildSelection) -> anyhow::Result<BuildOutput> {
+    let ctx = get_cargo_ctx(selection)?;
+    let output = get_output_dir(&ctx, selection, None)?;
+    let run_dir = find_latest_run(&output.path)?;
+    Ok(BuildOutput::new(
+        &ctx,
+        get_build_target_dir(&ctx, selection, false, None)?,
+        output,
+        run_dir,
+    ))
+}
+
+/// Loads the configuration of the selected workspace from Cargo metadata and `remark.toml`.
+pub fn load_config(selection: &BuildSelection) -> anyhow::Result<LoadedConfig> {
+    Ok(get_cargo_ctx(selection)?.config)
+}
+
+/// Returns the directory that contains the remarks of `cargo remark matrix` configurations.
+pub fn get_matrix_dir(selection: &BuildSelection) -> anyhow::Result<PathBuf> {
+    get_cargo_ctx(selection)?.get_target_directory(Path::new(MATRIX_DIR))
+}
+
+/// Returns the root directory of the selected workspace.
+pub fn get_workspace_root(selection: &BuildSelection) -> anyhow::Result<PathBuf> {
+    Ok(get_cargo_ctx(selection)?.root_directory)
+}
+
+/// Returns the directory into which `cargo remark watch` renders its website.
+pub fn get_watch_dir(selection: &BuildSelection) -> anyhow::Result<PathBuf> {
+    get_cargo_ctx(selection)?.get_target_directory(Path::new(WATCH_DIR))
+}
+
+/// Returns the build selection of the Cargo arguments passed to `cargo remark`.
+pub fn parse_build_selection(cargo_args: &[String]) -> BuildSelection {
+    parse_cargo_args(cargo_args.to_vec()).selection
+}
+
+/// Returns the `remarks` directory inside the target directory of the current crate.
+pub fn get_remarks_dir() -> anyhow::Result<PathBuf> {
+    get_cargo_ctx(&BuildSelection::default())?.get_target_directory(Path::new("remarks"))
+}
+
+/// Directory that contains remark runs of a single profile and target.
+struct OutputDir {
+    path: PathBuf,
+    profile: String,
+    target: String,
+}
+
+fn get_output_dir(
+    ctx: &CargoContext,
+    selection: &BuildSelection,
+    configuration: Option<&str>,
+) -> anyhow::Result<OutputDir> {
+    let target = match selection.target {
+        Some(ref target) => target.clone(),
+        None => get_host_triple()?,
+    };
+    let profile = selection.profile().to_string();
+    let remarks_dir = match configuration {
+        Some(configuration) => Path::new(MATRIX_DIR).join(configuration),
+        None => PathBuf::from("remarks"),
+    };
+    // Remarks of each profile and target are stored separately
+    let path = ctx
+        .get_target_directory(&remarks_dir)?
+        .join(&profile)
+        .join(&target);
+    ensure_directory(&path)?;
+    Ok(OutputDir {
+        path,
+        profile,
+        target,
+    })
+}
+
+fn get_build_target_dir(
+    ctx: &CargoContext,
+    selection: &BuildSelection,
+    shared_target_dir: bool,
+    configuration: Option<&str>,
+) -> anyhow::Result<PathBuf> {
+    // Remark builds use different compiler flags, so by default they are stored in a separate
+    // directory, to avoid invalidating the normal build cache.
+    if shared_target_dir || selection.target_dir.is_some() {
+        return Ok(ctx.target_directory.clone());
+    }
+    match configuration {
+        // Each matrix configuration has its own build cache
+        Some(configuration) => {
+            ctx.get_target_directory(&Path::new(MATRIX_DIR).join(configuration).join("build"))
+        }
+        None => ctx.get_target_directory(Path::new("remarks/build")),
+    }
+}
+
+pub fn run_cargo(
+    subcmd: CargoSubcommand,
+    cargo_args: Vec<String>,
+    options: RemarkBuildOptions,
+) -> anyhow::Result<BuildOutput> {
+    let cargo_args = parse_cargo_args(cargo_args);
+    let selection = &cargo_args.selection;
+
+    let ctx = get_cargo_ctx(selection)?;
+    let configuration = options.configuration.as_deref();
+    let target_dir =
+        get_build_target_dir(&ctx, selection, options.shared_target_dir, configuration)?;
+    let _lock = lock_remark_dir(&ctx.get_target_directory(Path::new("remarks"))?)?;
+
+    let output_dir = get_output_dir(&ctx, selection, configuration)?;
+    let runs_dir = output_dir.path.clone();
+    let run_dir = create_run_dir(&runs_dir)?;
+    let output = BuildOutput::new(&ctx, target_dir, output_dir, run_dir);
+    let yaml_dir = ensure_directory(&output.yaml_dir)?;
+    ensure_directory(&output.flags_dir)?;
 
     log::info!(
         "Optimization remarks will be stored into {}.",
@@ -33,37 +305,112 @@ pub fn run_cargo(subcmd: CargoSubcommand, cargo_args: Vec<String>) -> anyhow::Re
 
     let mut cmd = match subcmd {
         CargoSubcommand::Build => {
-            let cargo_args = parse_cargo_args(cargo_args);
             let mut cargo = Command::new("cargo");
             cargo
                 .arg("build")
-                .arg("--release")
-                .stdin(Stdio::null())
-                .args(cargo_args.filtered);
+                .arg("--profile")
+                .arg(selection.profile())
+                .stdin(Stdio::null());
+            if options.collection == RemarkCollection::Diagnostics {
+                cargo.arg(MESSAGE_FORMAT_ARG);
+            }
+            if let Some(ref manifest_path) = selection.manifest_path {
+                cargo.arg("--manifest-path").arg(manifest_path);
+            }
+            if let Some(ref target) = selection.target {
+                cargo.arg("--target").arg(target);
+            }
+            cargo.args(cargo_args.filtered);
             cargo
         }
         CargoSubcommand::Wrap => {
-            if cargo_args.is_empty() {
+            if cargo_args.original.is_empty() {
                 return Err(anyhow::anyhow!("You have to enter a command after `--` that will be executed when using `wrap`."));
             };
 
             let mut cmd = Command::new("cargo");
-            cmd.args(&cargo_args).stdin(Stdio::null());
+            match options.collection {
+                RemarkCollection::RemarkDir => {
+                    cmd.args(&cargo_args.original);
+                }
+                RemarkCollection::Diagnostics => {
+                    cmd.args(diagnostics_wrap_args(&cargo_args.original)?);
+                }
+            }
+            cmd.stdin(Stdio::null());
             cmd
         }
     };
 
-    let flags = format!(
-        "-Cremark=all -Zremark-dir={} -Cdebuginfo=1",
-        yaml_dir.display()
-    );
-    set_cargo_env(&mut cmd, &flags);
+    log::debug!("Using target directory {}", output.target_dir.display());
+    cmd.env("CARGO_TARGET_DIR", &output.target_dir);
 
-    let status = cmd
-        .spawn()
-        .map_err(|error| anyhow::anyhow!("Cannot start cargo: {error:?}"))?
-        .wait()
-        .map_err(|error| anyhow::anyhow!("Cargo failed: {error:?}"))?;
+    let remark_packages = if options.remark_packages.is_empty() {
+        output.config.remark_packages.clone()
+    } else {
+        options.remark_packages
+    };
+    let packages: Vec<String> = ctx
+        .workspace_packages
+        .iter()
+        .chain(&remark_packages)
+        .cloned()
+        .collect();
+    // Cargo does not recompile crates that are up to date, which would leave them without
+    // remarks, so the selected packages are always rebuilt.
+    let clean_profile = match subcmd {
+        CargoSubcommand::Build => selection.profile(),
+        CargoSubcommand::Wrap => match selection.profile {
+            Some(ref profile) => profile.as_str(),
+            None if cargo_args.original.iter().any(|arg| arg == "--release") => "release",
+            None => "dev",
+        },
+    };
+    if output.target_dir == ctx.target_directory && !packages.is_empty() {
+        log::warn!(
+            "The build artifacts of {} for profile `{clean_profile}` in the shared target directory {} are removed, so that remarks are generated for them. Your regular builds will recompile them. Do not use `--shared-target-dir` to build remarks in a dedicated directory.",
+            packages.join(", "),
+            cli_format_path(&output.target_dir)
+        );
+    }
+    clean_packages(&packages, selection, clean_profile, &output.target_dir)?;
+
+    // The flags are passed to rustc through a wrapper, so that they do not override the
+    // rustflags configured in environment variables or in Cargo config files.
+    let passes = if options.passes.is_empty() {
+        &output.config.passes
+    } else {
+        &options.passes
+    };
+    let mut flags = remark_flags(passes);
+    if options.collection == RemarkCollection::RemarkDir {
+        flags.push(format!("-Zremark-dir={}", yaml_dir.display()));
+    }
+    flags.push("-Cdebuginfo=1".to_string());
+    flags.extend(options.rustflags);
+    log::debug!("Remark flags: {}", flags.join(" "));
+    configure_wrapper(
+        &mut cmd,
+        WrapperConfig {
+            flags,
+            workspace_packages: ctx.workspace_packages,
+            extra_packages: remark_packages,
+            flags_dir: output.flags_dir.clone(),
+        },
+    )?;
+
+    let status = match options.collection {
+        RemarkCollection::RemarkDir => cmd
+            .spawn()
+            .map_err(|error| anyhow::anyhow!("Cannot start cargo: {error:?}"))?
+            .wait()
+            .map_err(|error| anyhow::anyhow!("Cargo failed: {error:?}"))?,
+        RemarkCollection::Diagnostics => collect_diagnostics(
+            &mut cmd,
+            &output.source_dir,
+            &yaml_dir.join(DIAGNOSTICS_FILE_NAME),
+        )?,
+    };
     if !status.success() {
         return Err(anyhow::anyhow!(
             "Cargo build failed: exit code {}",
@@ -73,48 +420,226 @@ pub fn run_cargo(subcmd: CargoSubcommand, cargo_args: Vec<String>) -> anyhow::Re
 
     log::info!("Optimization remarks sucessfully generated");
 
-    let web_dir = ensure_directory(&remark_dir.join("web"))?;
-    Ok(BuildOutput {
-        web_dir,
-        source_dir: ctx.root_directory,
-        yaml_dir,
-    })
+    // The sources can be edited after the build, so fingerprints are computed only once
+    let options = RemarkLoadOptions {
+        source_dir: output.source_dir.clone(),
+        rustc_source_root: find_rustc_source_root(&output.config)?,
+        path_mappings: output.config.path_mappings()?,
+        ..Default::default()
+    };
+    if let Err(error) =
+        time_block_log_info("Fingerprinting", || store_fingerprints(&yaml_dir, options))
+    {
+        log::warn!("Cannot store remark fingerprints: {error:?}");
+    }
+
+    ensure_directory(&output.web_dir)?;
+    mark_latest_run(&runs_dir, &output.remark_dir)?;
+    if output.config.keep_runs > 0 {
+        match prune_runs(&runs_dir, output.config.keep_runs) {
+            Ok(removed) if !removed.is_empty() => {
+                log::debug!("Removed {} old run(s)", removed.len());
+            }
+            Ok(_) => {}
+            Err(error) => log::warn!("Cannot remove old runs: {error:?}"),
+        }
+    }
+    Ok(output)
+}
+
+/// Returns the arguments of a wrapped Cargo command that make it print JSON messages.
+///
+/// The message format has to be passed to the Cargo subcommand, so only built-in subcommands that
+/// accept it are supported. External subcommands (e.g. `cargo pgo`) have their own arguments.
+fn diagnostics_wrap_args(args: &[String]) -> anyhow::Result<Vec<String>> {
+    let subcommand = &args[0];
+    if !MESSAGE_FORMAT_SUBCOMMANDS.contains(&subcommand.as_str()) {
+        return Err(anyhow::anyhow!(
+            "Remarks cannot be collected from diagnostics of `cargo {subcommand}`. When remarks are collected from diagnostics, `wrap` only supports `build`, `check`, `test`, `run` and `bench`. Use a toolchain that supports `-Zremark-dir` to wrap other commands."
+        ));
+    }
+    let mut wrapped = vec![subcommand.clone(), MESSAGE_FORMAT_ARG.to_string()];
+    wrapped.extend_from_slice(&args[1..]);
+    Ok(wrapped)
+}
+
+/// Returns true if `message` is a JSON message printed by Cargo, rather than e.g. JSON printed
+/// by an executed program or test.
+fn is_cargo_message(message: &serde_json::Value) -> bool {
+    message
+        .get("reason")
+        .and_then(|reason| reason.as_str())
+        .is_some_and(|reason| CARGO_MESSAGE_REASONS.contains(&reason))
 }
 
-pub fn get_rustc_source_root() -> anyhow::Result<RustcSourceRoot> {
+/// Runs `cmd`, which prints JSON messages of Cargo to stdout, and stores the remarks found in the
+/// messages into the diagnostics file at `path`. Other compiler messages are printed to stderr,
+/// and the output that does not contain Cargo messages (e.g. of executed tests) to stdout.
+fn collect_diagnostics(
+    cmd: &mut Command,
+    source_dir: &Path,
+    path: &Path,
+) -> anyhow::Result<ExitStatus> {
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|error| anyhow::anyhow!("Cannot start cargo: {error:?}"))?;
+    let stdout = child.stdout.take().expect("Cargo stdout is not piped");
+
+    let file = File::create(path)
+        .with_context(|| format!("Cannot create diagnostics file {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    let mut count = 0;
+    for line in BufReader::new(stdout).lines() {
+        let line = line.context("Cannot read Cargo output")?;
+        let message = match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(message) if is_cargo_message(&message) => message,
+            _ => {
+                println!("{line}");
+                continue;
+            }
+        };
+        if let Some(remark) = DiagnosticRemark::from_cargo_message(&message, source_dir) {
+            serde_json::to_writer(&mut writer, &remark)?;
+            writeln!(writer)?;
+            count += 1;
+        } else if let Some(rendered) = message
+            .get("message")
+            .and_then(|message| message.get("rendered"))
+            .and_then(|rendered| rendered.as_str())
+        {
+            eprint!("{rendered}");
+        }
+    }
+    writer.flush()?;
+    log::debug!("Collected {count} remark(s) from diagnostics");
+
+    child
+        .wait()
+        .map_err(|error| anyhow::anyhow!("Cargo failed: {error:?}"))
+}
+
+/// Returns flags that make rustc generate remarks for the selected LLVM passes.
+fn remark_flags(passes: &[String]) -> Vec<String> {
+    if passes.is_empty() {
+        return vec!["-Cremark=all".to_string()];
+    }
+    // rustc accumulates passes from repeated `-Cremark` flags
+    passes
+        .iter()
+        .map(|pass| format!("-Cremark={}", pass.trim()))
+        .collect()
+}
+
+/// Removes build artifacts of `packages`, so that cargo will compile them again.
+fn clean_packages(
+    packages: &[String],
+    selection: &BuildSelection,
+    profile: &str,
+    target_dir: &Path,
+) -> anyhow::Result<()> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+    log::debug!("Cleaning packages {}", packages.join(", "));
+
+    let mut cmd = Command::new("cargo");
+    cmd.arg("clean").arg("--profile").arg(profile);
+    for package in packages {
+        cmd.arg("-p").arg(package);
+    }
+    if let Some(ref manifest_path) = selection.manifest_path {
+        cmd.arg("--manifest-path").arg(manifest_path);
+    }
+    if let Some(ref target) = selection.target {
+        cmd.arg("--target").arg(target);
+    }
+    let output = cmd
+        .env("CARGO_TARGET_DIR", target_dir)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| anyhow::anyhow!("Cannot start cargo: {error:?}"))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Cannot clean packages {}: {}",
+            packages.join(", "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Finds rustc sources in the sysroot of the `sysroot` configuration option, or in the sysroot
+/// of the used toolchain.
+pub fn find_rustc_source_root(config: &RemarkConfig) -> anyhow::Result<Option<RustcSourceRoot>> {
+    if let Some(ref sysroot) = config.sysroot {
+        return Ok(Some(RustcSourceRoot::from_sysroot(sysroot.clone())?));
+    }
+    match get_rustc_sysroot().and_then(RustcSourceRoot::from_sysroot) {
+        Ok(root) => Ok(Some(root)),
+        Err(error) => {
+            log::warn!("Cannot find rustc source root: {error:?}");
+            Ok(None)
+        }
+    }
+}
+
+fn get_rustc_sysroot() -> anyhow::Result<PathBuf> {
     let output = Command::new("rustc")
         .arg("--print")
         .arg("sysroot")
         .output()
         .context("Cannot get sysroot from `rustc`")?;
-    let sysroot = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
-    RustcSourceRoot::from_sysroot(sysroot)
-}
-
-fn set_cargo_env(command: &mut Command, flags: &str) {
-    use std::fmt::Write;
-
-    let mut rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
-    write!(&mut rustflags, " {}", flags).unwrap();
-
-    command.env("RUSTFLAGS", rustflags);
+    Ok(PathBuf::from(
+        String::from_utf8_lossy(&output.stdout).trim(),
+    ))
 }
 
 #[derive(Debug, Default)]
 struct CargoArgs {
+    /// Arguments without the ones that are handled by `cargo-remark`.
     filtered: Vec<String>,
+    /// All arguments, as they were passed by the user.
+    original: Vec<String>,
+    selection: BuildSelection,
 }
 
 fn parse_cargo_args(cargo_args: Vec<String>) -> CargoArgs {
-    let mut args = CargoArgs::default();
+    let mut args = CargoArgs {
+        original: cargo_args.clone(),
+        ..Default::default()
+    };
 
-    for arg in cargo_args {
-        match arg.as_str() {
-            // Skip `--release`, we will pass it by ourselves.
-            "--release" => {
-                log::warn!("Do not pass `--release` manually, it will be added automatically by `cargo-remark`");
-            }
-            _ => args.filtered.push(arg),
+    let mut iter = cargo_args.into_iter();
+    while let Some(arg) = iter.next() {
+        // Skip `--release`, we will pass the profile by ourselves.
+        if arg == "--release" {
+            log::warn!("Do not pass `--release` manually, it will be added automatically by `cargo-remark`");
+            continue;
+        }
+
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        if !matches!(
+            name.as_str(),
+            "--profile" | "--manifest-path" | "--target" | "--target-dir"
+        ) {
+            args.filtered.push(arg);
+            continue;
+        }
+
+        let Some(value) = value.or_else(|| iter.next()) else {
+            log::warn!("Missing value for `{name}`");
+            continue;
+        };
+        match name.as_str() {
+            "--profile" => args.selection.profile = Some(value),
+            "--manifest-path" => args.selection.manifest_path = Some(PathBuf::from(value)),
+            "--target" => args.selection.target = Some(value),
+            "--target-dir" => args.selection.target_dir = Some(PathBuf::from(value)),
+            _ => unreachable!(),
         }
     }
     args
@@ -123,6 +648,9 @@ fn parse_cargo_args(cargo_args: Vec<String>) -> CargoArgs {
 struct CargoContext {
     target_directory: PathBuf,
     root_directory: PathBuf,
+    /// Names of workspace member packages.
+    workspace_packages: Vec<String>,
+    config: LoadedConfig,
 }
 
 impl CargoContext {
@@ -133,14 +661,130 @@ impl CargoContext {
     }
 }
 
-/// Finds Cargo metadata from the current directory.
-fn get_cargo_ctx() -> anyhow::Result<CargoContext> {
-    let cmd = cargo_metadata::MetadataCommand::new();
+/// Finds Cargo metadata of the selected crate.
+fn get_cargo_ctx(selection: &BuildSelection) -> anyhow::Result<CargoContext> {
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    if let Some(ref manifest_path) = selection.manifest_path {
+        cmd.manifest_path(manifest_path);
+    }
+    if let Some(ref target_dir) = selection.target_dir {
+        cmd.env("CARGO_TARGET_DIR", target_dir);
+    }
     let metadata = cmd
         .exec()
         .map_err(|error| anyhow::anyhow!("Cannot get cargo metadata: {:?}", error))?;
+    let workspace_packages = metadata
+        .workspace_packages()
+        .into_iter()
+        .map(|package| package.name.clone())
+        .collect();
+
+    let root_directory = metadata.workspace_root.clone().into_std_path_buf();
+    let mut config = ConfigLoader::default();
+    config.add_metadata(
+        &metadata.workspace_metadata,
+        &root_directory,
+        "[workspace.metadata.remark] in Cargo.toml",
+    )?;
+    if let Some(package) = metadata.root_package() {
+        let package_dir = package
+            .manifest_path
+            .parent()
+            .map(|dir| dir.as_std_path())
+            .unwrap_or(&root_directory);
+        config.add_metadata(
+            &package.metadata,
+            package_dir,
+            &format!("[package.metadata.remark] of {}", package.name),
+        )?;
+    }
+    config.add_file(&root_directory.join(CONFIG_FILE_NAME))?;
+
     Ok(CargoContext {
         target_directory: metadata.target_directory.into_std_path_buf(),
-        root_directory: metadata.workspace_root.into_std_path_buf(),
+        root_directory,
+        workspace_packages,
+        config: config.finish()?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cargo::{diagnostics_wrap_args, is_cargo_message, parse_cargo_args, remark_flags};
+    use std::path::Path;
+
+    #[test]
+    fn wrap_with_diagnostics() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics_wrap_args(&args(&["run", "--release", "--", "foo"])).unwrap(),
+            args(&["run", "--message-format=json", "--release", "--", "foo"])
+        );
+        assert!(diagnostics_wrap_args(&args(&["pgo", "optimize"])).is_err());
+    }
+
+    #[test]
+    fn cargo_messages() {
+        let message = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+        assert!(is_cargo_message(&message(
+            r#"{"reason": "compiler-message", "message": {}}"#
+        )));
+        assert!(is_cargo_message(&message(
+            r#"{"reason": "build-finished", "success": true}"#
+        )));
+        // JSON printed by the user's program
+        assert!(!is_cargo_message(&message(r#"{"name": "foo"}"#)));
+        assert!(!is_cargo_message(&message(r#"{"reason": "timeout"}"#)));
+        assert!(!is_cargo_message(&message("[1, 2]")));
+    }
+
+    #[test]
+    fn parse_selection() {
+        let args = parse_cargo_args(
+            [
+                "--release",
+                "--profile=profiling",
+                "--features",
+                "foo",
+                "--target",
+                "x86_64-unknown-linux-gnu",
+                "--manifest-path",
+                "foo/Cargo.toml",
+                "--target-dir=/tmp/target",
+            ]
+            .map(|arg| arg.to_string())
+            .to_vec(),
+        );
+        assert_eq!(args.filtered, vec!["--features", "foo"]);
+        assert_eq!(args.original.len(), 9);
+        let selection = args.selection;
+        assert_eq!(selection.profile(), "profiling");
+        assert_eq!(
+            selection.target.as_deref(),
+            Some("x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            selection.manifest_path.as_deref(),
+            Some(Path::new("foo/Cargo.toml"))
+        );
+        assert_eq!(
+            selection.target_dir.as_deref(),
+            Some(Path::new("/tmp/target"))
+        );
+    }
+
+    #[test]
+    fn parse_default_profile() {
+        let args = parse_cargo_args(vec!["--features".to_string(), "foo".to_string()]);
+        assert_eq!(args.selection.profile(), "release");
+    }
+
+    #[test]
+    fn remark_flags_for_passes() {
+        assert_eq!(remark_flags(&[]), vec!["-Cremark=all"]);
+        assert_eq!(
+            remark_flags(&["inline".to_string(), "gvn".to_string()]),
+            vec!["-Cremark=inline", "-Cremark=gvn"]
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Profile-guided optimization workflow of `cargo remark pgo`.
+//!
+//! The crate is first built with PGO instrumentation in a dedicated target directory, then a
+//! workload gathers profiles, which are merged with `llvm-profdata`. The merged profile is then
+//! used by the remark build, so that the remarks contain hotness.
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use cargo_remark::utils::cli::cli_format_path;
+use cargo_remark::utils::io::ensure_directory;
+
+use crate::cargo::version::get_host_triple;
+use crate::cargo::wrapper::{configure_wrapper, WrapperConfig};
+use crate::cargo::{get_cargo_ctx, get_rustc_sysroot, BuildSelection};
+
+/// Directory inside the target directory that contains instrumented builds and profiles.
+const PGO_DIR: &str = "remarks/pgo";
+
+/// Directories used by the PGO workflow.
+pub struct PgoDirs {
+    /// Target directory of the instrumented build.
+    pub target_dir: PathBuf,
+    /// Directory into which the workload stores raw profiles.
+    pub profiles_dir: PathBuf,
+    /// Path to the merged profile.
+    pub merged_profile: PathBuf,
+    /// Directory where the flags of the instrumented crates are recorded.
+    pub flags_dir: PathBuf,
+}
+
+/// Prepares the directories of the PGO workflow, and removes profiles of previous workloads.
+pub fn prepare_pgo_dirs(selection: &BuildSelection) -> anyhow::Result<PgoDirs> {
+    let ctx = get_cargo_ctx(selection)?;
+    let pgo_dir = ctx.get_target_directory(Path::new(PGO_DIR))?;
+    let profiles_dir = pgo_dir.join("profiles");
+    if profiles_dir.is_dir() {
+        std::fs::remove_dir_all(&profiles_dir).with_context(|| {
+            format!("Cannot remove old profiles from {}", profiles_dir.display())
+        })?;
+    }
+    std::fs::create_dir_all(&profiles_dir)?;
+    let flags_dir = ensure_directory(&pgo_dir.join("flags"))?;
+    Ok(PgoDirs {
+        target_dir: pgo_dir.join("build"),
+        profiles_dir,
+        merged_profile: pgo_dir.join("merged.profdata"),
+        flags_dir,
+    })
+}
+
+/// Builds the crate with PGO instrumentation, and returns the directory that contains the
+/// instrumented binaries.
+pub fn build_instrumented(selection: &BuildSelection, dirs: &PgoDirs) -> anyhow::Result<PathBuf> {
+    let mut cmd = Command::new("cargo");
+    cmd.arg("build")
+        .arg("--profile")
+        .arg(selection.profile())
+        .stdin(Stdio::null())
+        .env("CARGO_TARGET_DIR", &dirs.target_dir);
+    if let Some(ref manifest_path) = selection.manifest_path {
+        cmd.arg("--manifest-path").arg(manifest_path);
+    }
+    if let Some(ref target) = selection.target {
+        cmd.arg("--target").arg(target);
+    }
+    // The instrumentation flag is passed through the wrapper, so that it does not override the
+    // rustflags configured in environment variables or in Cargo config files. The same crates
+    // are instrumented as those that later use the profile in the remark build.
+    let ctx = get_cargo_ctx(selection)?;
+    configure_wrapper(
+        &mut cmd,
+        WrapperConfig {
+            flags: vec![format!(
+                "-Cprofile-generate={}",
+                dirs.profiles_dir.display()
+            )],
+            workspace_packages: ctx.workspace_packages,
+            extra_packages: ctx.config.config.remark_packages,
+            flags_dir: dirs.flags_dir.clone(),
+        },
+    )?;
+
+    log::info!("Building the crate with PGO instrumentation");
+    let status = cmd
+        .status()
+        .map_err(|error| anyhow::anyhow!("Cannot start cargo: {error:?}"))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "Instrumented build failed: exit code {}",
+            status.code().unwrap_or(1)
+        ));
+    }
+
+    let mut bin_dir = dirs.target_dir.clone();
+    if let Some(ref target) = selection.target {
+        bin_dir.push(target);
+    }
+    bin_dir.push(profile_dir_name(selection.profile()));
+    Ok(bin_dir)
+}
+
+/// Returns the name of the directory into which Cargo stores the artifacts of `profile`.
+fn profile_dir_name(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        profile => profile,
+    }
+}
+
+/// Runs the workload, with the directory of the instrumented binaries prepended to `PATH`.
+pub fn run_workload(workload: &[String], bin_dir: &Path, dirs: &PgoDirs) -> anyhow::Result<()> {
+    let Some((program, args)) = workload.split_first() else {
+        return Err(anyhow::anyhow!(
+            "You have to enter a workload command after `--`."
+        ));
+    };
+
+    let mut paths = vec![bin_dir.to_path_buf()];
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+    let path: OsString = std::env::join_paths(paths).context("Cannot extend PATH")?;
+
+    log::info!("Running workload `{}`", workload.join(" "));
+    let status = Command::new(program)
+        .args(args)
+        .env("PATH", path)
+        .env("LLVM_PROFILE_FILE", dirs.profiles_dir.join("%m_%p.profraw"))
+        .stdin(Stdio::null())
+        .status()
+        .with_context(|| format!("Cannot start workload `{program}`"))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "Workload failed: exit code {}",
+            status.code().unwrap_or(1)
+        ));
+    }
+
+    if count_profiles(&dirs.profiles_dir)? == 0 {
+        return Err(anyhow::anyhow!(
+            "The workload did not produce any profiles. Make sure that it executes the instrumented binaries from {} (which is added to PATH).",
+            cli_format_path(bin_dir)
+        ));
+    }
+    Ok(())
+}
+
+fn count_profiles(dir: &Path) -> anyhow::Result<usize> {
+    Ok(std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "profraw"))
+        .count())
+}
+
+/// Merges the gathered profiles with `llvm-profdata`. If `llvm_profdata` is not specified, it is
+/// searched in the `llvm-tools` component of the used toolchain and then in `PATH`.
+pub fn merge_profiles(llvm_profdata: Option<PathBuf>, dirs: &PgoDirs) -> anyhow::Result<()> {
+    let llvm_profdata = llvm_profdata
+        .or_else(find_toolchain_llvm_profdata)
+        .unwrap_or_else(|| PathBuf::from("llvm-profdata"));
+    log::debug!("Using {}", llvm_profdata.display());
+
+    let output = Command::new(&llvm_profdata)
+        .arg("merge")
+        .arg("-o")
+        .arg(&dirs.merged_profile)
+        .arg(&dirs.profiles_dir)
+        .output()
+        .with_context(|| {
+            format!(
+                "Cannot execute {}. Install it with `rustup component add llvm-tools`, or pass its path with `--llvm-profdata`.",
+                llvm_profdata.display()
+            )
+        })?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Cannot merge profiles with {}: {}\nThe version of llvm-profdata has to match the LLVM version of rustc (`rustc -vV`), install it with `rustup component add llvm-tools`.",
+            llvm_profdata.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    log::info!(
+        "Profiles merged into {}",
+        cli_format_path(&dirs.merged_profile)
+    );
+    Ok(())
+}
+
+/// Finds `llvm-profdata` of the `llvm-tools` component of the used toolchain.
+fn find_toolchain_llvm_profdata() -> Option<PathBuf> {
+    let path = get_rustc_sysroot()
+        .ok()?
+        .join("lib")
+        .join("rustlib")
+        .join(get_host_triple().ok()?)
+        .join("bin")
+        .join(format!("llvm-profdata{}", std::env::consts::EXE_SUFFIX));
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cargo::pgo::profile_dir_name;
+
+    #[test]
+    fn profile_dirs() {
+        assert_eq!(profile_dir_name("dev"), "debug");
+        assert_eq!(profile_dir_name("release"), "release");
+        assert_eq!(profile_dir_name("bench"), "release");
+        assert_eq!(profile_dir_name("profiling"), "profiling");
+    }
+}
@@ -0,0 +1,175 @@
+//! Each `cargo remark` invocation stores its outputs into a separate run directory
+//! (`<output-dir>/runs/<timestamp>`), so that remarks of different builds are never mixed.
+//! The `latest` file in the output directory contains the name of the last successful run.
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use cargo_remark::utils::io::ensure_directory;
+
+const RUNS_DIR: &str = "runs";
+const LATEST_FILE: &str = "latest";
+const LOCK_FILE: &str = ".lock";
+
+/// Exclusive lock of a remark directory, which is released when it is dropped.
+pub struct RemarkDirLock {
+    _file: File,
+}
+
+/// Locks `dir`, so that concurrent `cargo remark` invocations do not overwrite each other.
+/// Blocks until the lock is acquired.
+pub fn lock_remark_dir(dir: &Path) -> anyhow::Result<RemarkDirLock> {
+    let path = dir.join(LOCK_FILE);
+    let file = File::create(&path)
+        .with_context(|| format!("Cannot create lock file {}", path.display()))?;
+    if file.try_lock().is_err() {
+        log::info!("Waiting for another `cargo remark` invocation to finish");
+        file.lock()
+            .with_context(|| format!("Cannot lock {}", path.display()))?;
+    }
+    Ok(RemarkDirLock { _file: file })
+}
+
+/// Creates a new, empty run directory inside `output_dir`.
+pub fn create_run_dir(output_dir: &Path) -> anyhow::Result<PathBuf> {
+    let runs_dir = ensure_directory(&output_dir.join(RUNS_DIR))?;
+    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let mut run_dir = runs_dir.join(&timestamp);
+    let mut index = 1;
+    while run_dir.exists() {
+        run_dir = runs_dir.join(format!("{timestamp}-{index}"));
+        index += 1;
+    }
+    ensure_directory(&run_dir)
+        .with_context(|| format!("Cannot create run directory {}", run_dir.display()))
+}
+
+/// Marks `run_dir` as the latest run of `output_dir`.
+pub fn mark_latest_run(output_dir: &Path, run_dir: &Path) -> anyhow::Result<()> {
+    let name = run_dir
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid run directory {}", run_dir.display()))?;
+    std::fs::write(
+        output_dir.join(LATEST_FILE),
+        name.to_string_lossy().as_bytes(),
+    )
+    .context("Cannot store the latest run")
+}
+
+/// Removes the oldest runs of `output_dir`, so that at most `keep` runs remain, and returns the
+/// removed run directories. The latest run is never removed.
+pub fn prune_runs(output_dir: &Path, keep: usize) -> anyhow::Result<Vec<PathBuf>> {
+    let runs_dir = output_dir.join(RUNS_DIR);
+    let latest = find_latest_run(output_dir).ok();
+    let mut runs: Vec<PathBuf> = std::fs::read_dir(&runs_dir)
+        .with_context(|| format!("Cannot read run directory {}", runs_dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    runs.sort_by_key(|path| run_order(path));
+
+    let count = runs.len().saturating_sub(keep.max(1));
+    let mut removed = vec![];
+    for run in runs.into_iter().take(count) {
+        if latest.as_ref() == Some(&run) {
+            continue;
+        }
+        std::fs::remove_dir_all(&run)
+            .with_context(|| format!("Cannot remove run directory {}", run.display()))?;
+        removed.push(run);
+    }
+    Ok(removed)
+}
+
+/// Orders runs by their timestamp, and then by the index that distinguishes runs started in the
+/// same second (`<timestamp>-<index>`).
+fn run_order(path: &Path) -> (String, usize) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let timestamp_len = "YYYY-MM-DD_HH-MM-SS".len().min(name.len());
+    let (timestamp, index) = name.split_at(timestamp_len);
+    let index = index.trim_start_matches('-').parse().unwrap_or(0);
+    (timestamp.to_string(), index)
+}
+
+/// Finds the directory of the latest run stored in `output_dir`.
+pub fn find_latest_run(output_dir: &Path) -> anyhow::Result<PathBuf> {
+    let latest = std::fs::read_to_string(output_dir.join(LATEST_FILE)).map_err(|_| {
+        anyhow::anyhow!(
+            "No remarks found in {}, run `cargo remark build` first",
+            output_dir.display()
+        )
+    })?;
+    let run_dir = output_dir.join(RUNS_DIR).join(latest.trim());
+    if !run_dir.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Latest run directory {} does not exist",
+            run_dir.display()
+        ));
+    }
+    Ok(run_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cargo::run::{create_run_dir, find_latest_run, mark_latest_run, prune_runs};
+
+    #[test]
+    fn latest_run() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(find_latest_run(dir.path()).is_err());
+
+        let first = create_run_dir(dir.path()).unwrap();
+        let second = create_run_dir(dir.path()).unwrap();
+        assert_ne!(first, second);
+        assert!(second.is_dir());
+
+        mark_latest_run(dir.path(), &first).unwrap();
+        assert_eq!(find_latest_run(dir.path()).unwrap(), first);
+        mark_latest_run(dir.path(), &second).unwrap();
+        assert_eq!(find_latest_run(dir.path()).unwrap(), second);
+    }
+
+    #[test]
+    fn keep_recent_runs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let runs_dir = dir.path().join("runs");
+        for name in [
+            "2023-08-20_10-00-00",
+            "2023-08-20_10-00-00-1",
+            "2023-08-20_10-00-00-10",
+            "2023-08-20_10-00-00-2",
+            "2023-08-21_09-00-00",
+        ] {
+            std::fs::create_dir_all(runs_dir.join(name)).unwrap();
+        }
+        // The latest run is kept even if it is not the most recent one
+        mark_latest_run(dir.path(), &runs_dir.join("2023-08-20_10-00-00")).unwrap();
+
+        let removed = prune_runs(dir.path(), 2).unwrap();
+        assert_eq!(
+            removed,
+            vec![
+                runs_dir.join("2023-08-20_10-00-00-1"),
+                runs_dir.join("2023-08-20_10-00-00-2")
+            ]
+        );
+        let mut remaining: Vec<_> = std::fs::read_dir(&runs_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            vec![
+                "2023-08-20_10-00-00",
+                "2023-08-20_10-00-00-10",
+                "2023-08-21_09-00-00"
+            ]
+        );
+    }
+}
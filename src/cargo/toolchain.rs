@@ -0,0 +1,207 @@
+//! Selection of the Rust toolchain that is used to build the crate and to generate remarks.
+//!
+//! The toolchain is selected by setting `RUSTUP_TOOLCHAIN` for the whole process, so that all
+//! executed rustup proxies (`cargo`, `rustc`) consistently use the same toolchain.
+use std::ffi::OsString;
+use std::process::Command;
+
+use crate::cargo::version::{check_remark_dir_support, get_rustc_short_version};
+use crate::cargo::RemarkCollection;
+
+const TOOLCHAIN_ENV: &str = "RUSTUP_TOOLCHAIN";
+/// Set by rustup proxies to the reason why `RUSTUP_TOOLCHAIN` was selected (e.g. `cli`, `env`,
+/// `toolchain-file` or `default`).
+const TOOLCHAIN_SOURCE_ENV: &str = "RUSTUP_TOOLCHAIN_SOURCE";
+
+const DIAGNOSTICS_FALLBACK_HINT: &str = "Remarks will be collected from rustc diagnostics, with a reduced fidelity. For complete remarks, install a nightly toolchain newer than 4. 7. 2023 (`rustup toolchain install nightly`).";
+
+/// Removes a `+<toolchain>` argument located directly after the subcommand name
+/// (`cargo remark +nightly build`) from `args`, and returns the toolchain.
+pub fn extract_toolchain_arg(args: &mut Vec<OsString>) -> Option<String> {
+    // Cargo passes the name of the subcommand (`remark`) as the first argument
+    let index = match args.get(1) {
+        Some(arg) if arg == "remark" => 2,
+        _ => 1,
+    };
+    let toolchain = args
+        .get(index)?
+        .to_str()?
+        .strip_prefix('+')
+        .map(|toolchain| toolchain.to_string())?;
+    args.remove(index);
+    Some(toolchain)
+}
+
+/// Makes all subsequently executed `cargo` and `rustc` commands use `toolchain`.
+/// The toolchain is marked as selected on the command line, like `cargo +<toolchain>` does.
+pub fn use_toolchain(toolchain: &str) {
+    log::debug!("Using toolchain {toolchain}");
+    std::env::set_var(TOOLCHAIN_ENV, toolchain);
+    std::env::set_var(TOOLCHAIN_SOURCE_ENV, "cli");
+}
+
+/// Returns the toolchain that was selected explicitly by the user, either with `+<toolchain>`
+/// (`cargo +<toolchain> remark` or `cargo remark +<toolchain>`) or with `--toolchain`.
+///
+/// `RUSTUP_TOOLCHAIN` on its own does not mean an explicit choice. The rustup cargo proxy sets it
+/// even for the default toolchain, and it is inherited from parent processes, such as
+/// `cargo test`, cargo aliases or xtasks.
+fn explicit_toolchain() -> Option<String> {
+    let toolchain = std::env::var(TOOLCHAIN_ENV).ok()?;
+    let source = std::env::var(TOOLCHAIN_SOURCE_ENV).ok();
+    is_explicit_source(source.as_deref()).then_some(toolchain)
+}
+
+/// Only toolchains selected on the command line are explicit, rustup reports them with the
+/// `cli` source.
+fn is_explicit_source(source: Option<&str>) -> bool {
+    source == Some("cli")
+}
+
+/// Selects how remarks are collected, based on the used toolchain.
+///
+/// If no toolchain was selected explicitly and the default toolchain does not support
+/// `-Zremark-dir` (e.g. because it is stable), an installed nightly toolchain is used instead.
+/// If no toolchain with `-Zremark-dir` is available, remarks are collected from diagnostics.
+pub fn select_remark_collection() -> RemarkCollection {
+    let collection = if check_remark_dir_support().unwrap_or(false) {
+        RemarkCollection::RemarkDir
+    } else if let Some(toolchain) = explicit_toolchain() {
+        log::warn!(
+            "Toolchain `{toolchain}` does not support `-Zremark-dir`. {DIAGNOSTICS_FALLBACK_HINT}"
+        );
+        RemarkCollection::Diagnostics
+    } else if let Some(toolchain) = find_nightly_toolchain() {
+        let default_toolchain = std::env::var(TOOLCHAIN_ENV).ok();
+        use_toolchain(&toolchain);
+        if check_remark_dir_support().unwrap_or(false) {
+            log::info!(
+                "The default toolchain does not support `-Zremark-dir`, using toolchain `{toolchain}`"
+            );
+            RemarkCollection::RemarkDir
+        } else {
+            restore_default_toolchain(default_toolchain);
+            log::warn!(
+                "Neither the default toolchain nor toolchain `{toolchain}` support `-Zremark-dir`. {DIAGNOSTICS_FALLBACK_HINT}"
+            );
+            RemarkCollection::Diagnostics
+        }
+    } else {
+        log::warn!(
+            "Your version of rustc does not support `-Zremark-dir`, and no nightly toolchain was found. {DIAGNOSTICS_FALLBACK_HINT}"
+        );
+        RemarkCollection::Diagnostics
+    };
+    log_rustc_version();
+    collection
+}
+
+/// Switches back to the toolchain that was used before the nightly fallback.
+fn restore_default_toolchain(toolchain: Option<String>) {
+    match toolchain {
+        Some(toolchain) => {
+            std::env::set_var(TOOLCHAIN_ENV, toolchain);
+            std::env::set_var(TOOLCHAIN_SOURCE_ENV, "default");
+        }
+        None => {
+            std::env::remove_var(TOOLCHAIN_ENV);
+            std::env::remove_var(TOOLCHAIN_SOURCE_ENV);
+        }
+    }
+}
+
+/// Logs the exact version of the used rustc.
+pub fn log_rustc_version() {
+    match get_rustc_short_version() {
+        Ok(version) => log::info!("Using {version}"),
+        Err(error) => log::warn!("Cannot find rustc version: {error:?}"),
+    }
+}
+
+/// Finds an installed nightly toolchain using rustup.
+fn find_nightly_toolchain() -> Option<String> {
+    let output = Command::new("rustup")
+        .args(["toolchain", "list"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    select_nightly_toolchain(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Selects a nightly toolchain from the output of `rustup toolchain list`.
+/// An undated nightly is preferred, otherwise the most recent dated nightly is used.
+fn select_nightly_toolchain(toolchains: &str) -> Option<String> {
+    let nightlies = toolchains
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|toolchain| *toolchain == "nightly" || toolchain.starts_with("nightly-"));
+    let (dated, undated): (Vec<&str>, Vec<&str>) = nightlies.partition(|toolchain| {
+        toolchain
+            .strip_prefix("nightly-")
+            .map(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+            .unwrap_or(false)
+    });
+    undated
+        .first()
+        .or_else(|| dated.iter().max())
+        .map(|toolchain| toolchain.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use crate::cargo::toolchain::{
+        extract_toolchain_arg, is_explicit_source, select_nightly_toolchain,
+    };
+
+    #[test]
+    fn extract_toolchain() {
+        let mut cli = args(&["cargo-remark", "remark", "+nightly", "build", "+foo"]);
+        assert_eq!(extract_toolchain_arg(&mut cli).as_deref(), Some("nightly"));
+        assert_eq!(cli, args(&["cargo-remark", "remark", "build", "+foo"]));
+
+        let mut cli = args(&["cargo-remark", "remark", "build"]);
+        assert_eq!(extract_toolchain_arg(&mut cli), None);
+        assert_eq!(cli, args(&["cargo-remark", "remark", "build"]));
+    }
+
+    #[test]
+    fn select_nightly() {
+        assert_eq!(
+            select_nightly_toolchain(
+                "stable-x86_64-unknown-linux-gnu (default)\nnightly-2023-08-01-x86_64-unknown-linux-gnu\nnightly-x86_64-unknown-linux-gnu\n"
+            )
+            .as_deref(),
+            Some("nightly-x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            select_nightly_toolchain(
+                "nightly-2023-08-01-x86_64-unknown-linux-gnu\nnightly-2023-09-15-x86_64-unknown-linux-gnu (active, default)\n"
+            )
+            .as_deref(),
+            Some("nightly-2023-09-15-x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            select_nightly_toolchain("stable-x86_64-unknown-linux-gnu (default)\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn explicit_toolchain_source() {
+        assert!(is_explicit_source(Some("cli")));
+        // The rustup proxy sets `RUSTUP_TOOLCHAIN_SOURCE=default` for the default toolchain
+        assert!(!is_explicit_source(Some("default")));
+        // Inherited from a parent process, e.g. `cargo test`
+        assert!(!is_explicit_source(Some("env")));
+        assert!(!is_explicit_source(Some("toolchain-file")));
+        assert!(!is_explicit_source(None));
+    }
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+}
@@ -22,3 +22,31 @@ pub fn check_remark_dir_support() -> anyhow::Result<bool> {
 
     Ok(false)
 }
+
+/// Returns the verbose version (`rustc -vV`) of the currently used rustc.
+pub fn get_rustc_version() -> anyhow::Result<String> {
+    let output = Command::new("rustc").arg("-vV").output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Failed to execute rustc -vV"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the short version (`rustc -V`) of the currently used rustc.
+pub fn get_rustc_short_version() -> anyhow::Result<String> {
+    let output = Command::new("rustc").arg("-V").output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Failed to execute rustc -V"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns the host target triple of the currently used rustc.
+pub fn get_host_triple() -> anyhow::Result<String> {
+    let version = get_rustc_version()?;
+    version
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(|host| host.trim().to_string())
+        .ok_or_else(|| anyhow::anyhow!("Cannot find host triple in rustc version output"))
+}
@@ -0,0 +1,151 @@
+//! Detection of source changes for `cargo remark watch`.
+//!
+//! Sources of the workspace are polled periodically, and changes are detected by comparing the
+//! modification times and sizes of the watched files.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::cargo::{get_cargo_ctx, BuildSelection};
+
+/// Files that are watched in addition to Rust sources.
+const WATCHED_FILES: &[&str] = &["Cargo.toml", "remark.toml"];
+
+/// Modification time and size of watched files, keyed by their path relative to the workspace
+/// root.
+#[derive(Default, PartialEq, Debug)]
+struct SourceSnapshot {
+    files: BTreeMap<String, (Option<SystemTime>, u64)>,
+}
+
+impl SourceSnapshot {
+    /// Scans the watched files inside `root`, skipping hidden directories and `ignored`.
+    fn scan(root: &Path, ignored: &Path) -> Self {
+        let mut snapshot = Self::default();
+        snapshot.scan_dir(root, root, ignored);
+        snapshot
+    }
+
+    fn scan_dir(&mut self, root: &Path, dir: &Path, ignored: &Path) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if !hidden && path != ignored {
+                    self.scan_dir(root, &path, ignored);
+                }
+            } else if is_watched(&path) {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                self.files.insert(
+                    relative.to_string_lossy().into_owned(),
+                    (metadata.modified().ok(), metadata.len()),
+                );
+            }
+        }
+    }
+
+    /// Returns files that were added, removed or modified since `previous`.
+    fn changed_files(&self, previous: &SourceSnapshot) -> Vec<String> {
+        let mut changed: Vec<String> = self
+            .files
+            .iter()
+            .filter(|(path, state)| previous.files.get(*path) != Some(state))
+            .map(|(path, _)| path.clone())
+            .collect();
+        changed.extend(
+            previous
+                .files
+                .keys()
+                .filter(|path| !self.files.contains_key(*path))
+                .cloned(),
+        );
+        changed.sort();
+        changed
+    }
+}
+
+fn is_watched(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "rs")
+        || path
+            .file_name()
+            .is_some_and(|name| WATCHED_FILES.iter().any(|file| name == *file))
+}
+
+/// Watches the sources of the selected workspace.
+pub struct SourceWatcher {
+    root: PathBuf,
+    target_dir: PathBuf,
+    interval: Duration,
+    snapshot: SourceSnapshot,
+}
+
+impl SourceWatcher {
+    /// Starts watching the sources. Changes are detected relative to their current state.
+    pub fn new(selection: &BuildSelection, interval: Duration) -> anyhow::Result<Self> {
+        let ctx = get_cargo_ctx(selection)?;
+        let snapshot = SourceSnapshot::scan(&ctx.root_directory, &ctx.target_directory);
+        Ok(Self {
+            root: ctx.root_directory,
+            target_dir: ctx.target_directory,
+            interval,
+            snapshot,
+        })
+    }
+
+    /// Blocks until some sources change, and returns the changed files, relative to the
+    /// workspace root.
+    pub fn wait_for_changes(&mut self) -> Vec<String> {
+        loop {
+            std::thread::sleep(self.interval);
+            let snapshot = SourceSnapshot::scan(&self.root, &self.target_dir);
+            let changed = snapshot.changed_files(&self.snapshot);
+            self.snapshot = snapshot;
+            if !changed.is_empty() {
+                return changed;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cargo::watch::SourceSnapshot;
+
+    #[test]
+    fn detect_changes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "fn foo() {}").unwrap();
+        std::fs::write(root.join("src/bar.rs"), "fn bar() {}").unwrap();
+        std::fs::write(root.join("README.md"), "").unwrap();
+
+        let before = SourceSnapshot::scan(root, &root.join("target"));
+        assert_eq!(
+            before.files.keys().collect::<Vec<_>>(),
+            vec!["Cargo.toml", "src/bar.rs", "src/lib.rs"]
+        );
+
+        std::fs::write(root.join("src/lib.rs"), "fn foo() { bar(); }").unwrap();
+        std::fs::remove_file(root.join("src/bar.rs")).unwrap();
+        std::fs::write(root.join("target/build.rs"), "").unwrap();
+        std::fs::write(root.join(".git/hook.rs"), "").unwrap();
+        std::fs::write(root.join("README.md"), "readme").unwrap();
+
+        let after = SourceSnapshot::scan(root, &root.join("target"));
+        assert_eq!(
+            after.changed_files(&before),
+            vec!["src/bar.rs", "src/lib.rs"]
+        );
+        assert!(after.changed_files(&after).is_empty());
+    }
+}
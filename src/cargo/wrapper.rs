@@ -0,0 +1,152 @@
+//! `cargo-remark` can act as a rustc wrapper, which adds remark flags only to selected crates.
+//!
+//! Cargo invokes the wrapper as `<wrapper> <path-to-rustc> <rustc-args>...`.
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use cargo_remark::flags::CrateFlags;
+
+/// Flags that should be added to the compilation of selected crates.
+const FLAGS_ENV: &str = "CARGO_REMARK_FLAGS";
+/// Names of packages whose crates should be compiled with remark flags.
+const PACKAGES_ENV: &str = "CARGO_REMARK_PACKAGES";
+/// Wrapper that was configured by the user before `cargo-remark` has replaced it.
+const INNER_WRAPPER_ENV: &str = "CARGO_REMARK_INNER_WRAPPER";
+/// Directory into which the final flags of each compiled crate will be recorded.
+const FLAGS_DIR_ENV: &str = "CARGO_REMARK_FLAGS_DIR";
+
+/// Separator of items inside environment variables, the same as in `CARGO_ENCODED_RUSTFLAGS`.
+const SEPARATOR: char = '\x1f';
+
+pub struct WrapperConfig {
+    /// Flags that will be passed to rustc when compiling the selected packages.
+    pub flags: Vec<String>,
+    /// Workspace members.
+    pub workspace_packages: Vec<String>,
+    /// Additional packages (dependencies) that should be compiled with the flags.
+    pub extra_packages: Vec<String>,
+    /// Directory where the final flags of the selected crates will be recorded.
+    pub flags_dir: PathBuf,
+}
+
+/// Configures `command` (a cargo invocation) to use `cargo-remark` as a rustc wrapper.
+pub fn configure_wrapper(command: &mut Command, config: WrapperConfig) -> anyhow::Result<()> {
+    let WrapperConfig {
+        flags,
+        workspace_packages,
+        extra_packages,
+        flags_dir,
+    } = config;
+
+    // `RUSTC_WORKSPACE_WRAPPER` is only applied to workspace members, so we only have to
+    // wrap all crates if some dependencies should also be compiled with remarks.
+    let wrapper_env = if extra_packages.is_empty() {
+        "RUSTC_WORKSPACE_WRAPPER"
+    } else {
+        "RUSTC_WRAPPER"
+    };
+    if let Some(inner) = std::env::var_os(wrapper_env).filter(|w| !w.is_empty()) {
+        command.env(INNER_WRAPPER_ENV, inner);
+    }
+
+    let current_exe = std::env::current_exe()
+        .map_err(|error| anyhow::anyhow!("Cannot find path to cargo-remark: {error:?}"))?;
+    let packages: Vec<String> = workspace_packages
+        .into_iter()
+        .chain(extra_packages)
+        .collect();
+
+    command
+        .env(wrapper_env, current_exe)
+        .env(FLAGS_ENV, join(&flags))
+        .env(PACKAGES_ENV, join(&packages))
+        .env(FLAGS_DIR_ENV, flags_dir);
+    Ok(())
+}
+
+/// Returns true if the current process was executed by cargo as a rustc wrapper.
+pub fn is_wrapper_invocation() -> bool {
+    std::env::var_os(FLAGS_ENV).is_some()
+        && std::env::args_os()
+            .nth(1)
+            .map(|arg| arg != "remark")
+            .unwrap_or(false)
+}
+
+/// Executes rustc, and adds remark flags to its arguments if the compiled crate was selected
+/// for remark generation.
+pub fn run_wrapper() -> anyhow::Result<i32> {
+    let mut args = std::env::args_os().skip(1);
+    let rustc = args
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Missing path to rustc"))?;
+    let mut args: Vec<OsString> = args.collect();
+
+    if let Some(crate_name) = get_selected_crate(&args) {
+        let flags = std::env::var(FLAGS_ENV).unwrap_or_default();
+        args.extend(split(&flags).map(OsString::from));
+
+        if let Some(dir) = std::env::var_os(FLAGS_DIR_ENV) {
+            let args: Vec<Cow<str>> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+            // Failing to record the flags should not fail the compilation
+            let _ = record_flags(
+                Path::new(&dir),
+                CrateFlags::from_rustc_args(crate_name, &args),
+            );
+        }
+    }
+
+    let mut command = match std::env::var_os(INNER_WRAPPER_ENV) {
+        Some(inner) => {
+            let mut command = Command::new(inner);
+            command.arg(rustc);
+            command
+        }
+        None => Command::new(rustc),
+    };
+    let status = command
+        .args(args)
+        .status()
+        .map_err(|error| anyhow::anyhow!("Cannot execute rustc: {error:?}"))?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Returns the name of the compiled crate, if it was selected for remark generation.
+fn get_selected_crate(args: &[OsString]) -> Option<String> {
+    // Cargo also uses the wrapper to query information about rustc
+    let crate_name = arg_values(args, "--crate-name").next()?;
+    // Build scripts and proc macros are not part of the optimized program
+    if crate_name.starts_with("build_script_")
+        || arg_values(args, "--crate-type").any(|crate_type| crate_type == "proc-macro")
+    {
+        return None;
+    }
+
+    let package = std::env::var("CARGO_PKG_NAME").unwrap_or_default();
+    let packages = std::env::var(PACKAGES_ENV).unwrap_or_default();
+    let selected = split(&packages).any(|p| p == package);
+    selected.then(|| crate_name.into_owned())
+}
+
+fn record_flags(dir: &Path, flags: CrateFlags) -> anyhow::Result<()> {
+    let file = std::fs::File::create(dir.join(flags.file_name()))?;
+    serde_json::to_writer_pretty(file, &flags)?;
+    Ok(())
+}
+
+/// Returns values of the rustc argument `name`, passed in the `<name> <value>` form.
+fn arg_values<'a>(args: &'a [OsString], name: &'a str) -> impl Iterator<Item = Cow<'a, str>> {
+    args.windows(2)
+        .filter(move |pair| pair[0] == name)
+        .map(|pair| pair[1].to_string_lossy())
+}
+
+fn join(items: &[String]) -> String {
+    items.join(&SEPARATOR.to_string())
+}
+
+fn split(value: &str) -> impl Iterator<Item = &str> {
+    value.split(SEPARATOR).filter(|item| !item.is_empty())
+}
@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use crate::cargo::toolchain::{log_rustc_version, select_remark_collection};
+use crate::cargo::{run_cargo, CargoSubcommand, RemarkBuildOptions, RemarkCollection};
+use cargo_remark::query::{RemarkQuery, RemarkSort};
+use cargo_remark::remark::Remark;
+use cargo_remark::terminal::format_diagnostic;
+use cargo_remark::utils::cli::flag_value;
+use cargo_remark::utils::source::SourceCache;
+use colored::Colorize;
+
+use super::{load_build_remarks, load_options, render_build_remarks, GenerateArgs};
+
+#[derive(clap::Parser, Debug)]
+#[clap(trailing_var_arg = true)]
+pub struct SharedArgs {
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    /// Also generate remarks for the selected dependency.
+    /// By default, remarks are only generated for workspace members.
+    /// Note that this may produce a large amount of data!
+    #[arg(long = "remark-package", value_name = "PACKAGE")]
+    remark_packages: Vec<String>,
+
+    /// Build the crate in the normal Cargo target directory, instead of a dedicated directory
+    /// (`target/remarks/build`) used only for remark builds.
+    #[arg(long)]
+    shared_target_dir: bool,
+
+    /// Collect remarks from rustc diagnostics instead of using `-Zremark-dir`.
+    /// This also works with stable toolchains, but the remarks have a reduced fidelity.
+    /// It is used automatically if no toolchain with `-Zremark-dir` is available.
+    #[arg(long)]
+    diagnostics: bool,
+
+    /// Output format of the remarks.
+    #[arg(long, default_value = "html")]
+    format: OutputFormat,
+
+    /// Additional arguments that will be passed to Cargo.
+    cargo_args: Vec<String>,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Render the website.
+    Html,
+    /// Render the website, and print the remarks to the terminal as rustc-style diagnostics with
+    /// source snippets.
+    Human,
+}
+
+pub fn run(subcmd: CargoSubcommand, args: SharedArgs) -> anyhow::Result<()> {
+    let SharedArgs {
+        generate:
+            GenerateArgs {
+                open,
+                no_open,
+                passes,
+                load,
+            },
+        remark_packages,
+        shared_target_dir,
+        diagnostics,
+        format,
+        cargo_args,
+    } = args;
+    let collection = if diagnostics {
+        log_rustc_version();
+        RemarkCollection::Diagnostics
+    } else {
+        select_remark_collection()
+    };
+    let output = run_cargo(
+        subcmd,
+        cargo_args,
+        RemarkBuildOptions {
+            remark_packages,
+            passes,
+            shared_target_dir,
+            collection,
+            configuration: None,
+            rustflags: vec![],
+        },
+    )?;
+    let options = load_options(&output.config, load, &output.source_dir)?;
+    let (remarks, crate_flags) = load_build_remarks(&output, options)?;
+    if format == OutputFormat::Human {
+        print_diagnostics(&remarks, &output.source_dir);
+    }
+    render_build_remarks(&output, remarks, crate_flags, flag_value(open, no_open))
+}
+
+/// Prints remarks that were not suppressed as rustc-style diagnostics, ordered by location.
+fn print_diagnostics(remarks: &[Remark], source_dir: &Path) {
+    let page = RemarkQuery {
+        sort: RemarkSort::Location,
+        ..Default::default()
+    }
+    .apply(remarks);
+    let mut sources = SourceCache::new(source_dir);
+    let mut suppressed = 0;
+    for remark in page.remarks {
+        if remark.suppressed {
+            suppressed += 1;
+            continue;
+        }
+        println!("{}", format_diagnostic(remark, &mut sources));
+    }
+    let generated = remarks.len() - suppressed;
+    let suppressed = if suppressed > 0 {
+        format!(" ({suppressed} suppressed)")
+    } else {
+        String::new()
+    };
+    println!(
+        "{}{}",
+        "note".green().bold(),
+        format!(": {generated} remark(s) generated{suppressed}").bold()
+    );
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use crate::cargo::{get_build_output, BuildSelection};
+use cargo_remark::baseline::Baseline;
+use cargo_remark::remark::{load_remarks_from_dir, Remark};
+use cargo_remark::utils::cli::cli_format_path;
+use cargo_remark::utils::timing::time_block_log_info;
+use colored::Colorize;
+
+use super::{describe_remark, load_options, LoadArgs};
+
+#[derive(clap::Parser, Debug)]
+pub struct CheckArgs {
+    /// Baseline file with known remarks.
+    #[arg(long)]
+    baseline: PathBuf,
+
+    /// Rewrite the baseline with the current remarks, instead of checking them.
+    #[arg(long)]
+    update_baseline: bool,
+
+    /// Only fail on new remarks of these kinds (e.g. `LoadClobbered`).
+    /// If neither this nor `--min-hotness` is specified, all new remarks fail the check.
+    #[arg(long = "kind", value_name = "KIND")]
+    fail_kinds: Vec<String>,
+
+    /// Fail on new remarks whose hotness is at least this value.
+    #[arg(long)]
+    min_hotness: Option<i32>,
+
+    #[clap(flatten)]
+    load: LoadArgs,
+
+    #[clap(flatten)]
+    selection: BuildSelection,
+}
+
+pub fn run(args: CheckArgs) -> anyhow::Result<()> {
+    let CheckArgs {
+        baseline,
+        update_baseline,
+        fail_kinds,
+        min_hotness,
+        load,
+        selection,
+    } = args;
+    let build_output = get_build_output(&selection)?;
+    let options = load_options(&build_output.config, load, &build_output.source_dir)?;
+    let remarks = time_block_log_info("Remark loading", || {
+        load_remarks_from_dir(&build_output.yaml_dir, options, None)
+    })?;
+    // Suppressed remarks were accepted in the source code, so they are not tracked by the baseline
+    let remarks: Vec<Remark> = remarks
+        .into_iter()
+        .filter(|remark| !remark.suppressed)
+        .collect();
+
+    if update_baseline {
+        Baseline::from_remarks(&remarks).store(&baseline)?;
+        log::info!(
+            "Stored {} remark(s) into baseline {}.",
+            remarks.len(),
+            cli_format_path(&baseline)
+        );
+        return Ok(());
+    }
+
+    let new_remarks = Baseline::load(&baseline)?.find_new_remarks(&remarks);
+    let fails = |remark: &Remark| {
+        if fail_kinds.is_empty() && min_hotness.is_none() {
+            return true;
+        }
+        fail_kinds.contains(&remark.name)
+            || min_hotness
+                .map(|min| remark.hotness.unwrap_or(0) >= min)
+                .unwrap_or(false)
+    };
+
+    let mut failed = 0;
+    for remark in &new_remarks {
+        let marker = if fails(remark) {
+            failed += 1;
+            "error".red()
+        } else {
+            "note".normal()
+        };
+        println!(
+            "{marker}: {} [{}]",
+            describe_remark(remark),
+            remark.fingerprint
+        );
+    }
+    println!(
+        "{} new remark(s), {failed} of them fail the check",
+        new_remarks.len()
+    );
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{failed} new remark(s) are not present in baseline {}",
+            baseline.display()
+        ));
+    }
+    Ok(())
+}
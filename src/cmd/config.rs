@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use crate::cargo::{load_config, BuildSelection};
+
+#[derive(clap::Parser, Debug)]
+pub struct ConfigArgs {
+    /// Path to `Cargo.toml` of the crate.
+    #[arg(long)]
+    manifest_path: Option<PathBuf>,
+}
+
+pub fn run(args: ConfigArgs) -> anyhow::Result<()> {
+    let selection = BuildSelection {
+        manifest_path: args.manifest_path,
+        ..Default::default()
+    };
+    print!("{}", load_config(&selection)?.to_toml()?);
+    Ok(())
+}
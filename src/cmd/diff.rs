@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use crate::cargo::{get_remarks_dir, load_config, BuildSelection};
+use anyhow::Context;
+use cargo_remark::archive::unpack_remarks;
+use cargo_remark::config::RemarkConfig;
+use cargo_remark::diff::{diff_remarks, RemarkChange, RemarkDiff, RemarkDiffEntry};
+use cargo_remark::remark::locate::infer_source_dir;
+use cargo_remark::remark::{load_remarks_from_dir, Remark};
+use cargo_remark::render::{render_diff, RenderOptions};
+use cargo_remark::triage::load_triage;
+use cargo_remark::utils::callback::ProgressBarCallback;
+use cargo_remark::utils::cli::{cli_format_path, flag_value};
+use cargo_remark::utils::open_result;
+use cargo_remark::utils::timing::time_block_log_info;
+use colored::Colorize;
+
+use super::{describe_remark, load_options, LoadArgs, MAX_PRINTED_CHANGES};
+
+#[derive(clap::Parser, Debug)]
+pub struct DiffArgs {
+    /// Old remarks: a directory with YAML remarks, a run directory of `cargo remark build`, or an
+    /// archive created by `cargo remark pack`.
+    old: PathBuf,
+
+    /// New remarks, in the same format as the old remarks.
+    new: PathBuf,
+
+    /// Source directory of the old remarks.
+    /// Inferred if not specified.
+    #[arg(long)]
+    old_source_dir: Option<PathBuf>,
+
+    /// Source directory of the new remarks.
+    /// Inferred if not specified.
+    #[arg(long)]
+    new_source_dir: Option<PathBuf>,
+
+    /// Output directory of the rendered diff.
+    /// Defaults to `target/remarks/diff`.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    load: LoadArgs,
+
+    /// Open the generated website after the diff is rendered.
+    /// Defaults to the `open` configuration option.
+    #[arg(long, overrides_with = "no_open")]
+    open: bool,
+
+    /// Do not open the generated website, even if the `open` configuration option is set.
+    #[arg(long, overrides_with = "open")]
+    no_open: bool,
+}
+
+/// Remarks loaded from a single input of the `diff` command.
+struct DiffInput {
+    remarks: Vec<Remark>,
+    source_dir: PathBuf,
+    /// The extracted archive has to live until the remarks are rendered.
+    _archive_dir: Option<tempfile::TempDir>,
+}
+
+fn load_diff_input(
+    path: &Path,
+    source_dir: Option<PathBuf>,
+    config: &RemarkConfig,
+    load: LoadArgs,
+) -> anyhow::Result<DiffInput> {
+    let (yaml_dir, options, archive_dir) = if path.is_file() {
+        let archive_dir = tempfile::TempDir::new()?;
+        let archive = unpack_remarks(path, archive_dir.path())?;
+        // Flags passed on the command line take precedence over the options used for packing
+        let mut options = archive.load_options();
+        if let Some(external) = flag_value(load.external, load.no_external) {
+            options.external = external;
+        }
+        if !load.filter_kind.is_empty() {
+            options.filter_kind = load.filter_kind;
+        }
+        if !load.include.is_empty() {
+            options.filter.include = load.include;
+        }
+        if !load.exclude.is_empty() {
+            options.filter.exclude = load.exclude;
+        }
+        (archive.yaml_dir, options, Some(archive_dir))
+    } else {
+        // Run directories of `cargo remark build` store the remarks in the `yaml` subdirectory
+        let yaml_dir = match path.join("yaml") {
+            dir if dir.is_dir() => dir,
+            _ => path.to_path_buf(),
+        };
+        let source_dir = match source_dir {
+            Some(dir) => dir,
+            None => {
+                infer_source_dir(&yaml_dir)
+                    .with_context(|| {
+                        format!("Cannot infer source directory of {}", cli_format_path(path))
+                    })?
+                    .source_dir
+            }
+        };
+        let options = load_options(config, load, &source_dir)?;
+        (yaml_dir, options, None)
+    };
+    let source_dir = options.source_dir.clone();
+    let remarks = load_remarks_from_dir(yaml_dir, options, Some(&ProgressBarCallback::default()))?;
+    Ok(DiffInput {
+        remarks,
+        source_dir,
+        _archive_dir: archive_dir,
+    })
+}
+
+pub fn run(args: DiffArgs) -> anyhow::Result<()> {
+    let DiffArgs {
+        old,
+        new,
+        old_source_dir,
+        new_source_dir,
+        output,
+        load,
+        open,
+        no_open,
+    } = args;
+
+    // Diffs can also be created outside of a Cargo project, e.g. from two archives
+    let config = match load_config(&BuildSelection::default()) {
+        Ok(loaded) => loaded.config,
+        Err(error) => {
+            log::warn!("Cannot load configuration, using defaults: {error:?}");
+            RemarkConfig::default()
+        }
+    };
+    let old = time_block_log_info("Old remark loading", || {
+        load_diff_input(&old, old_source_dir, &config, load.clone())
+    })?;
+    let new = time_block_log_info("New remark loading", || {
+        load_diff_input(&new, new_source_dir, &config, load)
+    })?;
+    let output_dir = match output {
+        Some(output) => output,
+        None => get_remarks_dir()?.join("diff"),
+    };
+
+    let diff = diff_remarks(old.remarks, new.remarks);
+    print_diff_summary(&diff);
+
+    time_block_log_info("Rendering", || {
+        render_diff(
+            diff,
+            &new.source_dir,
+            &output_dir,
+            RenderOptions {
+                triage: load_triage(&new.source_dir),
+                ..Default::default()
+            },
+            Some(&ProgressBarCallback::default()),
+        )
+    })?;
+    log::info!("Diff website built into {}.", cli_format_path(&output_dir));
+    open_result(
+        &output_dir,
+        flag_value(open, no_open).unwrap_or(config.open),
+    )?;
+    Ok(())
+}
+
+fn print_diff_summary(diff: &RemarkDiff) {
+    println!(
+        "{} added, {} removed, {} with changed hotness, {} unchanged",
+        diff.count(RemarkChange::Added).to_string().red(),
+        diff.count(RemarkChange::Removed).to_string().green(),
+        diff.count(RemarkChange::HotnessChanged)
+            .to_string()
+            .yellow(),
+        diff.count(RemarkChange::Unchanged)
+    );
+    for change in [
+        RemarkChange::Added,
+        RemarkChange::Removed,
+        RemarkChange::HotnessChanged,
+    ] {
+        let entries: Vec<&RemarkDiffEntry> = diff
+            .entries
+            .iter()
+            .filter(|entry| entry.change == change)
+            .collect();
+        if entries.is_empty() {
+            continue;
+        }
+        println!("\n{}:", change.as_str());
+        for entry in entries.iter().take(MAX_PRINTED_CHANGES) {
+            let hotness_change = match change {
+                RemarkChange::HotnessChanged => {
+                    format!(", previous hotness {}", entry.old_hotness.unwrap_or(0))
+                }
+                _ => String::new(),
+            };
+            let marker = match change {
+                RemarkChange::Added => "+".red(),
+                RemarkChange::Removed => "-".green(),
+                _ => "~".yellow(),
+            };
+            println!(
+                "{marker} {}{hotness_change}",
+                describe_remark(&entry.remark)
+            );
+        }
+        if entries.len() > MAX_PRINTED_CHANGES {
+            println!("... and {} more", entries.len() - MAX_PRINTED_CHANGES);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use cargo_remark::query::{message_text, RemarkQuery, RemarkSort};
+use cargo_remark::table::{Align, Table};
+use colored::Colorize;
+
+use super::{format_location, load_latest_remarks, LatestRemarksArgs};
+
+#[derive(clap::Parser, Debug)]
+pub struct ListArgs {
+    /// Order of the remarks.
+    /// By default, remarks are listed in the order in which they were loaded.
+    #[arg(long)]
+    sort: Option<RemarkSort>,
+
+    /// Maximum number of listed remarks.
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Print the remarks as JSON.
+    #[arg(long)]
+    json: bool,
+
+    #[clap(flatten)]
+    remarks: LatestRemarksArgs,
+}
+
+pub fn run(args: ListArgs) -> anyhow::Result<()> {
+    let ListArgs {
+        sort,
+        limit,
+        json,
+        remarks,
+    } = args;
+    let (remarks, _) = load_latest_remarks(remarks)?;
+    let page = RemarkQuery {
+        sort: sort.unwrap_or_default(),
+        limit,
+        ..Default::default()
+    }
+    .apply(&remarks);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&page)?);
+        return Ok(());
+    }
+
+    let mut table = Table::new(&[
+        ("Location", Align::Left),
+        ("Remark", Align::Left),
+        ("Hotness", Align::Right),
+        ("Function", Align::Left),
+        ("Message", Align::Left),
+    ]);
+    for remark in &page.remarks {
+        table.push_row(vec![
+            format_location(remark),
+            format!("{}/{}", remark.pass, remark.name),
+            remark
+                .hotness
+                .map(|hotness| hotness.to_string())
+                .unwrap_or_default(),
+            remark.function.name.clone(),
+            message_text(remark),
+        ]);
+    }
+    println!("{}", table.format_header().bold());
+    for row in table.format_rows() {
+        println!("{row}");
+    }
+    if page.remarks.len() < page.total {
+        println!("Listed {} of {} remark(s)", page.remarks.len(), page.total);
+    } else {
+        println!("{} remark(s)", page.total);
+    }
+    Ok(())
+}
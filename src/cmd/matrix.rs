@@ -0,0 +1,134 @@
+use std::path::PathBuf;
+
+use crate::cargo::toolchain::select_remark_collection;
+use crate::cargo::{
+    get_matrix_dir, load_config, parse_build_selection, run_cargo, CargoSubcommand,
+    RemarkBuildOptions,
+};
+use cargo_remark::config::MatrixConfiguration;
+use cargo_remark::matrix::compare_remarks;
+use cargo_remark::remark::load_remarks_from_dir;
+use cargo_remark::render::{render_matrix, RenderOptions};
+use cargo_remark::triage::load_triage;
+use cargo_remark::utils::callback::ProgressBarCallback;
+use cargo_remark::utils::cli::{cli_format_path, flag_value};
+use cargo_remark::utils::open_result;
+use cargo_remark::utils::timing::time_block_log_info;
+use colored::Colorize;
+
+use super::{load_options, GenerateArgs};
+
+#[derive(clap::Parser, Debug)]
+#[clap(trailing_var_arg = true)]
+pub struct MatrixArgs {
+    /// Configuration in the `NAME=RUSTFLAGS` format, e.g. `native=-Ctarget-cpu=native`.
+    /// Can be passed multiple times. Defaults to the `matrix` configuration option.
+    #[arg(long = "configuration", short = 'c', value_name = "NAME=RUSTFLAGS")]
+    configurations: Vec<MatrixConfiguration>,
+
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    /// Additional arguments that will be passed to Cargo in all configurations.
+    cargo_args: Vec<String>,
+}
+
+pub fn run(args: MatrixArgs) -> anyhow::Result<()> {
+    let MatrixArgs {
+        configurations,
+        generate:
+            GenerateArgs {
+                open,
+                no_open,
+                passes,
+                load,
+            },
+        cargo_args,
+    } = args;
+
+    let selection = parse_build_selection(&cargo_args);
+    let config = load_config(&selection)?.config;
+    let configurations = if configurations.is_empty() {
+        config.matrix.clone()
+    } else {
+        configurations
+    };
+    if configurations.len() < 2 {
+        return Err(anyhow::anyhow!(
+            "At least two configurations have to be compared. Pass them with `--configuration` or configure them in the `matrix` configuration option."
+        ));
+    }
+    let mut names: Vec<&str> = configurations.iter().map(|c| c.name.as_str()).collect();
+    names.sort();
+    if names.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(anyhow::anyhow!("Configuration names have to be unique"));
+    }
+
+    let collection = select_remark_collection();
+    let mut sets = vec![];
+    let mut source_dir = PathBuf::new();
+    for configuration in configurations {
+        log::info!(
+            "Building configuration `{}` (flags: `{}`, Cargo arguments: `{}`)",
+            configuration.name,
+            configuration.rustflags.join(" "),
+            configuration.cargo_args.join(" ")
+        );
+        let mut args = cargo_args.clone();
+        args.extend(configuration.cargo_args);
+        let output = run_cargo(
+            CargoSubcommand::Build,
+            args,
+            RemarkBuildOptions {
+                remark_packages: vec![],
+                passes: passes.clone(),
+                shared_target_dir: false,
+                collection,
+                configuration: Some(configuration.name.clone()),
+                rustflags: configuration.rustflags,
+            },
+        )?;
+        let options = load_options(&config, load.clone(), &output.source_dir)?;
+        let remarks = time_block_log_info("Remark loading", || {
+            load_remarks_from_dir(
+                &output.yaml_dir,
+                options,
+                Some(&ProgressBarCallback::default()),
+            )
+        })?;
+        source_dir = output.source_dir;
+        sets.push((configuration.name, remarks));
+    }
+
+    let matrix = compare_remarks(sets);
+    for (name, count) in matrix.configurations.iter().zip(&matrix.counts) {
+        println!("{}: {count} remark(s)", name.bold());
+    }
+    println!(
+        "{} remark(s) are only present in some configurations",
+        matrix.partial_count().to_string().yellow()
+    );
+
+    let output_dir = get_matrix_dir(&selection)?.join("web");
+    time_block_log_info("Rendering", || {
+        render_matrix(
+            matrix,
+            &source_dir,
+            &output_dir,
+            RenderOptions {
+                triage: load_triage(&source_dir),
+                ..Default::default()
+            },
+            Some(&ProgressBarCallback::default()),
+        )
+    })?;
+    log::info!(
+        "Comparison website built into {}.",
+        cli_format_path(&output_dir)
+    );
+    open_result(
+        &output_dir,
+        flag_value(open, no_open).unwrap_or(config.open),
+    )?;
+    Ok(())
+}
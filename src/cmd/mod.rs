@@ -0,0 +1,265 @@
+//! Subcommands of `cargo remark`, together with options and helpers shared by several of them.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::cargo::version::get_rustc_short_version;
+use crate::cargo::{find_rustc_source_root, get_build_output, BuildOutput, BuildSelection};
+use cargo_remark::config::RemarkConfig;
+use cargo_remark::filter::FilterExpr;
+use cargo_remark::flags::{load_crate_flags, CrateFlags};
+use cargo_remark::history::{load_history, record_history, HistoryEntry, RemarkSummary};
+use cargo_remark::remark::{load_remarks_from_dir, Remark, RemarkLoadOptions};
+use cargo_remark::render::{render_remarks, RenderOptions};
+use cargo_remark::triage::load_triage;
+use cargo_remark::utils::callback::ProgressBarCallback;
+use cargo_remark::utils::cli::{cli_format_path, flag_value};
+use cargo_remark::utils::open_result;
+use cargo_remark::utils::timing::time_block_log_info;
+
+pub mod build;
+pub mod check;
+pub mod config;
+pub mod diff;
+pub mod list;
+pub mod matrix;
+pub mod pack;
+pub mod pgo;
+pub mod serve;
+pub mod show;
+pub mod stats;
+pub mod triage;
+pub mod watch;
+
+/// Options of commands that generate remarks and render them into a website.
+#[derive(clap::Args, Debug, Clone)]
+struct GenerateArgs {
+    /// Open the generated website after the build finishes.
+    /// Defaults to the `open` configuration option.
+    #[arg(long, overrides_with = "no_open")]
+    open: bool,
+
+    /// Do not open the generated website, even if the `open` configuration option is set.
+    #[arg(long, overrides_with = "open")]
+    no_open: bool,
+
+    /// Comma separated list of LLVM passes (e.g. `inline,gvn,loop-vectorize`) that should
+    /// generate remarks. By default, all passes generate remarks.
+    #[arg(long, value_delimiter = ',', value_name = "PASSES")]
+    passes: Vec<String>,
+
+    #[clap(flatten)]
+    load: LoadArgs,
+}
+
+/// Options that select which remarks are loaded.
+#[derive(clap::Args, Debug, Clone, Default)]
+struct LoadArgs {
+    /// Load remarks from external code (i.e. crate dependencies).
+    /// Note that this may produce a large amount of data!
+    /// Defaults to the `external` configuration option.
+    #[arg(long, overrides_with = "no_external")]
+    external: bool,
+
+    /// Do not load remarks from external code, even if the `external` configuration option is
+    /// set.
+    #[arg(long, overrides_with = "external")]
+    no_external: bool,
+
+    /// Optimization remark kinds that should be ignored.
+    /// Defaults to the `filter` configuration option, or to `FastISelFailure`, `NeverInline` and
+    /// `SpillReloadCopies`.
+    #[arg(long = "filter")]
+    filter_kind: Vec<String>,
+
+    /// Only load remarks that match this filter expression, e.g.
+    /// `pass == "gvn" && file ~ "src/hot/**" && hotness > 1000`.
+    /// If passed multiple times, remarks that match any of the expressions are loaded.
+    #[arg(long, value_name = "EXPR")]
+    include: Vec<FilterExpr>,
+
+    /// Ignore remarks that match this filter expression.
+    /// Cold remarks can be ignored with e.g. `hotness < 1000`. They are still generated, since
+    /// filters are only applied when remarks are loaded.
+    #[arg(long, value_name = "EXPR")]
+    exclude: Vec<FilterExpr>,
+}
+
+/// Loads remarks of the latest run.
+#[derive(clap::Args, Debug)]
+struct LatestRemarksArgs {
+    #[clap(flatten)]
+    load: LoadArgs,
+
+    #[clap(flatten)]
+    selection: BuildSelection,
+}
+
+/// Combines remark loading options passed on the command line with the configuration.
+fn load_options(
+    config: &RemarkConfig,
+    load: LoadArgs,
+    source_dir: &Path,
+) -> anyhow::Result<RemarkLoadOptions> {
+    Ok(RemarkLoadOptions {
+        external: flag_value(load.external, load.no_external).unwrap_or(config.external),
+        source_dir: source_dir.to_path_buf(),
+        filter_kind: config.filter_kind(load.filter_kind),
+        filter: config.remark_filter(load.include, load.exclude),
+        rustc_source_root: find_rustc_source_root(config)?,
+        path_mappings: config.path_mappings()?,
+    })
+}
+
+/// Loads remarks of the latest run, and returns them together with their source directory.
+fn load_latest_remarks(args: LatestRemarksArgs) -> anyhow::Result<(Vec<Remark>, PathBuf)> {
+    let LatestRemarksArgs { load, selection } = args;
+    let output = get_build_output(&selection)?;
+    let options = load_options(&output.config, load, &output.source_dir)?;
+    let remarks = load_remarks_from_dir(&output.yaml_dir, options, None)?;
+    Ok((remarks, output.source_dir))
+}
+
+/// Loads remarks and compiler flags of a finished remark build.
+fn load_build_remarks(
+    output: &BuildOutput,
+    options: RemarkLoadOptions,
+) -> anyhow::Result<(Vec<Remark>, Vec<CrateFlags>)> {
+    let crate_flags = match load_crate_flags(&output.flags_dir) {
+        Ok(flags) => flags,
+        Err(error) => {
+            log::warn!("Cannot load compiler flags: {error:?}");
+            vec![]
+        }
+    };
+    log_crate_flags(&crate_flags);
+
+    let remarks = time_block_log_info("Remark loading", || {
+        load_remarks_from_dir(
+            &output.yaml_dir,
+            options,
+            Some(&ProgressBarCallback::default()),
+        )
+    })?;
+    Ok((remarks, crate_flags))
+}
+
+/// Records the run into history and renders the website of a remark build.
+fn render_build_remarks(
+    output: &BuildOutput,
+    remarks: Vec<Remark>,
+    crate_flags: Vec<CrateFlags>,
+    open: Option<bool>,
+) -> anyhow::Result<()> {
+    let history = record_run_history(output, &crate_flags, &remarks);
+    time_block_log_info("Rendering", || {
+        render_remarks(
+            remarks,
+            &output.source_dir,
+            &output.web_dir,
+            RenderOptions {
+                crate_flags,
+                history,
+                rerender_files: None,
+                triage: load_triage(&output.source_dir),
+            },
+            Some(&ProgressBarCallback::default()),
+        )
+    })?;
+
+    log::info!("Website built into {}.", cli_format_path(&output.web_dir));
+
+    open_result(&output.web_dir, open.unwrap_or(output.config.open))?;
+    Ok(())
+}
+
+fn log_crate_flags(crate_flags: &[CrateFlags]) {
+    for krate in crate_flags {
+        log::info!(
+            "Crate {} ({}) was compiled with {}",
+            krate.crate_name,
+            krate.crate_types.join(", "),
+            krate.flags.join(" ")
+        );
+    }
+}
+
+/// Records the current run into the run history, and returns the history of runs with the same
+/// profile and target.
+fn record_run_history(
+    output: &BuildOutput,
+    crate_flags: &[CrateFlags],
+    remarks: &[Remark],
+) -> Vec<HistoryEntry> {
+    let entry = HistoryEntry {
+        run: output
+            .remark_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        date: chrono::Local::now().to_rfc3339(),
+        git_commit: get_git_commit(&output.source_dir),
+        profile: output.profile.clone(),
+        target: output.target.clone(),
+        rustc_version: get_rustc_short_version().ok(),
+        flags: crate_flags.to_vec(),
+        summary: RemarkSummary::from_remarks(remarks),
+    };
+    if let Err(error) = record_history(&output.history_dir, &entry) {
+        log::warn!("Cannot record run history: {error:?}");
+    }
+    load_run_history(output)
+}
+
+/// Returns the history of runs with the same profile and target as `output`.
+fn load_run_history(output: &BuildOutput) -> Vec<HistoryEntry> {
+    match load_history(&output.history_dir) {
+        Ok(history) => history
+            .into_iter()
+            .filter(|e| e.profile == output.profile && e.target == output.target)
+            .collect(),
+        Err(error) => {
+            log::warn!("Cannot load run history: {error:?}");
+            vec![]
+        }
+    }
+}
+
+fn get_git_commit(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(dir)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Describes a remark in a single line.
+fn describe_remark(remark: &Remark) -> String {
+    let location = format_location(remark);
+    let hotness = remark
+        .hotness
+        .map(|hotness| format!(" (hotness {hotness})"))
+        .unwrap_or_default();
+    format!(
+        "{}/{} in {} at {location}{hotness}",
+        remark.pass, remark.name, remark.function.name
+    )
+}
+
+/// Formats the location of a remark as `file:line:column`.
+fn format_location(remark: &Remark) -> String {
+    remark
+        .function
+        .location
+        .as_ref()
+        .map(|l| format!("{}:{}:{}", l.file, l.line, l.column))
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+/// Maximum number of remarks of each change kind printed to the terminal.
+const MAX_PRINTED_CHANGES: usize = 20;
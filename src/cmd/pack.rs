@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use crate::cargo::version::get_rustc_version;
+use crate::cargo::{get_build_output, BuildSelection};
+use cargo_remark::archive::{pack_remarks, ARCHIVE_FILE_NAME};
+use cargo_remark::utils::callback::ProgressBarCallback;
+use cargo_remark::utils::cli::cli_format_path;
+use cargo_remark::utils::timing::time_block_log_info;
+
+use super::{load_options, LoadArgs};
+
+#[derive(clap::Parser, Debug)]
+pub struct PackArgs {
+    /// Path of the created archive.
+    /// Defaults to `remarks.tar.gz` inside the directory of the latest run.
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    load: LoadArgs,
+
+    #[clap(flatten)]
+    selection: BuildSelection,
+}
+
+pub fn run(args: PackArgs) -> anyhow::Result<()> {
+    let PackArgs {
+        output,
+        load,
+        selection,
+    } = args;
+    let build_output = get_build_output(&selection)?;
+    let archive_path = output.unwrap_or_else(|| build_output.remark_dir.join(ARCHIVE_FILE_NAME));
+
+    let rustc_version = match get_rustc_version() {
+        Ok(version) => Some(version),
+        Err(error) => {
+            log::warn!("Cannot find rustc version: {error:?}");
+            None
+        }
+    };
+
+    let options = load_options(&build_output.config, load, &build_output.source_dir)?;
+
+    let summary = time_block_log_info("Packing", || {
+        pack_remarks(
+            &build_output.yaml_dir,
+            options,
+            rustc_version,
+            &archive_path,
+            Some(&ProgressBarCallback::default()),
+        )
+    })?;
+
+    log::info!(
+        "Packed {} remark(s) from {} remark file(s) and {} source file(s) into {}.",
+        summary.remark_count,
+        summary.remark_file_count,
+        summary.source_file_count,
+        cli_format_path(&archive_path)
+    );
+    Ok(())
+}
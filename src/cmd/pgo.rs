@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use crate::cargo::pgo::{build_instrumented, merge_profiles, prepare_pgo_dirs, run_workload};
+use crate::cargo::toolchain::select_remark_collection;
+use crate::cargo::{
+    run_cargo, BuildSelection, CargoSubcommand, RemarkBuildOptions, RemarkCollection,
+};
+use cargo_remark::remark::Remark;
+use cargo_remark::utils::cli::flag_value;
+
+use super::{
+    describe_remark, load_build_remarks, load_options, render_build_remarks, GenerateArgs,
+};
+
+#[derive(clap::Parser, Debug)]
+#[clap(trailing_var_arg = true)]
+pub struct PgoArgs {
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    /// Path to `llvm-profdata` used to merge the gathered profiles.
+    /// Defaults to `llvm-profdata` from the `llvm-tools` component of the used toolchain, or
+    /// from `PATH`.
+    #[arg(long)]
+    llvm_profdata: Option<PathBuf>,
+
+    /// Number of the hottest remarks printed to the terminal.
+    #[arg(long, default_value_t = 20)]
+    report_count: usize,
+
+    #[clap(flatten)]
+    selection: BuildSelection,
+
+    /// Workload command that executes the instrumented binaries, e.g. `-- my-app bench.txt`.
+    /// The directory with the instrumented binaries is prepended to `PATH`.
+    #[arg(required = true)]
+    workload: Vec<String>,
+}
+
+pub fn run(args: PgoArgs) -> anyhow::Result<()> {
+    let PgoArgs {
+        generate:
+            GenerateArgs {
+                open,
+                no_open,
+                passes,
+                load,
+            },
+        llvm_profdata,
+        report_count,
+        selection,
+        workload,
+    } = args;
+
+    if select_remark_collection() == RemarkCollection::Diagnostics {
+        return Err(anyhow::anyhow!(
+            "Remarks collected from rustc diagnostics do not contain hotness, so PGO remarks require a toolchain that supports `-Zremark-dir`."
+        ));
+    }
+
+    let dirs = prepare_pgo_dirs(&selection)?;
+    let bin_dir = build_instrumented(&selection, &dirs)?;
+    run_workload(&workload, &bin_dir, &dirs)?;
+    merge_profiles(llvm_profdata, &dirs)?;
+
+    let output = run_cargo(
+        CargoSubcommand::Build,
+        selection.to_cargo_args(),
+        RemarkBuildOptions {
+            passes,
+            rustflags: vec![format!("-Cprofile-use={}", dirs.merged_profile.display())],
+            ..Default::default()
+        },
+    )?;
+    let options = load_options(&output.config, load, &output.source_dir)?;
+    let (remarks, crate_flags) = load_build_remarks(&output, options)?;
+
+    let mut hot: Vec<&Remark> = remarks
+        .iter()
+        .filter(|remark| remark.hotness.is_some())
+        .collect();
+    hot.sort_by_key(|remark| std::cmp::Reverse(remark.hotness));
+    let missing_hotness = !remarks.is_empty() && hot.is_empty();
+    if !hot.is_empty() {
+        println!("Hottest remarks:");
+        for remark in hot.iter().take(report_count) {
+            println!("{}", describe_remark(remark));
+        }
+        if hot.len() > report_count {
+            println!("... and {} more", hot.len() - report_count);
+        }
+    }
+
+    render_build_remarks(&output, remarks, crate_flags, flag_value(open, no_open))?;
+    if missing_hotness {
+        return Err(anyhow::anyhow!(
+            "None of the generated remarks contains hotness. Make sure that the workload exercises the code of the crate, and that the profile matches the built code."
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use crate::cargo::{get_build_output, get_watch_dir, BuildOutput, BuildSelection};
+use cargo_remark::remark::load_remarks_from_dir;
+use cargo_remark::render::INDEX_FILE_PATH;
+use cargo_remark::server::{Report, ReportSource};
+use cargo_remark::triage::TRIAGE_FILE_NAME;
+use colored::Colorize;
+
+use super::{load_options, LoadArgs};
+
+#[derive(clap::Parser, Debug)]
+pub struct ServeArgs {
+    /// Port on which the server listens.
+    #[arg(long, default_value_t = 8000)]
+    port: u16,
+
+    /// Open the served website in a browser.
+    #[arg(long)]
+    open: bool,
+
+    /// Serve the website of `cargo remark watch`, instead of the website of the latest run.
+    #[arg(long)]
+    watch: bool,
+
+    #[clap(flatten)]
+    load: LoadArgs,
+
+    #[clap(flatten)]
+    selection: BuildSelection,
+}
+
+/// Report of the latest run of the selected crate.
+struct LatestRunReport {
+    /// Output of the run that was the latest one when the server was started.
+    output: BuildOutput,
+    /// Website of `cargo remark watch`, which is served instead of the website of the run.
+    watch_web_dir: Option<PathBuf>,
+    load: LoadArgs,
+}
+
+impl LatestRunReport {
+    fn web_dir(&self, output: &BuildOutput) -> PathBuf {
+        self.watch_web_dir
+            .clone()
+            .unwrap_or_else(|| output.web_dir.clone())
+    }
+}
+
+impl ReportSource for LatestRunReport {
+    fn revision(&self) -> anyhow::Result<String> {
+        // Called periodically, so it only reads the latest run marker and the rendered index
+        let output = self.output.latest_run()?;
+        let modified = std::fs::metadata(self.web_dir(&output).join(INDEX_FILE_PATH))
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        Ok(format!("{}:{modified:?}", output.remark_dir.display()))
+    }
+
+    fn load(&self) -> anyhow::Result<Report> {
+        let output = self.output.latest_run()?;
+        let options = load_options(&output.config, self.load.clone(), &output.source_dir)?;
+        let remarks = load_remarks_from_dir(&output.yaml_dir, options, None)?;
+        Ok(Report {
+            web_dir: self.web_dir(&output),
+            remarks,
+            triage_file: Some(output.source_dir.join(TRIAGE_FILE_NAME)),
+        })
+    }
+}
+
+pub fn run(args: ServeArgs) -> anyhow::Result<()> {
+    let ServeArgs {
+        port,
+        open,
+        watch,
+        load,
+        selection,
+    } = args;
+
+    let watch_web_dir = if watch {
+        Some(get_watch_dir(&selection)?.join("web"))
+    } else {
+        None
+    };
+    let source = LatestRunReport {
+        output: get_build_output(&selection)?,
+        watch_web_dir,
+        load,
+    };
+    cargo_remark::server::serve(source, &format!("127.0.0.1:{port}"), |url| {
+        println!("Serving remarks at {}", url.bold());
+        println!("The JSON API is available at {url}/api/{{remarks,files,stats}}");
+        if open {
+            if let Err(error) = opener::open_browser(url) {
+                log::warn!("Could not open {url} in browser: {error:?}");
+            }
+        }
+    })
+}
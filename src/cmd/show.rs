@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use cargo_remark::query::{message_text, parse_file_location, RemarkQuery, RemarkSort};
+use colored::Colorize;
+
+use super::{load_latest_remarks, LatestRemarksArgs};
+
+#[derive(clap::Parser, Debug)]
+pub struct ShowArgs {
+    /// Source file, relative to the source directory, optionally with a line (e.g.
+    /// `src/main.rs:7`).
+    #[arg(value_name = "FILE[:LINE]")]
+    location: String,
+
+    /// Print the remarks as JSON.
+    #[arg(long)]
+    json: bool,
+
+    #[clap(flatten)]
+    remarks: LatestRemarksArgs,
+}
+
+pub fn run(args: ShowArgs) -> anyhow::Result<()> {
+    let ShowArgs {
+        location,
+        json,
+        remarks,
+    } = args;
+    let (remarks, source_dir) = load_latest_remarks(remarks)?;
+    let (file, line) = parse_file_location(&location);
+    // Remark locations are relative to the source directory
+    let file = Path::new(&file);
+    let file = file
+        .strip_prefix(&source_dir)
+        .or_else(|_| file.strip_prefix("."))
+        .unwrap_or(file)
+        .to_string_lossy()
+        .into_owned();
+    let page = RemarkQuery {
+        file: Some(file.clone()),
+        line,
+        sort: RemarkSort::Location,
+        ..Default::default()
+    }
+    .apply(&remarks);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&page.remarks)?);
+        return Ok(());
+    }
+
+    let source = std::fs::read_to_string(source_dir.join(&file)).unwrap_or_default();
+    let lines: Vec<&str> = source.lines().collect();
+    let mut previous_line = None;
+    for remark in &page.remarks {
+        let Some(ref location) = remark.function.location else {
+            continue;
+        };
+        if previous_line != Some(location.line) {
+            let code = lines
+                .get((location.line as usize).wrapping_sub(1))
+                .map(|code| code.trim())
+                .unwrap_or_default();
+            println!(
+                "{} {}",
+                format!("{}:{}", file, location.line).bold(),
+                code.dimmed()
+            );
+            previous_line = Some(location.line);
+        }
+        let hotness = remark
+            .hotness
+            .map(|hotness| format!(" (hotness {hotness})"))
+            .unwrap_or_default();
+        println!(
+            "  {}/{} in {}{hotness}: {}",
+            remark.pass.yellow(),
+            remark.name.yellow(),
+            remark.function.name,
+            message_text(remark)
+        );
+    }
+    println!("{} remark(s) in {location}", page.total);
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use cargo_remark::query::{top_counts, RemarkStats};
+use colored::Colorize;
+
+use super::{load_latest_remarks, LatestRemarksArgs};
+
+#[derive(clap::Parser, Debug)]
+pub struct StatsArgs {
+    /// Number of files and functions with the most remarks that are printed.
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// Print the statistics as JSON. All files and functions are included.
+    #[arg(long)]
+    json: bool,
+
+    #[clap(flatten)]
+    remarks: LatestRemarksArgs,
+}
+
+pub fn run(args: StatsArgs) -> anyhow::Result<()> {
+    let StatsArgs { top, json, remarks } = args;
+    let (remarks, _) = load_latest_remarks(remarks)?;
+    let stats = RemarkStats::from_remarks(&remarks);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    let summary = &stats.summary;
+    println!(
+        "{} remark(s), total hotness {}",
+        summary.total.count, summary.total.hotness
+    );
+    let sections = [
+        ("Passes", &summary.passes, usize::MAX),
+        ("Kinds", &summary.kinds, usize::MAX),
+        ("Files", &summary.files, top),
+        ("Functions", &stats.functions, top),
+    ];
+    for (title, counts, limit) in sections {
+        if counts.is_empty() {
+            continue;
+        }
+        let sorted = top_counts(counts);
+        let title = if sorted.len() > limit {
+            format!("{title} (top {limit} of {})", sorted.len())
+        } else {
+            title.to_string()
+        };
+        println!("\n{}", title.bold());
+        let sorted = &sorted[..sorted.len().min(limit)];
+        let width = sorted
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or_default();
+        for (name, counts) in sorted {
+            println!(
+                "  {name:<width$}  {:>6}  hotness {}",
+                counts.count, counts.hotness
+            );
+        }
+    }
+    Ok(())
+}
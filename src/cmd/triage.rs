@@ -0,0 +1,215 @@
+use crate::cargo::{get_build_output, get_workspace_root, BuildSelection};
+use cargo_remark::filter::FilterExpr;
+use cargo_remark::remark::{load_remarks_from_dir, Fingerprint, Remark};
+use cargo_remark::triage::{Triage, TriageStatus, TRIAGE_FILE_NAME};
+use cargo_remark::utils::data_structures::Set;
+use colored::Colorize;
+
+use super::{load_options, LoadArgs};
+
+#[derive(clap::Parser, Debug)]
+pub struct TriageArgs {
+    #[clap(subcommand)]
+    command: TriageCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum TriageCommand {
+    /// Set the triage status of remarks from the latest run.
+    Set(TriageSetArgs),
+    /// Remove remarks from the latest run from the triage state.
+    Clear(TriageSelectArgs),
+    /// List triaged remarks.
+    List(TriageListArgs),
+}
+
+/// Selects remarks from the latest run.
+#[derive(clap::Args, Debug)]
+struct TriageSelectArgs {
+    /// Fingerprints of the remarks, which are shown in the remark list of the website.
+    fingerprints: Vec<Fingerprint>,
+
+    /// Select remarks that match this filter expression, e.g. `file ~ "src/parser/**"`.
+    #[arg(long, value_name = "EXPR")]
+    include: Vec<FilterExpr>,
+
+    #[clap(flatten)]
+    selection: BuildSelection,
+}
+
+/// Triage status, as it is passed on the command line.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusArg {
+    /// The remark was looked at, and it does not need any action.
+    Reviewed,
+    /// The remark was looked at, and it will not be resolved.
+    WontFix,
+    /// The remark should be resolved.
+    Todo,
+}
+
+impl From<StatusArg> for TriageStatus {
+    fn from(status: StatusArg) -> Self {
+        match status {
+            StatusArg::Reviewed => TriageStatus::Reviewed,
+            StatusArg::WontFix => TriageStatus::WontFix,
+            StatusArg::Todo => TriageStatus::Todo,
+        }
+    }
+}
+
+#[derive(clap::Parser, Debug)]
+struct TriageSetArgs {
+    /// Triage status.
+    #[arg(long)]
+    status: StatusArg,
+
+    /// Note that explains the status.
+    #[arg(long)]
+    note: Option<String>,
+
+    #[clap(flatten)]
+    remarks: TriageSelectArgs,
+}
+
+#[derive(clap::Parser, Debug)]
+struct TriageListArgs {
+    /// Only list remarks with this status.
+    #[arg(long)]
+    status: Option<StatusArg>,
+
+    #[clap(flatten)]
+    selection: BuildSelection,
+}
+
+pub fn run(args: TriageArgs) -> anyhow::Result<()> {
+    match args.command {
+        TriageCommand::Set(args) => {
+            update_triage(args.remarks, Some(args.status.into()), args.note)
+        }
+        TriageCommand::Clear(args) => update_triage(args, None, None),
+        TriageCommand::List(args) => list_triage(args),
+    }
+}
+
+/// Sets the triage status of the selected remarks, or removes them from the triage state if
+/// `status` is `None`.
+fn update_triage(
+    args: TriageSelectArgs,
+    status: Option<TriageStatus>,
+    note: Option<String>,
+) -> anyhow::Result<()> {
+    let TriageSelectArgs {
+        fingerprints,
+        include,
+        selection,
+    } = args;
+    if fingerprints.is_empty() && include.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Select remarks by their fingerprints or with `--include`"
+        ));
+    }
+
+    let output = get_build_output(&selection)?;
+    // Remarks of any kind can be triaged, even if they are filtered out by default
+    let options = load_options(
+        &output.config,
+        LoadArgs {
+            external: true,
+            filter_kind: vec![String::new()],
+            ..Default::default()
+        },
+        &output.source_dir,
+    )?;
+    let remarks = load_remarks_from_dir(&output.yaml_dir, options, None)?;
+    let selected: Vec<&Remark> = remarks
+        .iter()
+        .filter(|remark| {
+            fingerprints.contains(&remark.fingerprint)
+                || include.iter().any(|expr| expr.matches(remark))
+        })
+        .collect();
+    let missing: Vec<String> = fingerprints
+        .iter()
+        .filter(|fingerprint| !selected.iter().any(|r| r.fingerprint == **fingerprint))
+        .map(|fingerprint| fingerprint.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Remarks {} were not found in the latest run",
+            missing.join(", ")
+        ));
+    }
+
+    let path = output.source_dir.join(TRIAGE_FILE_NAME);
+    let mut triage = Triage::load(&path)?;
+    for remark in &selected {
+        triage.set(remark, status, note.clone());
+    }
+    triage.store(&path)?;
+
+    let count = selected
+        .iter()
+        .map(|remark| remark.fingerprint)
+        .collect::<Set<_>>()
+        .len();
+    match status {
+        Some(status) => println!("{count} remark(s) marked as {}", status.as_str().bold()),
+        None => println!("{count} remark(s) removed from the triage state"),
+    }
+    Ok(())
+}
+
+fn list_triage(args: TriageListArgs) -> anyhow::Result<()> {
+    let TriageListArgs { status, selection } = args;
+    let status = status.map(TriageStatus::from);
+    let triage = Triage::load(&get_workspace_root(&selection)?.join(TRIAGE_FILE_NAME))?;
+    for (fingerprint, entry) in triage
+        .remarks
+        .iter()
+        .filter(|(_, entry)| status.is_none() || status == Some(entry.status))
+    {
+        let label = format!("{:<8}", entry.status.as_str());
+        let label = match entry.status {
+            TriageStatus::Reviewed => label.green(),
+            TriageStatus::WontFix => label.normal(),
+            TriageStatus::Todo => label.yellow(),
+        };
+        let file = entry
+            .file
+            .as_ref()
+            .map(|file| format!(" at {file}"))
+            .unwrap_or_default();
+        let note = entry
+            .note
+            .as_ref()
+            .map(|note| format!(": {note}"))
+            .unwrap_or_default();
+        println!(
+            "{fingerprint} {label} {} in {}{file}{note}",
+            entry.kind, entry.function
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cargo_remark::triage::TriageStatus;
+    use clap::ValueEnum;
+
+    use crate::cmd::triage::StatusArg;
+
+    #[test]
+    fn parse_status() {
+        assert_eq!(
+            TriageStatus::from(StatusArg::from_str("wont-fix", false).unwrap()),
+            TriageStatus::WontFix
+        );
+        assert!(StatusArg::from_str("done", false).is_err());
+        for status in StatusArg::value_variants() {
+            let value = status.to_possible_value().unwrap();
+            assert_eq!(value.get_name(), TriageStatus::from(*status).as_str());
+        }
+    }
+}
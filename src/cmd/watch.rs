@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::cargo::toolchain::select_remark_collection;
+use crate::cargo::watch::SourceWatcher;
+use crate::cargo::{
+    get_watch_dir, parse_build_selection, run_cargo, CargoSubcommand, RemarkBuildOptions,
+};
+use cargo_remark::diff::{diff_remarks, RemarkChange, RemarkDiff, RemarkDiffEntry};
+use cargo_remark::remark::Remark;
+use cargo_remark::render::{render_remarks, RenderOptions};
+use cargo_remark::triage::load_triage;
+use cargo_remark::utils::cli::{cli_format_path, flag_value};
+use cargo_remark::utils::data_structures::Set;
+use cargo_remark::utils::io::normalize_separators;
+use cargo_remark::utils::open_result;
+use cargo_remark::utils::timing::time_block_log_info;
+use colored::Colorize;
+
+use super::{
+    describe_remark, load_build_remarks, load_options, load_run_history, GenerateArgs,
+    MAX_PRINTED_CHANGES,
+};
+
+#[derive(clap::Parser, Debug)]
+#[clap(trailing_var_arg = true)]
+pub struct WatchArgs {
+    #[clap(flatten)]
+    generate: GenerateArgs,
+
+    /// How often (in milliseconds) are the sources checked for changes.
+    #[arg(long, default_value_t = 500)]
+    interval: u64,
+
+    /// Additional arguments that will be passed to Cargo.
+    cargo_args: Vec<String>,
+}
+
+pub fn run(args: WatchArgs) -> anyhow::Result<()> {
+    let WatchArgs {
+        generate:
+            GenerateArgs {
+                open,
+                no_open,
+                passes,
+                load,
+            },
+        interval,
+        cargo_args,
+    } = args;
+
+    let selection = parse_build_selection(&cargo_args);
+    let web_dir = get_watch_dir(&selection)?.join("web");
+    let collection = select_remark_collection();
+    let mut watcher = SourceWatcher::new(&selection, Duration::from_millis(interval))?;
+
+    let mut previous: Option<(Vec<Remark>, PathBuf)> = None;
+    let mut changed_files: Vec<String> = vec![];
+    let mut opened = false;
+    loop {
+        let result = run_cargo(
+            CargoSubcommand::Build,
+            cargo_args.clone(),
+            RemarkBuildOptions {
+                passes: passes.clone(),
+                collection,
+                ..Default::default()
+            },
+        )
+        .and_then(|output| {
+            let options = load_options(&output.config, load.clone(), &output.source_dir)?;
+            let (remarks, crate_flags) = load_build_remarks(&output, options)?;
+            Ok((output, remarks, crate_flags))
+        });
+        match result {
+            Ok((output, remarks, crate_flags)) => {
+                // Watched paths use native separators, remark locations always use `/`
+                let edited_files: Set<String> = changed_files
+                    .iter()
+                    .map(|file| normalize_separators(Path::new(file)))
+                    .collect();
+                // Only pages affected by the changes are rendered again
+                let rerender_files = match previous.take() {
+                    Some((previous_remarks, previous_run)) => {
+                        let diff = diff_remarks(previous_remarks, remarks.clone());
+                        print_watch_summary(&diff, &edited_files);
+                        // Each iteration creates a new run, only the latest one is kept
+                        if let Err(error) = std::fs::remove_dir_all(&previous_run) {
+                            log::warn!("Cannot remove previous run: {error:?}");
+                        }
+
+                        let mut files = edited_files;
+                        files.extend(
+                            diff.entries
+                                .into_iter()
+                                .filter(|entry| entry.change != RemarkChange::Unchanged)
+                                .filter_map(|entry| entry.remark.function.location)
+                                .map(|location| normalize_separators(Path::new(&location.file))),
+                        );
+                        Some(files)
+                    }
+                    None => {
+                        println!("{} remark(s) found", remarks.len().to_string().bold());
+                        None
+                    }
+                };
+                let options = RenderOptions {
+                    crate_flags,
+                    history: load_run_history(&output),
+                    rerender_files,
+                    triage: load_triage(&output.source_dir),
+                };
+                time_block_log_info("Rendering", || {
+                    render_remarks(remarks.clone(), &output.source_dir, &web_dir, options, None)
+                })?;
+                log::info!("Website built into {}.", cli_format_path(&web_dir));
+                if !opened {
+                    open_result(
+                        &web_dir,
+                        flag_value(open, no_open).unwrap_or(output.config.open),
+                    )?;
+                    opened = true;
+                }
+                previous = Some((remarks, output.remark_dir));
+            }
+            // Errors (e.g. compilation errors) are reported, and the sources are watched further
+            Err(error) => log::error!("{error:?}"),
+        }
+
+        log::info!("Watching for changes, press Ctrl+C to stop");
+        changed_files = watcher.wait_for_changes();
+        log::info!("Detected changes in {}", changed_files.join(", "));
+    }
+}
+
+/// Prints remarks that were added or removed in the edited files.
+fn print_watch_summary(diff: &RemarkDiff, edited_files: &Set<String>) {
+    let entries: Vec<&RemarkDiffEntry> = diff
+        .entries
+        .iter()
+        .filter(|entry| matches!(entry.change, RemarkChange::Added | RemarkChange::Removed))
+        .filter(|entry| {
+            entry
+                .remark
+                .function
+                .location
+                .as_ref()
+                .is_some_and(|location| {
+                    edited_files.contains(&normalize_separators(Path::new(&location.file)))
+                })
+        })
+        .collect();
+    let count = |change: RemarkChange| {
+        entries
+            .iter()
+            .filter(|entry| entry.change == change)
+            .count()
+    };
+    println!(
+        "{} added, {} removed in edited files",
+        count(RemarkChange::Added).to_string().red(),
+        count(RemarkChange::Removed).to_string().green(),
+    );
+    for entry in entries.iter().take(MAX_PRINTED_CHANGES) {
+        let marker = match entry.change {
+            RemarkChange::Added => "+".red(),
+            _ => "-".green(),
+        };
+        println!("{marker} {}", describe_remark(&entry.remark));
+    }
+    if entries.len() > MAX_PRINTED_CHANGES {
+        println!("... and {} more", entries.len() - MAX_PRINTED_CHANGES);
+    }
+}
@@ -0,0 +1,446 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+use serde_json::Value;
+
+use crate::filter::{FilterExpr, RemarkFilter};
+use crate::remark::PathMapping;
+use crate::DEFAULT_KIND_FILTER;
+
+/// Name of the configuration file, which is loaded from the workspace root.
+pub const CONFIG_FILE_NAME: &str = "remark.toml";
+
+/// Key of the configuration table inside `[workspace.metadata]` and `[package.metadata]`.
+const METADATA_KEY: &str = "remark";
+
+/// Number of run directories that are kept by default.
+const DEFAULT_KEEP_RUNS: usize = 10;
+
+/// Default values of CLI arguments of `cargo remark` and `analyze-remarks`.
+/// Arguments passed on the command line take precedence over the configuration.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RemarkConfig {
+    /// Optimization remark kinds that should be ignored.
+    pub filter: Vec<String>,
+    /// Only remarks that match at least one of these filter expressions are loaded.
+    pub include: Vec<FilterExpr>,
+    /// Remarks that match any of these filter expressions are ignored.
+    pub exclude: Vec<FilterExpr>,
+    /// Load remarks from external code (i.e. crate dependencies).
+    pub external: bool,
+    /// Open the generated website.
+    pub open: bool,
+    /// Dependencies that should also generate remarks.
+    pub remark_packages: Vec<String>,
+    /// LLVM passes that should generate remarks (all passes if empty).
+    pub passes: Vec<String>,
+    /// Remarks whose hotness is lower than this value are ignored when they are loaded (like with
+    /// `--exclude 'hotness < N'`). rustc still generates them.
+    pub hotness_threshold: Option<i32>,
+    /// Sysroot directory of the Rust toolchain which generated the remarks.
+    pub sysroot: Option<PathBuf>,
+    /// Path prefixes that should be remapped, in the `FROM=TO` format. Relative targets are
+    /// resolved against the directory of the configuration file.
+    pub remap_path_prefix: Vec<String>,
+    /// Number of most recent run directories that are kept, older runs are removed after each
+    /// build. All runs are kept if it is `0`.
+    pub keep_runs: usize,
+    /// Configurations compared by `cargo remark matrix`.
+    pub matrix: Vec<MatrixConfiguration>,
+}
+
+/// Named build configuration of `cargo remark matrix`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MatrixConfiguration {
+    pub name: String,
+    /// Additional rustc flags passed to the crates that generate remarks.
+    #[serde(default)]
+    pub rustflags: Vec<String>,
+    /// Additional arguments passed to Cargo (e.g. `--profile` or `--config`).
+    #[serde(default)]
+    pub cargo_args: Vec<String>,
+}
+
+impl FromStr for MatrixConfiguration {
+    type Err = anyhow::Error;
+
+    /// Parses a configuration in the `NAME=RUSTFLAGS` format, where the flags are separated by
+    /// whitespace.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, rustflags) = value.split_once('=').unwrap_or((value, ""));
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Invalid configuration `{value}`, expected `NAME=RUSTFLAGS`"
+            ));
+        }
+        check_configuration_name(name)?;
+        Ok(Self {
+            name: name.to_string(),
+            rustflags: rustflags
+                .split_whitespace()
+                .map(|s| s.to_string())
+                .collect(),
+            cargo_args: vec![],
+        })
+    }
+}
+
+/// Checks that a configuration name can be used as the name of the directory with its remarks
+/// (`target/remarks/matrix/<name>`).
+fn check_configuration_name(name: &str) -> anyhow::Result<()> {
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if name.is_empty() || !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid configuration name `{name}`, only ASCII letters, digits, `_` and `-` are allowed"
+        ));
+    }
+    Ok(())
+}
+
+impl Default for RemarkConfig {
+    fn default() -> Self {
+        Self {
+            filter: DEFAULT_KIND_FILTER.iter().map(|s| s.to_string()).collect(),
+            include: vec![],
+            exclude: vec![],
+            external: false,
+            open: false,
+            remark_packages: vec![],
+            passes: vec![],
+            hotness_threshold: None,
+            sysroot: None,
+            remap_path_prefix: vec![],
+            keep_runs: DEFAULT_KEEP_RUNS,
+            matrix: vec![],
+        }
+    }
+}
+
+impl RemarkConfig {
+    /// Returns the remark kinds passed on the command line, or the configured kinds if no kinds
+    /// were passed.
+    pub fn filter_kind(&self, cli_filter: Vec<String>) -> Vec<String> {
+        if cli_filter.is_empty() {
+            self.filter.clone()
+        } else {
+            cli_filter
+        }
+    }
+
+    /// Returns a filter with the expressions passed on the command line. The configured
+    /// expressions are used for include or exclude lists that were not passed.
+    /// Remarks below the configured hotness threshold are always excluded.
+    pub fn remark_filter(
+        &self,
+        include: Vec<FilterExpr>,
+        exclude: Vec<FilterExpr>,
+    ) -> RemarkFilter {
+        let or_config = |cli: Vec<FilterExpr>, config: &[FilterExpr]| {
+            if cli.is_empty() {
+                config.to_vec()
+            } else {
+                cli
+            }
+        };
+        let mut exclude = or_config(exclude, &self.exclude);
+        // rustc does not expose the hotness threshold of LLVM, so remarks below the threshold are
+        // generated, and they are only ignored when loading.
+        if let Some(threshold) = self.hotness_threshold {
+            exclude.push(
+                format!("hotness < {threshold}")
+                    .parse()
+                    .expect("Invalid hotness threshold expression"),
+            );
+        }
+        RemarkFilter {
+            include: or_config(include, &self.include),
+            exclude,
+        }
+    }
+
+    pub fn path_mappings(&self) -> anyhow::Result<Vec<PathMapping>> {
+        self.remap_path_prefix
+            .iter()
+            .map(|mapping| mapping.parse())
+            .collect()
+    }
+}
+
+/// Configuration merged from all found sources.
+#[derive(Debug, Default)]
+pub struct LoadedConfig {
+    pub config: RemarkConfig,
+    /// Descriptions of the sources from which the configuration was loaded, in the order in which
+    /// they were applied.
+    pub sources: Vec<String>,
+}
+
+impl LoadedConfig {
+    /// Renders the configuration in the format of `remark.toml`.
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        let mut output = String::new();
+        if self.sources.is_empty() {
+            output.push_str("# No configuration found, using defaults\n");
+        }
+        for source in &self.sources {
+            output.push_str(&format!("# Loaded from {source}\n"));
+        }
+        output.push_str(&toml::to_string_pretty(&self.config)?);
+        Ok(output)
+    }
+}
+
+/// Merges configuration tables. Keys of later tables override the keys of earlier tables.
+#[derive(Default)]
+pub struct ConfigLoader {
+    table: serde_json::Map<String, Value>,
+    sources: Vec<String>,
+}
+
+impl ConfigLoader {
+    /// Adds the `remark` table from Cargo metadata (e.g. the value of `[package.metadata]`).
+    /// Relative paths are resolved against `base_dir`.
+    pub fn add_metadata(
+        &mut self,
+        metadata: &Value,
+        base_dir: &Path,
+        source: &str,
+    ) -> anyhow::Result<()> {
+        match metadata.get(METADATA_KEY) {
+            Some(table) => self.add_table(table.clone(), base_dir, source),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds the `[workspace.metadata.remark]` and `[package.metadata.remark]` tables of the
+    /// `Cargo.toml` manifest at `path`, if it exists.
+    pub fn add_manifest(&mut self, path: &Path) -> anyhow::Result<()> {
+        let Some(manifest) = read_toml(path)? else {
+            return Ok(());
+        };
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        for section in ["workspace", "package"] {
+            if let Some(metadata) = manifest
+                .get(section)
+                .and_then(|table| table.get("metadata"))
+            {
+                self.add_metadata(
+                    metadata,
+                    base_dir,
+                    &format!("[{section}.metadata.{METADATA_KEY}] in {}", path.display()),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the configuration file at `path` (usually `remark.toml`), if it exists.
+    pub fn add_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        let Some(table) = read_toml(path)? else {
+            return Ok(());
+        };
+        self.add_table(
+            table,
+            path.parent().unwrap_or(Path::new(".")),
+            &path.display().to_string(),
+        )
+    }
+
+    pub fn finish(self) -> anyhow::Result<LoadedConfig> {
+        let config = serde_json::from_value(Value::Object(self.table))
+            .context("Cannot parse remark configuration")?;
+        Ok(LoadedConfig {
+            config,
+            sources: self.sources,
+        })
+    }
+
+    fn add_table(&mut self, table: Value, base_dir: &Path, source: &str) -> anyhow::Result<()> {
+        let Value::Object(mut table) = table else {
+            return Err(anyhow::anyhow!("Configuration in {source} is not a table"));
+        };
+        // Validate each source separately, to report which one is invalid
+        let config = serde_json::from_value::<RemarkConfig>(Value::Object(table.clone()))
+            .with_context(|| format!("Invalid configuration in {source}"))?;
+        for configuration in &config.matrix {
+            check_configuration_name(&configuration.name)
+                .with_context(|| format!("Invalid configuration in {source}"))?;
+        }
+
+        if let Some(Value::String(sysroot)) = table.get_mut("sysroot") {
+            *sysroot = base_dir.join(&*sysroot).to_string_lossy().into_owned();
+        }
+        if let Some(Value::Array(mappings)) = table.get_mut("remap-path-prefix") {
+            for mapping in mappings {
+                if let Value::String(mapping) = mapping {
+                    if let Some((from, to)) = mapping.split_once('=') {
+                        *mapping = format!("{from}={}", base_dir.join(to).to_string_lossy());
+                    }
+                }
+            }
+        }
+        self.table.extend(table);
+        self.sources.push(source.to_string());
+        Ok(())
+    }
+}
+
+/// Loads the configuration of `analyze-remarks` from `Cargo.toml` and `remark.toml` located in
+/// `dir`.
+pub fn load_config_from_dir(dir: &Path) -> anyhow::Result<LoadedConfig> {
+    let mut loader = ConfigLoader::default();
+    loader.add_manifest(&dir.join("Cargo.toml"))?;
+    loader.add_file(&dir.join(CONFIG_FILE_NAME))?;
+    loader.finish()
+}
+
+/// Reads a TOML file, returns `None` if it does not exist.
+fn read_toml(path: &Path) -> anyhow::Result<Option<Value>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    let table: toml::Table =
+        toml::from_str(&content).with_context(|| format!("Cannot parse {}", path.display()))?;
+    Ok(Some(serde_json::to_value(table)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::config::{load_config_from_dir, ConfigLoader, MatrixConfiguration, RemarkConfig};
+
+    #[test]
+    fn default_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let loaded = load_config_from_dir(dir.path()).unwrap();
+        assert_eq!(loaded.config, RemarkConfig::default());
+        assert!(loaded.sources.is_empty());
+        assert_eq!(
+            loaded.config.filter_kind(vec![]),
+            vec!["FastISelFailure", "NeverInline", "SpillReloadCopies"]
+        );
+        assert_eq!(loaded.config.filter_kind(vec!["".to_string()]), vec![""]);
+    }
+
+    #[test]
+    fn hotness_threshold_filter() {
+        let config = RemarkConfig {
+            exclude: vec!["pass == \"gvn\"".parse().unwrap()],
+            hotness_threshold: Some(100),
+            ..Default::default()
+        };
+        let filter = config.remark_filter(vec![], vec![]);
+        assert_eq!(
+            filter
+                .exclude
+                .iter()
+                .map(|expr| expr.to_string())
+                .collect::<Vec<_>>(),
+            vec!["pass == \"gvn\"", "hotness < 100"]
+        );
+        // The threshold also applies when exclude expressions are passed on the command line
+        let filter = config.remark_filter(vec![], vec!["kind == \"NeverInline\"".parse().unwrap()]);
+        assert_eq!(filter.exclude.len(), 2);
+    }
+
+    #[test]
+    fn merge_config_sources() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            r#"
+[package]
+name = "foo"
+
+[package.metadata.remark]
+filter = ["LoadClobbered"]
+external = true
+
+[workspace.metadata.remark]
+filter = ["NeverInline"]
+open = true
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("remark.toml"),
+            r#"
+external = false
+sysroot = "toolchain"
+remap-path-prefix = ["/build=src"]
+"#,
+        )
+        .unwrap();
+
+        let loaded = load_config_from_dir(dir.path()).unwrap();
+        let config = loaded.config;
+        assert_eq!(config.filter, vec!["LoadClobbered"]);
+        assert!(config.open);
+        assert!(!config.external);
+        assert_eq!(config.sysroot, Some(dir.path().join("toolchain")));
+        let mapping = &config.path_mappings().unwrap()[0];
+        assert_eq!(mapping.from, "/build");
+        assert_eq!(mapping.to, dir.path().join("src"));
+        assert_eq!(loaded.sources.len(), 3);
+    }
+
+    #[test]
+    fn matrix_config() {
+        let mut loader = ConfigLoader::default();
+        loader
+            .add_metadata(
+                &serde_json::json!({"remark": {"matrix": [
+                    {"name": "native", "rustflags": ["-Ctarget-cpu=native"]},
+                    {"name": "lto", "cargo-args": ["--config", "profile.release.lto=true"]},
+                ]}}),
+                Path::new("."),
+                "test",
+            )
+            .unwrap();
+        let loaded = loader.finish().unwrap();
+        assert_eq!(loaded.config.matrix.len(), 2);
+        assert_eq!(loaded.config.matrix[1].cargo_args[0], "--config");
+        assert!(loaded.to_toml().unwrap().contains("[[matrix]]"));
+
+        let configuration: MatrixConfiguration =
+            "cgu1=-Ccodegen-units=1 -Copt-level=2".parse().unwrap();
+        assert_eq!(configuration.name, "cgu1");
+        assert_eq!(
+            configuration.rustflags,
+            vec!["-Ccodegen-units=1", "-Copt-level=2"]
+        );
+        assert!("=-Copt-level=2".parse::<MatrixConfiguration>().is_err());
+        assert!("../x=-Copt-level=2".parse::<MatrixConfiguration>().is_err());
+
+        let mut loader = ConfigLoader::default();
+        assert!(loader
+            .add_metadata(
+                &serde_json::json!({"remark": {"matrix": [{"name": "a/b"}]}}),
+                Path::new("."),
+                "test",
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn invalid_config() {
+        let mut loader = ConfigLoader::default();
+        let error = loader
+            .add_metadata(
+                &serde_json::json!({"remark": {"filters": []}}),
+                Path::new("."),
+                "test",
+            )
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid configuration in test");
+    }
+}
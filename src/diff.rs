@@ -0,0 +1,264 @@
+use crate::remark::{Column, Fingerprint, Line, Remark};
+use crate::utils::data_structures::Map;
+
+/// Describes how did a remark change between two remark sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RemarkChange {
+    /// The remark is only present in the new set.
+    Added,
+    /// The remark is only present in the old set.
+    Removed,
+    Unchanged,
+    /// The remark is present in both sets, but its hotness has changed.
+    HotnessChanged,
+}
+
+impl RemarkChange {
+    pub const ALL: [RemarkChange; 4] = [
+        RemarkChange::Added,
+        RemarkChange::Removed,
+        RemarkChange::HotnessChanged,
+        RemarkChange::Unchanged,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemarkChange::Added => "added",
+            RemarkChange::Removed => "removed",
+            RemarkChange::Unchanged => "unchanged",
+            RemarkChange::HotnessChanged => "hotness-changed",
+        }
+    }
+}
+
+pub struct RemarkDiffEntry {
+    pub change: RemarkChange,
+    /// The remark from the new set, or from the old set if it was removed.
+    pub remark: Remark,
+    /// Hotness of the matching remark from the old set.
+    pub old_hotness: Option<i32>,
+}
+
+pub struct RemarkDiff {
+    pub entries: Vec<RemarkDiffEntry>,
+}
+
+impl RemarkDiff {
+    pub fn count(&self, change: RemarkChange) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.change == change)
+            .count()
+    }
+}
+
+/// Compares two remark sets.
+///
+/// Remarks are matched by their [`Fingerprint`]. Remarks with the same fingerprint are first
+/// matched at the same location, and the rest is matched in the order of their locations, so that
+/// remarks are still matched when the code around them has shifted.
+pub fn diff_remarks(old: Vec<Remark>, new: Vec<Remark>) -> RemarkDiff {
+    // Indices of old and new remarks with the same fingerprint
+    let mut groups: Map<Fingerprint, (Vec<usize>, Vec<usize>)> = Map::default();
+    for (index, remark) in new.iter().enumerate() {
+        groups.entry(remark.fingerprint).or_default().1.push(index);
+    }
+    for (index, remark) in old.iter().enumerate() {
+        groups.entry(remark.fingerprint).or_default().0.push(index);
+    }
+
+    let mut matches: Vec<Option<usize>> = vec![None; new.len()];
+    let mut old_matched = vec![false; old.len()];
+    for (mut old_indices, mut new_indices) in groups.into_values() {
+        old_indices.sort_by_key(|&index| position(&old[index]));
+        new_indices.sort_by_key(|&index| position(&new[index]));
+
+        // Match remarks on the same line first
+        new_indices.retain(|&new_index| {
+            let line = position(&new[new_index]);
+            match old_indices
+                .iter()
+                .position(|&old_index| position(&old[old_index]) == line)
+            {
+                Some(position) => {
+                    let old_index = old_indices.remove(position);
+                    matches[new_index] = Some(old_index);
+                    old_matched[old_index] = true;
+                    false
+                }
+                None => true,
+            }
+        });
+        for (new_index, old_index) in new_indices.into_iter().zip(old_indices) {
+            matches[new_index] = Some(old_index);
+            old_matched[old_index] = true;
+        }
+    }
+
+    let old_hotness: Vec<Option<i32>> = old.iter().map(|remark| remark.hotness).collect();
+    let mut entries: Vec<RemarkDiffEntry> = new
+        .into_iter()
+        .zip(matches)
+        .map(|(remark, matched)| match matched {
+            Some(old_index) => {
+                let old_hotness = old_hotness[old_index];
+                let change = if old_hotness == remark.hotness {
+                    RemarkChange::Unchanged
+                } else {
+                    RemarkChange::HotnessChanged
+                };
+                RemarkDiffEntry {
+                    change,
+                    remark,
+                    old_hotness,
+                }
+            }
+            None => RemarkDiffEntry {
+                change: RemarkChange::Added,
+                remark,
+                old_hotness: None,
+            },
+        })
+        .collect();
+    entries.extend(
+        old.into_iter()
+            .zip(old_matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(remark, _)| RemarkDiffEntry {
+                change: RemarkChange::Removed,
+                old_hotness: remark.hotness,
+                remark,
+            }),
+    );
+    RemarkDiff { entries }
+}
+
+fn position(remark: &Remark) -> Option<(&str, Line, Column)> {
+    remark
+        .function
+        .location
+        .as_ref()
+        .map(|location| (location.file.as_str(), location.line, location.column))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::{diff_remarks, RemarkChange};
+    use crate::remark::{Remark, RemarkBuilder};
+
+    #[test]
+    fn diff_unchanged() {
+        let diff = diff_remarks(
+            vec![remark("LoadClobbered", 10, None)],
+            vec![remark("LoadClobbered", 10, None)],
+        );
+        assert_eq!(changes(&diff.entries), vec![RemarkChange::Unchanged]);
+    }
+
+    #[test]
+    fn diff_added_removed() {
+        let diff = diff_remarks(
+            vec![remark("LoadClobbered", 10, None)],
+            vec![remark("NoDefinition", 10, None)],
+        );
+        assert_eq!(
+            changes(&diff.entries),
+            vec![RemarkChange::Added, RemarkChange::Removed]
+        );
+        assert_eq!(diff.entries[0].remark.name, "NoDefinition");
+        assert_eq!(diff.entries[1].remark.name, "LoadClobbered");
+    }
+
+    #[test]
+    fn diff_line_shift() {
+        let diff = diff_remarks(
+            vec![
+                remark("LoadClobbered", 10, None),
+                remark("LoadClobbered", 20, None),
+            ],
+            vec![
+                remark("LoadClobbered", 15, None),
+                remark("LoadClobbered", 25, None),
+                remark("LoadClobbered", 30, None),
+            ],
+        );
+        assert_eq!(
+            changes(&diff.entries),
+            vec![
+                RemarkChange::Unchanged,
+                RemarkChange::Unchanged,
+                RemarkChange::Added
+            ]
+        );
+        assert_eq!(diff.count(RemarkChange::Added), 1);
+    }
+
+    #[test]
+    fn diff_prefer_same_line() {
+        let diff = diff_remarks(
+            vec![
+                remark("LoadClobbered", 10, None),
+                remark("LoadClobbered", 20, Some(5)),
+            ],
+            vec![remark("LoadClobbered", 20, Some(5))],
+        );
+        assert_eq!(
+            changes(&diff.entries),
+            vec![RemarkChange::Unchanged, RemarkChange::Removed]
+        );
+        assert_eq!(
+            diff.entries[1]
+                .remark
+                .function
+                .location
+                .as_ref()
+                .unwrap()
+                .line,
+            10
+        );
+    }
+
+    #[test]
+    fn diff_hotness_changed() {
+        let diff = diff_remarks(
+            vec![remark("LoadClobbered", 10, Some(5))],
+            vec![remark("LoadClobbered", 12, Some(50))],
+        );
+        assert_eq!(changes(&diff.entries), vec![RemarkChange::HotnessChanged]);
+        assert_eq!(diff.entries[0].old_hotness, Some(5));
+        assert_eq!(diff.entries[0].remark.hotness, Some(50));
+    }
+
+    #[test]
+    fn diff_by_fingerprint() {
+        // Numbers in messages (e.g. inlining costs) are not part of the fingerprint
+        let diff = diff_remarks(
+            vec![RemarkBuilder::new("inline", "TooCostly")
+                .message("cost=100")
+                .build()],
+            vec![
+                RemarkBuilder::new("inline", "TooCostly")
+                    .message("cost=120")
+                    .build(),
+                RemarkBuilder::new("inline", "TooCostly")
+                    .message("no definition")
+                    .build(),
+            ],
+        );
+        assert_eq!(
+            changes(&diff.entries),
+            vec![RemarkChange::Unchanged, RemarkChange::Added]
+        );
+    }
+
+    fn changes(entries: &[crate::diff::RemarkDiffEntry]) -> Vec<RemarkChange> {
+        entries.iter().map(|entry| entry.change).collect()
+    }
+
+    fn remark(name: &str, line: u32, hotness: Option<i32>) -> Remark {
+        RemarkBuilder::new("gvn", name)
+            .location("src/lib.rs", line, 5)
+            .hotness(hotness)
+            .build()
+    }
+}
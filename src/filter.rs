@@ -0,0 +1,546 @@
+//! Expression language for selecting remarks.
+//!
+//! An expression compares remark fields with values, and combines the comparisons with `&&`, `||`,
+//! `!` and parentheses, e.g. `pass == "gvn" && file ~ "src/hot/**" && hotness > 1000`.
+//!
+//! String fields (`pass`, `name` or `kind`, `file`, `function`, `crate`, `message`) support `==`,
+//! `!=` and `~`. `~` matches a glob pattern for `file`, and a regular expression for the other
+//! fields. `hotness` supports `==`, `!=`, `<`, `<=`, `>` and `>=`, and it never matches remarks
+//! without hotness.
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::query::message_text;
+use crate::remark::Remark;
+
+/// Selects which remarks should be loaded.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RemarkFilter {
+    /// If not empty, only remarks that match at least one of these expressions are loaded.
+    pub include: Vec<FilterExpr>,
+    /// Remarks that match any of these expressions are ignored.
+    pub exclude: Vec<FilterExpr>,
+}
+
+impl RemarkFilter {
+    pub fn matches(&self, remark: &Remark) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|expr| expr.matches(remark)))
+            && !self.exclude.iter().any(|expr| expr.matches(remark))
+    }
+}
+
+/// Parsed filter expression.
+#[derive(Debug, Clone)]
+pub struct FilterExpr {
+    source: String,
+    expr: Expr,
+}
+
+impl FilterExpr {
+    pub fn matches(&self, remark: &Remark) -> bool {
+        self.expr.matches(remark)
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let expr = Parser::new(source)
+            .and_then(|parser| parser.parse())
+            .map_err(|error| anyhow::anyhow!("Invalid filter `{source}`: {error}"))?;
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+}
+
+impl PartialEq for FilterExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Display for FilterExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl serde::Serialize for FilterExpr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for FilterExpr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Pass,
+    Name,
+    File,
+    Function,
+    Crate,
+    Hotness,
+    Message,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        let field = match name {
+            "pass" => Field::Pass,
+            "name" | "kind" => Field::Name,
+            "file" => Field::File,
+            "function" => Field::Function,
+            "crate" => Field::Crate,
+            "hotness" => Field::Hotness,
+            "message" => Field::Message,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    fn string_value(&self, remark: &Remark) -> String {
+        match self {
+            Field::Pass => remark.pass.clone(),
+            Field::Name => remark.name.clone(),
+            Field::File => remark
+                .function
+                .location
+                .as_ref()
+                .map(|location| location.file.clone())
+                .unwrap_or_default(),
+            Field::Function => remark.function.name.clone(),
+            Field::Crate => remark
+                .origin
+                .as_ref()
+                .and_then(|origin| origin.crate_name.clone())
+                .unwrap_or_default(),
+            Field::Message => message_text(remark),
+            Field::Hotness => unreachable!("Hotness is not a string field"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    String {
+        field: Field,
+        negate: bool,
+        value: String,
+    },
+    Pattern {
+        field: Field,
+        regex: Regex,
+    },
+    Hotness {
+        operator: Operator,
+        value: i64,
+    },
+}
+
+impl Expr {
+    fn matches(&self, remark: &Remark) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(remark) && right.matches(remark),
+            Expr::Or(left, right) => left.matches(remark) || right.matches(remark),
+            Expr::Not(expr) => !expr.matches(remark),
+            Expr::String {
+                field,
+                negate,
+                value,
+            } => (&field.string_value(remark) == value) != *negate,
+            Expr::Pattern { field, regex } => regex.is_match(&field.string_value(remark)),
+            Expr::Hotness { operator, value } => {
+                let Some(hotness) = remark.hotness else {
+                    return false;
+                };
+                let hotness = hotness as i64;
+                match operator {
+                    Operator::Eq => hotness == *value,
+                    Operator::Ne => hotness != *value,
+                    Operator::Lt => hotness < *value,
+                    Operator::Le => hotness <= *value,
+                    Operator::Gt => hotness > *value,
+                    Operator::Ge => hotness >= *value,
+                    Operator::Match => unreachable!("Hotness cannot be matched by a pattern"),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    String(String),
+    Number(i64),
+    Operator(Operator),
+    Not,
+    And,
+    Or,
+    LeftParen,
+    RightParen,
+}
+
+fn tokenize(input: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut next_is = |expected: char| chars.next_if_eq(&expected).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '~' => Token::Operator(Operator::Match),
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '=' if next_is('=') => Token::Operator(Operator::Eq),
+            '!' if next_is('=') => Token::Operator(Operator::Ne),
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Operator(Operator::Le),
+            '<' => Token::Operator(Operator::Lt),
+            '>' if next_is('=') => Token::Operator(Operator::Ge),
+            '>' => Token::Operator(Operator::Gt),
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => value.push(c),
+                            None => return Err(anyhow::anyhow!("unterminated string")),
+                        },
+                        Some(c) => value.push(c),
+                        None => return Err(anyhow::anyhow!("unterminated string")),
+                    }
+                }
+                Token::String(value)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut value = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                    value.push(c);
+                }
+                Token::Number(
+                    value
+                        .parse()
+                        .map_err(|_| anyhow::anyhow!("invalid number `{value}`"))?,
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut value = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    value.push(c);
+                }
+                Token::Ident(value)
+            }
+            c => return Err(anyhow::anyhow!("unexpected character `{c}`")),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, `||` has a lower precedence than `&&`.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            position: 0,
+        })
+    }
+
+    fn parse(mut self) -> anyhow::Result<Expr> {
+        let expr = self.parse_or()?;
+        match self.next() {
+            None => Ok(expr),
+            Some(token) => Err(anyhow::anyhow!("unexpected {}", describe_token(&token))),
+        }
+    }
+
+    fn parse_or(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.next_if(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.parse_unary()?;
+        while self.next_if(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> anyhow::Result<Expr> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LeftParen) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(expr),
+                    token => Err(anyhow::anyhow!(
+                        "expected `)`, found {}",
+                        describe_next(token.as_ref())
+                    )),
+                }
+            }
+            Some(Token::Ident(name)) => {
+                let field = Field::parse(&name).ok_or_else(|| {
+                    anyhow::anyhow!("unknown field `{name}`, expected one of pass, name, kind, file, function, crate, hotness or message")
+                })?;
+                self.parse_comparison(field)
+            }
+            token => Err(anyhow::anyhow!(
+                "expected a field, `!` or `(`, found {}",
+                describe_next(token.as_ref())
+            )),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: Field) -> anyhow::Result<Expr> {
+        let operator = match self.next() {
+            Some(Token::Operator(operator)) => operator,
+            token => {
+                return Err(anyhow::anyhow!(
+                    "expected an operator, found {}",
+                    describe_next(token.as_ref())
+                ))
+            }
+        };
+        let value = self.next();
+        match (field, operator, value) {
+            (Field::Hotness, Operator::Match, _) => {
+                Err(anyhow::anyhow!("`~` cannot be used with hotness"))
+            }
+            (Field::Hotness, operator, Some(Token::Number(value))) => {
+                Ok(Expr::Hotness { operator, value })
+            }
+            (Field::Hotness, _, token) => Err(anyhow::anyhow!(
+                "expected a number, found {}",
+                describe_next(token.as_ref())
+            )),
+            (field, Operator::Match, Some(Token::String(pattern))) => {
+                let regex = match field {
+                    Field::File => glob_to_regex(&pattern),
+                    _ => pattern,
+                };
+                let regex = Regex::new(&regex)
+                    .map_err(|error| anyhow::anyhow!("invalid pattern: {error}"))?;
+                Ok(Expr::Pattern { field, regex })
+            }
+            (field, Operator::Eq | Operator::Ne, Some(Token::String(value))) => Ok(Expr::String {
+                field,
+                negate: operator == Operator::Ne,
+                value,
+            }),
+            (_, Operator::Eq | Operator::Ne | Operator::Match, token) => Err(anyhow::anyhow!(
+                "expected a string, found {}",
+                describe_next(token.as_ref())
+            )),
+            (_, _, _) => Err(anyhow::anyhow!(
+                "only `==`, `!=` and `~` can be used with strings"
+            )),
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn next_if(&mut self, expected: &Token) -> bool {
+        if self.tokens.get(self.position) == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn describe_next(token: Option<&Token>) -> String {
+    match token {
+        Some(token) => describe_token(token),
+        None => "end of input".to_string(),
+    }
+}
+
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => format!("`{name}`"),
+        Token::String(value) => format!("\"{value}\""),
+        Token::Number(value) => format!("`{value}`"),
+        Token::Operator(_) => "an operator".to_string(),
+        Token::Not => "`!`".to_string(),
+        Token::And => "`&&`".to_string(),
+        Token::Or => "`||`".to_string(),
+        Token::LeftParen => "`(`".to_string(),
+        Token::RightParen => "`)`".to_string(),
+    }
+}
+
+/// Converts a glob pattern to an anchored regular expression.
+/// `**` matches any path, `*` matches any part of a path segment and `?` matches a single character.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.next_if_eq(&'*').is_some() => {
+                if chars.next_if_eq(&'/').is_some() {
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::filter::{FilterExpr, RemarkFilter};
+    use crate::remark::{Remark, RemarkBuilder};
+
+    #[test]
+    fn match_fields() {
+        let remark = remark();
+        assert!(matches(r#"pass == "gvn""#, &remark));
+        assert!(matches(r#"kind == "LoadClobbered""#, &remark));
+        assert!(matches(r#"name != "NeverInline""#, &remark));
+        assert!(matches(r#"file ~ "src/hot/**""#, &remark));
+        assert!(matches(r#"file ~ "src/*/*.rs""#, &remark));
+        assert!(!matches(r#"file ~ "src/*.rs""#, &remark));
+        assert!(matches(r#"function ~ "^foo::""#, &remark));
+        assert!(matches(r#"message ~ "not eliminated""#, &remark));
+        assert!(matches(r#"crate == """#, &remark));
+        assert!(matches("hotness >= 1500", &remark));
+        assert!(!matches("hotness < 1500", &remark));
+    }
+
+    #[test]
+    fn combine_expressions() {
+        let remark = remark();
+        assert!(matches(
+            r#"pass == "gvn" && file ~ "src/hot/**" && hotness > 1000 && !function ~ "drop_in_place""#,
+            &remark
+        ));
+        assert!(matches(
+            r#"pass == "inline" || pass == "gvn" && hotness > 1000"#,
+            &remark
+        ));
+        assert!(!matches(
+            r#"(pass == "inline" || pass == "gvn") && hotness > 2000"#,
+            &remark
+        ));
+        assert!(matches(r#"!(name == "NeverInline")"#, &remark));
+    }
+
+    #[test]
+    fn missing_hotness() {
+        let mut remark = remark();
+        remark.hotness = None;
+        assert!(!matches("hotness > 0", &remark));
+        assert!(!matches("hotness <= 0", &remark));
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert_eq!(
+            error("foo == 1"),
+            "Invalid filter `foo == 1`: unknown field `foo`, expected one of pass, name, kind, file, function, crate, hotness or message"
+        );
+        assert_eq!(
+            error(r#"pass == "gvn" &&"#),
+            r#"Invalid filter `pass == "gvn" &&`: expected a field, `!` or `(`, found end of input"#
+        );
+        assert_eq!(
+            error(r#"hotness ~ "1""#),
+            r#"Invalid filter `hotness ~ "1"`: `~` cannot be used with hotness"#
+        );
+        assert_eq!(
+            error(r#"pass > "gvn""#),
+            r#"Invalid filter `pass > "gvn"`: only `==`, `!=` and `~` can be used with strings"#
+        );
+        assert_eq!(
+            error(r#"(pass == "gvn""#),
+            r#"Invalid filter `(pass == "gvn"`: expected `)`, found end of input"#
+        );
+        assert_eq!(
+            error(r#"pass == "gvn"#),
+            r#"Invalid filter `pass == "gvn`: unterminated string"#
+        );
+    }
+
+    #[test]
+    fn include_exclude() {
+        let filter = RemarkFilter {
+            include: vec![parse(r#"pass == "inline""#), parse(r#"pass == "gvn""#)],
+            exclude: vec![parse("hotness > 1000")],
+        };
+        assert!(!filter.matches(&remark()));
+        let mut remark = remark();
+        remark.hotness = Some(10);
+        assert!(filter.matches(&remark));
+        assert!(RemarkFilter::default().matches(&remark));
+    }
+
+    fn matches(expr: &str, remark: &Remark) -> bool {
+        parse(expr).matches(remark)
+    }
+
+    fn parse(expr: &str) -> FilterExpr {
+        expr.parse().unwrap()
+    }
+
+    fn error(expr: &str) -> String {
+        expr.parse::<FilterExpr>().unwrap_err().to_string()
+    }
+
+    fn remark() -> Remark {
+        RemarkBuilder::new("gvn", "LoadClobbered")
+            .function("foo::bar")
+            .location("src/hot/lib.rs", 1, 1)
+            .message("load not eliminated")
+            .hotness(Some(1500))
+            .build()
+    }
+}
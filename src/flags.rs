@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+/// Compiler flags that were used to compile a crate that has generated remarks.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct CrateFlags {
+    pub crate_name: String,
+    /// Crate types, e.g. `bin` or `lib`.
+    pub crate_types: Vec<String>,
+    /// Codegen (`-C`) and unstable (`-Z`) flags, together with the compilation target.
+    pub flags: Vec<String>,
+}
+
+impl CrateFlags {
+    /// Extracts relevant flags from command line arguments of rustc.
+    pub fn from_rustc_args<S: AsRef<str>>(crate_name: String, args: &[S]) -> Self {
+        // Flags that change between crates and builds, and that do not affect the generated code
+        const IGNORED_FLAGS: &[&str] = &["metadata", "extra-filename", "incremental"];
+
+        let mut crate_types = vec![];
+        let mut flags = vec![];
+        let mut args = args.iter().map(|arg| arg.as_ref());
+        while let Some(arg) = args.next() {
+            let flag = match arg {
+                "-C" | "-Z" => args.next().map(|value| format!("{arg}{value}")),
+                "--target" => args.next().map(|value| format!("--target={value}")),
+                "--crate-type" => {
+                    crate_types.extend(args.next().map(|value| value.to_string()));
+                    None
+                }
+                _ if arg.starts_with("-C") || arg.starts_with("-Z") => Some(arg.to_string()),
+                _ if arg.starts_with("--target=") => Some(arg.to_string()),
+                _ => None,
+            };
+            if let Some(flag) = flag {
+                let name = flag[2..].split('=').next().unwrap_or_default();
+                if !IGNORED_FLAGS.contains(&name) {
+                    flags.push(flag);
+                }
+            }
+        }
+        Self {
+            crate_name,
+            crate_types,
+            flags,
+        }
+    }
+
+    /// Name of the file into which the flags of this crate are stored.
+    pub fn file_name(&self) -> String {
+        format!("{}.{}.json", self.crate_name, self.crate_types.join("-"))
+    }
+}
+
+/// Loads flags of all crates stored in `dir`, sorted by crate name.
+pub fn load_crate_flags(dir: &Path) -> anyhow::Result<Vec<CrateFlags>> {
+    let mut crates = vec![];
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("Cannot read flag directory {}", dir.display()))?
+    {
+        let path = entry?.path();
+        let content = std::fs::read(&path)
+            .with_context(|| format!("Cannot read flag file {}", path.display()))?;
+        let flags: CrateFlags = serde_json::from_slice(&content)
+            .with_context(|| format!("Cannot parse flag file {}", path.display()))?;
+        crates.push(flags);
+    }
+    crates.sort_by(|a, b| (&a.crate_name, &a.crate_types).cmp(&(&b.crate_name, &b.crate_types)));
+    Ok(crates)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flags::CrateFlags;
+
+    #[test]
+    fn parse_rustc_args() {
+        let args = [
+            "--crate-name",
+            "foo",
+            "--edition=2021",
+            "src/main.rs",
+            "--crate-type",
+            "bin",
+            "-C",
+            "opt-level=3",
+            "-C",
+            "metadata=0123456789",
+            "-C",
+            "extra-filename=-0123456789",
+            "--target",
+            "x86_64-unknown-linux-gnu",
+            "-Ctarget-cpu=native",
+            "-Cremark=all",
+            "-Zremark-dir=/tmp/remarks",
+        ];
+        let flags = CrateFlags::from_rustc_args("foo".to_string(), &args);
+        assert_eq!(flags.crate_types, vec!["bin"]);
+        assert_eq!(
+            flags.flags,
+            vec![
+                "-Copt-level=3",
+                "--target=x86_64-unknown-linux-gnu",
+                "-Ctarget-cpu=native",
+                "-Cremark=all",
+                "-Zremark-dir=/tmp/remarks"
+            ]
+        );
+        assert_eq!(flags.file_name(), "foo.bin.json");
+    }
+}
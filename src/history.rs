@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::flags::CrateFlags;
+use crate::remark::Remark;
+
+/// Number of remarks and their total hotness.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RemarkCounts {
+    pub count: u64,
+    /// Sum of the hotness of all remarks (only available when PGO is used).
+    pub hotness: u64,
+}
+
+impl RemarkCounts {
+    pub(crate) fn add(&mut self, remark: &Remark) {
+        self.count += 1;
+        self.hotness += remark.hotness.unwrap_or(0).max(0) as u64;
+    }
+}
+
+/// Remark counts of a single run, aggregated in several ways.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RemarkSummary {
+    pub total: RemarkCounts,
+    pub passes: BTreeMap<String, RemarkCounts>,
+    /// Counts per remark kind (e.g. `LoadClobbered`).
+    pub kinds: BTreeMap<String, RemarkCounts>,
+    /// Counts per source file of the function that contains the remark.
+    pub files: BTreeMap<String, RemarkCounts>,
+}
+
+impl RemarkSummary {
+    pub fn from_remarks(remarks: &[Remark]) -> Self {
+        let mut summary = Self::default();
+        for remark in remarks {
+            summary.total.add(remark);
+            summary
+                .passes
+                .entry(remark.pass.clone())
+                .or_default()
+                .add(remark);
+            summary
+                .kinds
+                .entry(remark.name.clone())
+                .or_default()
+                .add(remark);
+            if let Some(ref location) = remark.function.location {
+                summary
+                    .files
+                    .entry(location.file.clone())
+                    .or_default()
+                    .add(remark);
+            }
+        }
+        summary
+    }
+}
+
+/// Record of a single `cargo remark` run.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    /// Name of the run directory.
+    pub run: String,
+    /// Date of the run, in RFC 3339 format.
+    pub date: String,
+    /// Git commit of the source directory, if it is a git repository.
+    pub git_commit: Option<String>,
+    pub profile: String,
+    pub target: String,
+    /// First line of `rustc -V` of the toolchain that generated the remarks.
+    #[serde(default)]
+    pub rustc_version: Option<String>,
+    pub flags: Vec<CrateFlags>,
+    pub summary: RemarkSummary,
+}
+
+/// Stores `entry` into the history directory `dir`.
+pub fn record_history(dir: &Path, entry: &HistoryEntry) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Cannot create history directory {}", dir.display()))?;
+    // Runs of different profiles and targets are stored in separate run directories
+    let path = dir.join(format!(
+        "{}.{}.{}.json",
+        entry.profile, entry.target, entry.run
+    ));
+    let file = std::fs::File::create(&path)
+        .with_context(|| format!("Cannot create history file {}", path.display()))?;
+    serde_json::to_writer_pretty(file, entry)?;
+    Ok(path)
+}
+
+/// Loads all history entries stored in `dir`, sorted by their date.
+/// Returns an empty history if the directory does not exist.
+pub fn load_history(dir: &Path) -> anyhow::Result<Vec<HistoryEntry>> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut entries = vec![];
+    for file in std::fs::read_dir(dir)
+        .with_context(|| format!("Cannot read history directory {}", dir.display()))?
+    {
+        let path = file?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let content = std::fs::read(&path)
+            .with_context(|| format!("Cannot read history file {}", path.display()))?;
+        match serde_json::from_slice::<HistoryEntry>(&content) {
+            Ok(entry) => entries.push(entry),
+            Err(error) => log::warn!("Cannot parse history file {}: {error:?}", path.display()),
+        }
+    }
+    entries.sort_by(|a, b| (&a.date, &a.run).cmp(&(&b.date, &b.run)));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{load_history, record_history, HistoryEntry, RemarkSummary};
+    use crate::remark::RemarkBuilder;
+
+    #[test]
+    fn summarize_remarks() {
+        let remarks = vec![
+            RemarkBuilder::new("inline", "NeverInline")
+                .location("src/main.rs", 1, 1)
+                .build(),
+            RemarkBuilder::new("gvn", "LoadClobbered")
+                .location("src/main.rs", 1, 1)
+                .hotness(Some(10))
+                .build(),
+            RemarkBuilder::new("gvn", "LoadClobbered")
+                .hotness(Some(5))
+                .build(),
+            RemarkBuilder::new("gvn", "LoadClobbered")
+                .without_location()
+                .build(),
+        ];
+        let summary = RemarkSummary::from_remarks(&remarks);
+        assert_eq!(summary.total.count, 4);
+        assert_eq!(summary.total.hotness, 15);
+        assert_eq!(summary.passes["gvn"].count, 3);
+        assert_eq!(summary.kinds["LoadClobbered"].hotness, 15);
+        assert_eq!(summary.files["src/main.rs"].count, 2);
+        assert_eq!(summary.files.len(), 2);
+    }
+
+    #[test]
+    fn store_history() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let history_dir = dir.path().join("history");
+        assert!(load_history(&history_dir).unwrap().is_empty());
+
+        for (run, date) in [
+            ("b", "2023-08-21T10:00:00+02:00"),
+            ("a", "2023-08-20T10:00:00+02:00"),
+        ] {
+            record_history(
+                &history_dir,
+                &HistoryEntry {
+                    run: run.to_string(),
+                    date: date.to_string(),
+                    git_commit: None,
+                    profile: "release".to_string(),
+                    target: "x86_64-unknown-linux-gnu".to_string(),
+                    rustc_version: None,
+                    flags: vec![],
+                    summary: Default::default(),
+                },
+            )
+            .unwrap();
+        }
+        let history = load_history(&history_dir).unwrap();
+        assert_eq!(
+            history.iter().map(|e| e.run.as_str()).collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+    }
+}
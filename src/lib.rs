@@ -1,7 +1,20 @@
 use std::path::PathBuf;
 
+pub mod archive;
+pub mod baseline;
+pub mod config;
+pub mod diff;
+pub mod filter;
+pub mod flags;
+pub mod history;
+pub mod matrix;
+pub mod query;
 pub mod remark;
 pub mod render;
+pub mod server;
+pub mod table;
+pub mod terminal;
+pub mod triage;
 pub mod utils;
 
 pub const DEFAULT_KIND_FILTER: &[&str] = &["FastISelFailure", "NeverInline", "SpillReloadCopies"];
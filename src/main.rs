@@ -1,14 +1,23 @@
 mod cargo;
+mod cmd;
 
-use cargo::version::check_remark_dir_support;
-use cargo::{get_rustc_source_root, run_cargo, CargoSubcommand};
-use cargo_remark::remark::{load_remarks_from_dir, RemarkLoadOptions};
-use cargo_remark::render::render_remarks;
-use cargo_remark::utils::callback::ProgressBarCallback;
-use cargo_remark::utils::cli::cli_format_path;
-use cargo_remark::utils::open_result;
-use cargo_remark::utils::timing::time_block_log_info;
+use cargo::toolchain::{extract_toolchain_arg, use_toolchain};
+use cargo::wrapper::{is_wrapper_invocation, run_wrapper};
+use cargo::CargoSubcommand;
 use clap::Parser;
+use cmd::build::SharedArgs;
+use cmd::check::CheckArgs;
+use cmd::config::ConfigArgs;
+use cmd::diff::DiffArgs;
+use cmd::list::ListArgs;
+use cmd::matrix::MatrixArgs;
+use cmd::pack::PackArgs;
+use cmd::pgo::PgoArgs;
+use cmd::serve::ServeArgs;
+use cmd::show::ShowArgs;
+use cmd::stats::StatsArgs;
+use cmd::triage::TriageArgs;
+use cmd::watch::WatchArgs;
 use env_logger::Env;
 
 #[cfg(feature = "mimalloc")]
@@ -20,9 +29,18 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 #[clap(bin_name("cargo"))]
 #[clap(disable_help_subcommand(true))]
 enum Args {
-    #[clap(subcommand)]
     #[clap(author, version, about)]
-    Remark(Subcommand),
+    Remark(RemarkArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct RemarkArgs {
+    /// Rust toolchain used to build the crate and to generate remarks (e.g. `nightly`).
+    /// It can also be selected with `cargo remark +<toolchain>`.
+    #[arg(long, global = true)]
+    toolchain: Option<String>,
+    #[clap(subcommand)]
+    command: Subcommand,
 }
 
 #[derive(clap::Subcommand, Debug)]
@@ -32,89 +50,68 @@ enum Subcommand {
     Build(SharedArgs),
     /// Wrap an arbitrary cargo command, while configuring it to generate remarks.
     Wrap(SharedArgs),
+    /// Pack previously generated remarks, together with the source files that they reference,
+    /// into an archive that can be rendered elsewhere using `analyze-remarks`.
+    Pack(PackArgs),
+    /// Compare two remark sets (e.g. from before and after a change) and render a website with
+    /// added, removed and changed remarks.
+    Diff(DiffArgs),
+    /// Compare the remarks of the latest run to a baseline, and fail if new remarks appear.
+    Check(CheckArgs),
+    /// Build the crate under several configurations (e.g. with different `-Ctarget-cpu` or LTO
+    /// settings), and render a website that compares their remarks.
+    Matrix(MatrixArgs),
+    /// Build the crate with PGO instrumentation, run a workload to gather profiles, and then
+    /// generate remarks with hotness from a build that uses the gathered profiles.
+    Pgo(PgoArgs),
+    /// Watch the sources of the workspace, and regenerate remarks and the website whenever they
+    /// change.
+    Watch(WatchArgs),
+    /// Serve the website of the latest run on localhost, together with a JSON API over its
+    /// remarks. Open pages are reloaded when the remarks are generated again.
+    Serve(ServeArgs),
+    /// List remarks of the latest run in a table.
+    List(ListArgs),
+    /// Print remark counts of the latest run per pass, kind, file and function.
+    Stats(StatsArgs),
+    /// Print remarks of the latest run located in a source file or at a single line.
+    Show(ShowArgs),
+    /// Mark remarks as reviewed, won't-fix or TODO, and list triaged remarks.
+    /// The triage state is stored in `remark-triage.toml` in the workspace root.
+    Triage(TriageArgs),
+    /// Print the effective configuration, loaded from `[workspace.metadata.remark]` and
+    /// `[package.metadata.remark]` in `Cargo.toml` and from `remark.toml`.
+    Config(ConfigArgs),
 }
 
-#[derive(clap::Parser, Debug)]
-#[clap(trailing_var_arg = true)]
-struct SharedArgs {
-    /// Open the generated website after the build finishes.
-    #[arg(long)]
-    open: bool,
-
-    /// Load remarks from external code (i.e. crate dependencies).
-    /// Note that this may produce a large amount of data!
-    #[arg(long)]
-    external: bool,
-
-    /// Optimization remark kinds that should be ignored.
-    #[arg(
-        long = "filter",
-        default_values = cargo_remark::DEFAULT_KIND_FILTER
-    )]
-    filter_kind: Vec<String>,
-
-    /// Additional arguments that will be passed to Cargo.
-    cargo_args: Vec<String>,
-}
-
-fn generate_remarks(subcmd: CargoSubcommand, args: SharedArgs) -> anyhow::Result<()> {
-    let SharedArgs {
-        open,
-        external,
-        filter_kind,
-        cargo_args,
-    } = args;
-    if !check_remark_dir_support()? {
-        return Err(anyhow::anyhow!(
-            "Your version of rustc does not support `-Zremark-dir`. Please use a nightly version newer than 4. 7. 2023."
-        ));
+fn main() -> anyhow::Result<()> {
+    if is_wrapper_invocation() {
+        std::process::exit(run_wrapper()?);
     }
-    let output = run_cargo(subcmd, cargo_args)?;
-
-    let rustc_source_root = match get_rustc_source_root() {
-        Ok(root) => Some(root),
-        Err(error) => {
-            log::warn!("Cannot find rustc source root: {error:?}");
-            None
-        }
-    };
-
-    let remarks = time_block_log_info("Remark loading", || {
-        load_remarks_from_dir(
-            output.yaml_dir,
-            RemarkLoadOptions {
-                external,
-                source_dir: output.source_dir.clone(),
-                filter_kind,
-                rustc_source_root,
-            },
-            Some(&ProgressBarCallback::default()),
-        )
-    })?;
-    time_block_log_info("Rendering", || {
-        render_remarks(
-            remarks,
-            &output.source_dir,
-            &output.web_dir,
-            Some(&ProgressBarCallback::default()),
-        )
-    })?;
-
-    log::info!("Website built into {}.", cli_format_path(&output.web_dir));
 
-    open_result(&output.web_dir, open)?;
-    Ok(())
-}
-
-fn main() -> anyhow::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("cargo_remark=info")).init();
 
-    let args = Args::parse();
+    let mut args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    let toolchain_arg = extract_toolchain_arg(&mut args);
+    let Args::Remark(args) = Args::parse_from(args);
+    if let Some(toolchain) = args.toolchain.or(toolchain_arg) {
+        use_toolchain(&toolchain);
+    }
 
-    match args {
-        Args::Remark(args) => match args {
-            Subcommand::Build(args) => generate_remarks(CargoSubcommand::Build, args),
-            Subcommand::Wrap(args) => generate_remarks(CargoSubcommand::Wrap, args),
-        },
+    match args.command {
+        Subcommand::Build(args) => cmd::build::run(CargoSubcommand::Build, args),
+        Subcommand::Wrap(args) => cmd::build::run(CargoSubcommand::Wrap, args),
+        Subcommand::Pack(args) => cmd::pack::run(args),
+        Subcommand::Diff(args) => cmd::diff::run(args),
+        Subcommand::Check(args) => cmd::check::run(args),
+        Subcommand::Matrix(args) => cmd::matrix::run(args),
+        Subcommand::Pgo(args) => cmd::pgo::run(args),
+        Subcommand::Watch(args) => cmd::watch::run(args),
+        Subcommand::Serve(args) => cmd::serve::run(args),
+        Subcommand::List(args) => cmd::list::run(args),
+        Subcommand::Stats(args) => cmd::stats::run(args),
+        Subcommand::Show(args) => cmd::show::run(args),
+        Subcommand::Triage(args) => cmd::triage::run(args),
+        Subcommand::Config(args) => cmd::config::run(args),
     }
 }
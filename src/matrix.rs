@@ -0,0 +1,135 @@
+use crate::remark::{Column, Fingerprint, Line, Remark};
+use crate::utils::data_structures::Map;
+
+/// Identifies a remark at its exact location.
+type MatrixKey = (Fingerprint, Option<(String, Line, Column)>);
+
+/// Remark found in one or more configurations of a matrix.
+pub struct MatrixEntry {
+    /// The remark from the first configuration in which it is present.
+    pub remark: Remark,
+    /// Indices of configurations in which the remark is present, in ascending order.
+    pub configurations: Vec<usize>,
+}
+
+/// Remarks of the same crate built under several configurations.
+pub struct RemarkMatrix {
+    /// Names of the configurations.
+    pub configurations: Vec<String>,
+    /// Number of remarks of each configuration.
+    pub counts: Vec<usize>,
+    pub entries: Vec<MatrixEntry>,
+}
+
+impl RemarkMatrix {
+    /// Returns true if the remark of `entry` is only present in some configurations.
+    pub fn is_partial(&self, entry: &MatrixEntry) -> bool {
+        entry.configurations.len() < self.configurations.len()
+    }
+
+    /// Returns the number of remarks that are only present in some configurations.
+    pub fn partial_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| self.is_partial(entry))
+            .count()
+    }
+}
+
+/// Compares remark sets of several configurations, given as `(name, remarks)` pairs.
+///
+/// The sources are the same for all configurations, so remarks are matched by their
+/// [`Fingerprint`] and exact location. Remarks with the same key are matched in the order in
+/// which they were loaded.
+pub fn compare_remarks(sets: Vec<(String, Vec<Remark>)>) -> RemarkMatrix {
+    let mut configurations = Vec::with_capacity(sets.len());
+    let mut counts = Vec::with_capacity(sets.len());
+    let mut entries: Vec<MatrixEntry> = vec![];
+    // Indices of entries with the same key, in the order of their occurrence
+    let mut groups: Map<MatrixKey, Vec<usize>> = Map::default();
+
+    for (index, (name, remarks)) in sets.into_iter().enumerate() {
+        configurations.push(name);
+        counts.push(remarks.len());
+
+        let mut occurrences: Map<MatrixKey, usize> = Map::default();
+        for remark in remarks {
+            let key = (
+                remark.fingerprint,
+                remark
+                    .function
+                    .location
+                    .as_ref()
+                    .map(|location| (location.file.clone(), location.line, location.column)),
+            );
+            let occurrence = occurrences.entry(key.clone()).or_default();
+            let group = groups.entry(key).or_default();
+            match group.get(*occurrence) {
+                Some(&entry) => entries[entry].configurations.push(index),
+                None => {
+                    group.push(entries.len());
+                    entries.push(MatrixEntry {
+                        remark,
+                        configurations: vec![index],
+                    });
+                }
+            }
+            *occurrence += 1;
+        }
+    }
+
+    RemarkMatrix {
+        configurations,
+        counts,
+        entries,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::compare_remarks;
+    use crate::remark::{Remark, RemarkBuilder};
+
+    #[test]
+    fn compare_configurations() {
+        let matrix = compare_remarks(vec![
+            (
+                "default".to_string(),
+                vec![
+                    remark("LoadClobbered", 10),
+                    remark("LoadClobbered", 10),
+                    remark("NoDefinition", 20),
+                ],
+            ),
+            (
+                "native".to_string(),
+                vec![remark("LoadClobbered", 10), remark("LoadClobbered", 30)],
+            ),
+        ]);
+        assert_eq!(matrix.configurations, vec!["default", "native"]);
+        assert_eq!(matrix.counts, vec![3, 2]);
+        assert_eq!(
+            matrix
+                .entries
+                .iter()
+                .map(|entry| (
+                    entry.remark.function.location.as_ref().unwrap().line,
+                    entry.configurations.clone()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (10, vec![0, 1]),
+                (10, vec![0]),
+                (20, vec![0]),
+                (30, vec![1])
+            ]
+        );
+        assert_eq!(matrix.partial_count(), 3);
+    }
+
+    fn remark(name: &str, line: u32) -> Remark {
+        RemarkBuilder::new("gvn", name)
+            .location("src/lib.rs", line, 5)
+            .build()
+    }
+}
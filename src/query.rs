@@ -0,0 +1,240 @@
+//! Selection, sorting and aggregation of loaded remarks, shared by the terminal commands
+//! (`cargo remark list`, `stats` and `show`) and by the JSON API of the server.
+use std::collections::BTreeMap;
+
+use crate::filter::FilterExpr;
+use crate::history::{RemarkCounts, RemarkSummary};
+use crate::remark::{Column, Line, MessagePart, Remark};
+
+/// Order of listed remarks.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemarkSort {
+    /// Order in which the remarks were loaded.
+    #[default]
+    #[value(skip)]
+    None,
+    /// Hottest remarks first.
+    Hotness,
+    /// By file, line and column.
+    Location,
+    /// By pass and remark kind.
+    Kind,
+    /// By function name.
+    Function,
+}
+
+/// Selects a page of remarks.
+#[derive(Debug, Clone, Default)]
+pub struct RemarkQuery {
+    /// Remarks have to match all these expressions.
+    pub filters: Vec<FilterExpr>,
+    /// Only select remarks of functions located in this file.
+    pub file: Option<String>,
+    /// Only select remarks located at this line of `file`.
+    pub line: Option<Line>,
+    pub sort: RemarkSort,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// Remarks selected by a [`RemarkQuery`].
+#[derive(serde::Serialize, Debug)]
+pub struct RemarkPage<'a> {
+    /// Number of matching remarks, before `offset` and `limit` were applied.
+    pub total: usize,
+    pub remarks: Vec<&'a Remark>,
+}
+
+impl RemarkQuery {
+    pub fn apply<'a>(&self, remarks: &'a [Remark]) -> RemarkPage<'a> {
+        let mut matched: Vec<&Remark> = remarks
+            .iter()
+            .filter(|remark| self.matches(remark))
+            .collect();
+        match self.sort {
+            RemarkSort::None => {}
+            RemarkSort::Hotness => {
+                matched.sort_by_key(|remark| std::cmp::Reverse(remark.hotness));
+            }
+            RemarkSort::Location => matched.sort_by_key(|remark| location_key(remark)),
+            RemarkSort::Kind => matched.sort_by(|a, b| {
+                (&a.pass, &a.name, location_key(a)).cmp(&(&b.pass, &b.name, location_key(b)))
+            }),
+            RemarkSort::Function => matched.sort_by(|a, b| {
+                (&a.function.name, location_key(a)).cmp(&(&b.function.name, location_key(b)))
+            }),
+        }
+        let total = matched.len();
+        let remarks = matched
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        RemarkPage { total, remarks }
+    }
+
+    fn matches(&self, remark: &Remark) -> bool {
+        if let Some(ref file) = self.file {
+            match remark.function.location {
+                Some(ref location) if &location.file == file => {
+                    if self.line.is_some_and(|line| line != location.line) {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+        self.filters.iter().all(|filter| filter.matches(remark))
+    }
+}
+
+fn location_key(remark: &Remark) -> Option<(&str, Line, Column)> {
+    remark
+        .function
+        .location
+        .as_ref()
+        .map(|location| (location.file.as_str(), location.line, location.column))
+}
+
+/// Parses a `<file>[:<line>]` location of `cargo remark show`.
+pub fn parse_file_location(value: &str) -> (String, Option<Line>) {
+    match value.rsplit_once(':') {
+        Some((file, line)) if !file.is_empty() => match line.parse() {
+            Ok(line) => (file.to_string(), Some(line)),
+            Err(_) => (value.to_string(), None),
+        },
+        _ => (value.to_string(), None),
+    }
+}
+
+/// Remark counts aggregated in several ways, including per function.
+#[derive(serde::Serialize, Debug, Default)]
+pub struct RemarkStats {
+    #[serde(flatten)]
+    pub summary: RemarkSummary,
+    /// Counts per function that contains the remark.
+    pub functions: BTreeMap<String, RemarkCounts>,
+}
+
+impl RemarkStats {
+    pub fn from_remarks(remarks: &[Remark]) -> Self {
+        let mut functions: BTreeMap<String, RemarkCounts> = BTreeMap::new();
+        for remark in remarks {
+            functions
+                .entry(remark.function.name.clone())
+                .or_default()
+                .add(remark);
+        }
+        Self {
+            summary: RemarkSummary::from_remarks(remarks),
+            functions,
+        }
+    }
+}
+
+/// Returns the counts sorted by the number of remarks (and then by hotness), highest first.
+pub fn top_counts(counts: &BTreeMap<String, RemarkCounts>) -> Vec<(&str, &RemarkCounts)> {
+    let mut sorted: Vec<(&str, &RemarkCounts)> = counts
+        .iter()
+        .map(|(name, counts)| (name.as_str(), counts))
+        .collect();
+    sorted.sort_by_key(|(_, counts)| std::cmp::Reverse((counts.count, counts.hotness)));
+    sorted
+}
+
+/// Joins the message of a remark into a single string.
+pub fn message_text(remark: &Remark) -> String {
+    remark
+        .message
+        .iter()
+        .map(|part| match part {
+            MessagePart::String(string) => string.as_str(),
+            MessagePart::AnnotatedString { message, .. } => message.as_str(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::{parse_file_location, top_counts, RemarkQuery, RemarkSort, RemarkStats};
+    use crate::remark::{Remark, RemarkBuilder};
+
+    #[test]
+    fn query_remarks() {
+        let remarks = vec![
+            remark("gvn", "foo", "src/main.rs", 7, Some(10)),
+            remark("inline", "bar", "src/lib.rs", 3, Some(50)),
+            remark("gvn", "baz", "src/main.rs", 2, None),
+            remark("licm", "foo", "src/main.rs", 7, Some(1)),
+        ];
+
+        let page = RemarkQuery {
+            sort: RemarkSort::Hotness,
+            limit: Some(2),
+            ..Default::default()
+        }
+        .apply(&remarks);
+        assert_eq!(page.total, 4);
+        assert_eq!(pass_names(&page.remarks), vec!["inline", "gvn"]);
+
+        let page = RemarkQuery {
+            filters: vec!["pass == \"gvn\"".parse().unwrap()],
+            sort: RemarkSort::Location,
+            ..Default::default()
+        }
+        .apply(&remarks);
+        assert_eq!(page.total, 2);
+        assert_eq!(page.remarks[0].function.name, "baz");
+
+        let page = RemarkQuery {
+            file: Some("src/main.rs".to_string()),
+            line: Some(7),
+            sort: RemarkSort::Kind,
+            ..Default::default()
+        }
+        .apply(&remarks);
+        assert_eq!(pass_names(&page.remarks), vec!["gvn", "licm"]);
+    }
+
+    #[test]
+    fn parse_location() {
+        assert_eq!(
+            parse_file_location("src/main.rs:7"),
+            ("src/main.rs".to_string(), Some(7))
+        );
+        assert_eq!(
+            parse_file_location("src/main.rs"),
+            ("src/main.rs".to_string(), None)
+        );
+        assert_eq!(parse_file_location("c:x"), ("c:x".to_string(), None));
+    }
+
+    #[test]
+    fn function_stats() {
+        let remarks = vec![
+            remark("gvn", "foo", "src/main.rs", 7, Some(10)),
+            remark("inline", "bar", "src/lib.rs", 3, None),
+            remark("licm", "foo", "src/main.rs", 8, Some(1)),
+        ];
+        let stats = RemarkStats::from_remarks(&remarks);
+        assert_eq!(stats.summary.total.count, 3);
+        assert_eq!(stats.functions["foo"].hotness, 11);
+        let top: Vec<&str> = top_counts(&stats.functions)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(top, vec!["foo", "bar"]);
+    }
+
+    fn pass_names<'a>(remarks: &[&'a Remark]) -> Vec<&'a str> {
+        remarks.iter().map(|remark| remark.pass.as_str()).collect()
+    }
+
+    fn remark(pass: &str, function: &str, file: &str, line: u32, hotness: Option<i32>) -> Remark {
+        RemarkBuilder::new(pass, "Missed")
+            .function(function)
+            .location(file, line, 1)
+            .hotness(hotness)
+            .build()
+    }
+}
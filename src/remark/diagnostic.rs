@@ -0,0 +1,381 @@
+//! Loading of remarks printed as rustc diagnostics.
+//!
+//! Toolchains that do not support `-Zremark-dir` can still print remarks as diagnostics when they
+//! are compiled with `-Cremark`. Cargo reports them as JSON messages (`--message-format=json`),
+//! which are converted to [`DiagnosticRemark`]s and stored in a diagnostics file, one JSON object
+//! per line.
+//!
+//! Diagnostics have a lower fidelity than remark files. They only contain the pass, the location
+//! and the text of the remark, so the remark kind and the function are inferred from the text,
+//! and hotness and the locations of callees are not available.
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use regex::Regex;
+
+use crate::remark::fingerprint::FileFingerprints;
+use crate::remark::parse::DebugLocation;
+use crate::remark::{
+    demangle, finish_remark, is_ignored, parse_debug_loc, Column, Fingerprint, Function, Line,
+    MessagePart, Remark, RemarkLoadOptions, RemarkOrigin, RemarkStage,
+};
+use crate::utils::data_structures::Map;
+use crate::utils::io::normalize_separators;
+use crate::utils::source::SourceCache;
+
+/// Name of the file that stores remarks collected from diagnostics.
+pub const DIAGNOSTICS_FILE_NAME: &str = "remarks.diagnostics.json";
+
+/// Diagnostics files are recognized by this extension.
+pub(crate) const DIAGNOSTICS_EXTENSION: &str = ".diagnostics.json";
+
+/// Remark kinds inferred from the pass and the text of the most common remarks.
+/// Other remarks use the name of their pass as their kind.
+const KNOWN_KINDS: &[(&str, &str, &str)] = &[
+    ("inline", "should never be inlined", "NeverInline"),
+    ("inline", "definition is unavailable", "NoDefinition"),
+    ("inline", "too costly to inline", "TooCostly"),
+    ("gvn", "not eliminated", "LoadClobbered"),
+    (
+        "licm",
+        "failed to move load with loop-invariant address",
+        "LoadWithLoopInvariantAddressInvalidated",
+    ),
+    (
+        "licm",
+        "failed to hoist load with loop-invariant address",
+        "LoadWithLoopInvariantAddressCondExecuted",
+    ),
+    ("regalloc", "generated in function", "SpillReloadCopies"),
+    ("regalloc", "generated in loop", "LoopSpillReloadCopies"),
+    ("sdagisel", "FastISel missed", "FastISelFailure"),
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticStatus {
+    /// The optimization was applied (`success`).
+    Passed,
+    Missed,
+    Analysis,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct DiagnosticLocation {
+    pub file: String,
+    pub line: Line,
+    pub column: Column,
+}
+
+/// Remark parsed from a rustc diagnostic.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+pub struct DiagnosticRemark {
+    /// Crate whose compilation has emitted the remark.
+    pub crate_name: String,
+    pub pass: String,
+    pub status: DiagnosticStatus,
+    pub location: Option<DiagnosticLocation>,
+    /// Text of the remark, with mangled symbol names.
+    pub message: String,
+}
+
+static HEADER_REGEX: OnceLock<Regex> = OnceLock::new();
+static FUNCTION_REGEX: OnceLock<Regex> = OnceLock::new();
+static SYMBOL_REGEX: OnceLock<Regex> = OnceLock::new();
+
+impl DiagnosticRemark {
+    /// Parses a remark from a JSON message printed by Cargo with `--message-format=json`.
+    /// Returns `None` if the message is not a remark.
+    ///
+    /// Absolute paths located inside `source_dir` are made relative to it, like the paths of
+    /// workspace crates in remark files.
+    pub fn from_cargo_message(message: &serde_json::Value, source_dir: &Path) -> Option<Self> {
+        if message.get("reason")?.as_str()? != "compiler-message" {
+            return None;
+        }
+        let crate_name = message
+            .get("target")?
+            .get("name")?
+            .as_str()?
+            .replace('-', "_");
+        let diagnostic = message.get("message")?;
+        if diagnostic.get("level")?.as_str()? != "note" {
+            return None;
+        }
+
+        let regex = HEADER_REGEX.get_or_init(|| {
+            Regex::new(r"(?s)^(?:(.+):(\d+):(\d+) )?([\w.-]+) \((success|missed|analysis)\): (.*)$")
+                .expect("Could not create regular expression")
+        });
+        let captures = regex.captures(diagnostic.get("message")?.as_str()?)?;
+        let status = match &captures[5] {
+            "success" => DiagnosticStatus::Passed,
+            "missed" => DiagnosticStatus::Missed,
+            _ => DiagnosticStatus::Analysis,
+        };
+        let location = primary_span_location(diagnostic).or_else(|| {
+            Some(DiagnosticLocation {
+                file: captures.get(1)?.as_str().to_string(),
+                line: captures[2].parse().ok()?,
+                column: captures[3].parse().ok()?,
+            })
+        });
+        // Remarks without debug info have an `<unknown file>:0:0` location
+        let location = location
+            .filter(|location| location.line > 0)
+            .map(|location| DiagnosticLocation {
+                file: make_relative(&location.file, source_dir),
+                ..location
+            });
+
+        Some(Self {
+            crate_name,
+            pass: captures[4].to_string(),
+            status,
+            location,
+            message: captures[6].trim_end().to_string(),
+        })
+    }
+}
+
+fn primary_span_location(diagnostic: &serde_json::Value) -> Option<DiagnosticLocation> {
+    let span = diagnostic
+        .get("spans")?
+        .as_array()?
+        .iter()
+        .find(|span| span.get("is_primary").and_then(|p| p.as_bool()) == Some(true))?;
+    Some(DiagnosticLocation {
+        file: span.get("file_name")?.as_str()?.to_string(),
+        line: span.get("line_start")?.as_u64()? as Line,
+        column: span.get("column_start")?.as_u64()? as Column,
+    })
+}
+
+fn make_relative(file: &str, source_dir: &Path) -> String {
+    match Path::new(file).strip_prefix(source_dir) {
+        Ok(relative) => normalize_separators(relative),
+        Err(_) => file.to_string(),
+    }
+}
+
+/// Loads missed remarks from a diagnostics file. `file` is the name of the diagnostics file.
+pub(crate) fn parse_diagnostics<R: BufRead>(
+    reader: R,
+    options: &RemarkLoadOptions,
+    file: &str,
+    fingerprints: &mut FileFingerprints,
+) -> Vec<Remark> {
+    let mut remarks = vec![];
+    let mut sources = SourceCache::new(&options.source_dir);
+    let mut origins: Map<String, Arc<RemarkOrigin>> = Map::default();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                log::debug!("Error while reading diagnostics: {error:?}");
+                break;
+            }
+        };
+        let diagnostic: DiagnosticRemark = match serde_json::from_str(&line) {
+            Ok(diagnostic) => diagnostic,
+            Err(error) => {
+                log::debug!("Error while deserializing diagnostic: {error:?}");
+                continue;
+            }
+        };
+        let DiagnosticRemark {
+            crate_name,
+            pass,
+            status: DiagnosticStatus::Missed,
+            location: Some(location),
+            message,
+        } = diagnostic
+        else {
+            continue;
+        };
+
+        let name = infer_kind(&pass, &message);
+        if is_ignored(options, &location.file, name) {
+            continue;
+        }
+
+        let origin = origins
+            .entry(crate_name)
+            .or_insert_with_key(|crate_name| {
+                Arc::new(RemarkOrigin {
+                    file: file.to_string(),
+                    crate_name: Some(crate_name.clone()),
+                    crate_hash: None,
+                    cgu: None,
+                    stage: Some(RemarkStage::Diagnostic),
+                    lto: false,
+                })
+            })
+            .clone();
+        let remark = Remark {
+            name: name.to_string(),
+            function: Function {
+                name: infer_function(&message),
+                location: Some(parse_debug_loc(
+                    options,
+                    DebugLocation {
+                        file: location.file.into(),
+                        line: location.line,
+                        column: location.column,
+                    },
+                )),
+            },
+            message: vec![MessagePart::String(demangle_symbols(&message))],
+            pass,
+            hotness: None,
+            origin: Some(origin),
+            fingerprint: Fingerprint::default(),
+            suppressed: false,
+        };
+        if let Some(remark) = finish_remark(remark, index, options, &mut sources, fingerprints) {
+            remarks.push(remark);
+        }
+    }
+    remarks
+}
+
+fn infer_kind<'a>(pass: &'a str, message: &str) -> &'a str {
+    KNOWN_KINDS
+        .iter()
+        .find(|(kind_pass, fragment, _)| *kind_pass == pass && message.contains(fragment))
+        .map(|(_, _, kind)| *kind)
+        .unwrap_or(pass)
+}
+
+/// Finds the function that contains the remark, based on the caller of inlining remarks or on
+/// the `(in function: ...)` suffix of codegen remarks.
+fn infer_function(message: &str) -> String {
+    let regex = FUNCTION_REGEX.get_or_init(|| {
+        Regex::new(r"(?:inlined into|in function:) '?([^'\s)]+)")
+            .expect("Could not create regular expression")
+    });
+    regex
+        .captures(message)
+        .map(|captures| demangle(&captures[1]))
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+fn demangle_symbols(message: &str) -> String {
+    let regex = SYMBOL_REGEX.get_or_init(|| {
+        Regex::new(r"_R[0-9A-Za-z_]+|_ZN[0-9A-Za-z_$.]+E")
+            .expect("Could not create regular expression")
+    });
+    regex
+        .replace_all(message, |captures: &regex::Captures| demangle(&captures[0]))
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::Path;
+
+    use crate::remark::diagnostic::{
+        parse_diagnostics, DiagnosticLocation, DiagnosticRemark, DiagnosticStatus,
+    };
+    use crate::remark::{MessagePart, RemarkLoadOptions, RemarkStage};
+
+    #[test]
+    fn parse_cargo_message() {
+        let message = cargo_message("/project/src/main.rs:2:5 inline (missed): '_RNvCs1234_8diagtest3foo' not inlined into '_RNvCs1234_8diagtest4main' because it should never be inlined (cost=never): noinline function attribute");
+        let remark = DiagnosticRemark::from_cargo_message(&message, Path::new("/project")).unwrap();
+        assert_eq!(
+            remark,
+            DiagnosticRemark {
+                crate_name: "my_crate".to_string(),
+                pass: "inline".to_string(),
+                status: DiagnosticStatus::Missed,
+                location: Some(DiagnosticLocation {
+                    file: "src/main.rs".to_string(),
+                    line: 2,
+                    column: 5,
+                }),
+                message: "'_RNvCs1234_8diagtest3foo' not inlined into '_RNvCs1234_8diagtest4main' because it should never be inlined (cost=never): noinline function attribute".to_string(),
+            }
+        );
+
+        let remark = DiagnosticRemark::from_cargo_message(
+            &cargo_message("<unknown file>:0:0 regalloc (missed): 1 virtual registers copies generated in function"),
+            Path::new("/project"),
+        )
+        .unwrap();
+        assert_eq!(remark.location, None);
+
+        assert!(DiagnosticRemark::from_cargo_message(
+            &cargo_message("unused variable: `a`"),
+            Path::new("/project")
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn load_diagnostics() {
+        let remarks = [
+            ("inline", DiagnosticStatus::Missed, "'_RNvCs1234_8diagtest3foo' not inlined into '_RNvCs1234_8diagtest4main' because it should never be inlined (cost=never): noinline function attribute"),
+            ("gvn", DiagnosticStatus::Missed, "load of type i32 not eliminated"),
+            ("inline", DiagnosticStatus::Passed, "'_RNvCs1234_8diagtest3bar' inlined into '_RNvCs1234_8diagtest4main'"),
+        ];
+        let content: String = remarks
+            .into_iter()
+            .map(|(pass, status, message)| {
+                let remark = DiagnosticRemark {
+                    crate_name: "diagtest".to_string(),
+                    pass: pass.to_string(),
+                    status,
+                    location: Some(DiagnosticLocation {
+                        file: "src/main.rs".to_string(),
+                        line: 2,
+                        column: 5,
+                    }),
+                    message: message.to_string(),
+                };
+                format!("{}\n", serde_json::to_string(&remark).unwrap())
+            })
+            .collect();
+
+        let options = RemarkLoadOptions {
+            external: true,
+            ..Default::default()
+        };
+        let remarks = parse_diagnostics(
+            Cursor::new(content),
+            &options,
+            "remarks.diagnostics.json",
+            &mut vec![],
+        );
+        assert_eq!(remarks.len(), 2);
+        assert_eq!(remarks[0].name, "NeverInline");
+        assert_eq!(remarks[0].function.name, "diagtest::main");
+        let MessagePart::String(ref message) = remarks[0].message[0] else {
+            panic!("Unexpected message {:?}", remarks[0].message);
+        };
+        assert_eq!(
+            message,
+            "'diagtest::foo' not inlined into 'diagtest::main' because it should never be inlined (cost=never): noinline function attribute"
+        );
+        assert_eq!(remarks[1].name, "LoadClobbered");
+        assert_eq!(remarks[1].function.name, "<unknown>");
+        let origin = remarks[0].origin.as_ref().unwrap();
+        assert_eq!(origin.crate_name.as_deref(), Some("diagtest"));
+        assert_eq!(origin.stage, Some(RemarkStage::Diagnostic));
+    }
+
+    fn cargo_message(message: &str) -> serde_json::Value {
+        serde_json::json!({
+            "reason": "compiler-message",
+            "target": {"name": "my-crate"},
+            "message": {
+                "level": "note",
+                "message": message,
+                "spans": [],
+            }
+        })
+    }
+}
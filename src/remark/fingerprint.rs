@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::remark::{Line, MessagePart};
+use crate::utils::source::SourceCache;
+
+/// Name of the file inside a remark directory that contains the fingerprints of its remarks,
+/// computed when the remarks were generated.
+pub const FINGERPRINTS_FILE_NAME: &str = "fingerprints.json";
+
+/// Fingerprints of the remarks of a single remark file, indexed by the position of the remark
+/// (a YAML document or a diagnostic line) in the file.
+pub(crate) type FileFingerprints = Vec<Option<Fingerprint>>;
+
+/// Fingerprints of the remarks of a remark directory, keyed by the name of the remark file.
+pub(crate) type StoredFingerprints = BTreeMap<String, FileFingerprints>;
+
+/// Number of source lines before and after the remark that are included in its fingerprint.
+const CONTEXT_LINES: usize = 2;
+
+/// Identity of a remark that does not change when the remark moves to a different line.
+///
+/// It is computed from the remark kind, pass, function name (without hashes and generic
+/// arguments), normalized message and from the source lines surrounding the remark.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    pub(crate) fn compute(
+        pass: &str,
+        name: &str,
+        function: &str,
+        message: &[MessagePart],
+        context: &str,
+    ) -> Self {
+        let mut hasher = FnvHasher::default();
+        hasher.write(pass);
+        hasher.write(name);
+        hasher.write(&strip_generics(function));
+        hasher.write(&normalize_message(message));
+        hasher.write(context);
+        Self(hasher.finish())
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl Debug for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Fingerprint({self})")
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(value, 16)
+            .map(Self)
+            .map_err(|_| anyhow::anyhow!("Invalid fingerprint `{value}`"))
+    }
+}
+
+impl serde::Serialize for Fingerprint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Fingerprint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// 64-bit FNV-1a hash, which (unlike the std hashers) is stable across Rust versions.
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl FnvHasher {
+    fn write(&mut self, value: &str) {
+        // The terminating zero separates consecutive fields
+        for byte in value.bytes().chain(std::iter::once(0)) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Removes generic arguments from a demangled function name.
+/// Angle brackets that start a path segment (e.g. `<T as Trait>::method`) are kept.
+fn strip_generics(function: &str) -> String {
+    let mut output = String::with_capacity(function.len());
+    let mut depth = 0;
+    for c in function.chars() {
+        match c {
+            '<' if depth > 0 => depth += 1,
+            '<' if output.ends_with("::") => {
+                output.truncate(output.len() - 2);
+                depth = 1;
+            }
+            '<' if output.ends_with(|c: char| c.is_alphanumeric() || c == '_') => depth = 1,
+            '>' if depth > 0 => depth -= 1,
+            _ if depth > 0 => {}
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// Joins the message text without source locations, and replaces numbers (e.g. inlining costs)
+/// and whitespace sequences, which can change with unrelated edits.
+fn normalize_message(message: &[MessagePart]) -> String {
+    let mut output = String::new();
+    let text = message.iter().map(|part| match part {
+        MessagePart::String(string) => string.as_str(),
+        MessagePart::AnnotatedString { message, .. } => message.as_str(),
+    });
+    for c in text.flat_map(|part| part.chars()) {
+        let c = match c {
+            '0'..='9' => '#',
+            c if c.is_whitespace() => ' ',
+            c => c,
+        };
+        if (c == '#' || c == ' ') && output.ends_with(c) {
+            continue;
+        }
+        output.push(c);
+    }
+    output.trim().to_string()
+}
+
+/// Loads fingerprints stored in the remark directory `dir`. If there are none, fingerprints are
+/// computed from the current sources.
+pub(crate) fn load_stored_fingerprints(dir: &Path) -> StoredFingerprints {
+    let path = dir.join(FINGERPRINTS_FILE_NAME);
+    if !path.is_file() {
+        return Default::default();
+    }
+    match std::fs::read(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(serde_json::from_slice(&content)?))
+    {
+        Ok(fingerprints) => fingerprints,
+        Err(error) => {
+            log::warn!(
+                "Cannot load fingerprints from {}, they will be computed from the current sources: {error:?}",
+                path.display()
+            );
+            Default::default()
+        }
+    }
+}
+
+/// Returns the trimmed source lines surrounding `line` in `file`, or an empty string if the file
+/// cannot be read.
+pub(crate) fn source_context(sources: &mut SourceCache, file: &str, line: Line) -> String {
+    let Some(lines) = sources.lines(file) else {
+        return String::new();
+    };
+
+    let line = (line as usize).saturating_sub(1);
+    let start = line.saturating_sub(CONTEXT_LINES);
+    let end = (line + CONTEXT_LINES + 1).min(lines.len());
+    lines
+        .get(start..end)
+        .map(|lines| {
+            lines
+                .iter()
+                .map(|line| line.trim())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remark::fingerprint::{
+        normalize_message, source_context, strip_generics, Fingerprint,
+    };
+    use crate::remark::{Location, MessagePart};
+    use crate::utils::source::SourceCache;
+
+    #[test]
+    fn strip_generic_arguments() {
+        assert_eq!(
+            strip_generics("core::ptr::drop_in_place::<alloc::vec::Vec<u8>>"),
+            "core::ptr::drop_in_place"
+        );
+        assert_eq!(
+            strip_generics("alloc::vec::Vec<T,A>::push"),
+            "alloc::vec::Vec::push"
+        );
+        assert_eq!(
+            strip_generics("<foo::Bar<u32> as core::fmt::Debug>::fmt"),
+            "<foo::Bar as core::fmt::Debug>::fmt"
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        let message = vec![
+            MessagePart::String(
+                "'foo' not inlined into 'main' because too costly (cost=45,  threshold=225)"
+                    .to_string(),
+            ),
+            MessagePart::AnnotatedString {
+                message: " at callsite".to_string(),
+                location: Location {
+                    file: "src/main.rs".to_string(),
+                    line: 10,
+                    column: 5,
+                },
+            },
+        ];
+        assert_eq!(
+            normalize_message(&message),
+            "'foo' not inlined into 'main' because too costly (cost=#, threshold=#) at callsite"
+        );
+    }
+
+    #[test]
+    fn fingerprint_ignores_line_shift() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("old.rs"),
+            "fn foo() {\n    let a = 1;\n    bar(a);\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("new.rs"),
+            "// New comment\n\nfn foo() {\n        let a = 1;\n    bar(a);\n}\n",
+        )
+        .unwrap();
+
+        let mut sources = SourceCache::new(dir.path());
+        let old = fingerprint(&source_context(&mut sources, "old.rs", 3));
+        let new = fingerprint(&source_context(&mut sources, "new.rs", 5));
+        assert_eq!(old, new);
+        assert_ne!(old, fingerprint(&source_context(&mut sources, "new.rs", 4)));
+
+        let text = old.to_string();
+        assert_eq!(text.len(), 16);
+        assert_eq!(text.parse::<Fingerprint>().unwrap(), old);
+    }
+
+    fn fingerprint(context: &str) -> Fingerprint {
+        Fingerprint::compute(
+            "inline",
+            "NeverInline",
+            "foo::main",
+            &[MessagePart::String("'bar' not inlined".to_string())],
+            context,
+        )
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use regex::Regex;
+
+use crate::remark::find_remark_files;
+use crate::utils::data_structures::{Map, Set};
+
+/// Source directory against which relative remark locations were resolved.
+pub struct SourceDirResolution {
+    pub source_dir: PathBuf,
+    /// Number of relative remark locations that point to an existing file in `source_dir`.
+    pub resolved: u64,
+    /// Total number of relative remark locations.
+    pub total: u64,
+}
+
+/// Counts how many relative remark locations found in `remark_dir` resolve to an existing file
+/// inside `source_dir`.
+pub fn check_source_dir(
+    remark_dir: &Path,
+    source_dir: &Path,
+) -> anyhow::Result<SourceDirResolution> {
+    let locations = collect_relative_locations(remark_dir)?;
+    Ok(SourceDirResolution {
+        source_dir: source_dir.to_path_buf(),
+        resolved: count_resolved(&locations, source_dir),
+        total: locations.values().sum(),
+    })
+}
+
+/// Tries to find the source directory from which remarks in `remark_dir` were generated.
+///
+/// Candidates are the Cargo workspace and package roots and all other ancestors of the remark
+/// directory and of the current working directory. The candidate under which most of the
+/// relative remark locations resolve wins, Cargo roots are preferred on ties.
+pub fn infer_source_dir(remark_dir: &Path) -> anyhow::Result<SourceDirResolution> {
+    let remark_dir = remark_dir
+        .canonicalize()
+        .with_context(|| format!("Cannot find remark directory {}", remark_dir.display()))?;
+    let locations = collect_relative_locations(&remark_dir)?;
+
+    let mut roots = vec![remark_dir];
+    if let Ok(cwd) = std::env::current_dir() {
+        roots.push(cwd);
+    }
+    let candidates = get_candidates(&roots);
+
+    let total = locations.values().sum();
+    let mut best: Option<SourceDirResolution> = None;
+    for candidate in candidates {
+        let resolved = count_resolved(&locations, &candidate);
+        if best.as_ref().map(|b| resolved > b.resolved).unwrap_or(true) {
+            best = Some(SourceDirResolution {
+                source_dir: candidate,
+                resolved,
+                total,
+            });
+        }
+    }
+    best.ok_or_else(|| anyhow::anyhow!("Cannot infer source directory"))
+}
+
+/// Returns candidate source directories in the order of preference.
+fn get_candidates(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut candidates = vec![];
+    for root in roots {
+        let manifest_dirs: Vec<&Path> = root
+            .ancestors()
+            .filter(|dir| dir.join("Cargo.toml").is_file())
+            .collect();
+        if let Some(workspace) = manifest_dirs
+            .iter()
+            .rev()
+            .find(|dir| is_workspace_manifest(&dir.join("Cargo.toml")))
+        {
+            candidates.push(workspace.to_path_buf());
+        }
+        candidates.extend(manifest_dirs.iter().map(|dir| dir.to_path_buf()));
+    }
+    for root in roots {
+        candidates.extend(root.ancestors().map(|dir| dir.to_path_buf()));
+    }
+
+    let mut visited = Set::default();
+    candidates.retain(|dir| visited.insert(dir.clone()));
+    candidates
+}
+
+fn is_workspace_manifest(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .map(|content| content.lines().any(|line| line.trim() == "[workspace]"))
+        .unwrap_or(false)
+}
+
+fn count_resolved(locations: &Map<String, u64>, source_dir: &Path) -> u64 {
+    locations
+        .iter()
+        .filter(|(file, _)| source_dir.join(file).is_file())
+        .map(|(_, count)| count)
+        .sum()
+}
+
+static FILE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Finds all relative file paths referenced by `DebugLoc`s in remark files, along with the number
+/// of their occurrences.
+///
+/// The files are scanned textually, which is much faster than deserializing them.
+fn collect_relative_locations(remark_dir: &Path) -> anyhow::Result<Map<String, u64>> {
+    let regex = FILE_REGEX.get_or_init(|| {
+        Regex::new(r#"File:\s*(?:'((?:[^']|'')*)'|"([^"]*)"|([^,}\s]+))"#)
+            .expect("Could not create regular expression")
+    });
+
+    let mut locations: Map<String, u64> = Map::default();
+    for file in find_remark_files(remark_dir)? {
+        let content = std::fs::read_to_string(&file)
+            .with_context(|| format!("Cannot read remark file {}", file.display()))?;
+        for captures in regex.captures_iter(&content) {
+            let path = match (captures.get(1), captures.get(2), captures.get(3)) {
+                (Some(path), _, _) => path.as_str().replace("''", "'"),
+                (_, Some(path), _) | (_, _, Some(path)) => path.as_str().to_string(),
+                _ => continue,
+            };
+            if Path::new(&path).is_absolute() || path.starts_with('/') {
+                continue;
+            }
+            *locations.entry(path).or_default() += 1;
+        }
+    }
+    Ok(locations)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remark::locate::{check_source_dir, get_candidates, infer_source_dir};
+    use std::path::Path;
+
+    const REMARK: &str = r#"--- !Missed
+Pass:            inline
+Name:            NoDefinition
+DebugLoc:        { File: 'src/main.rs', Line: 7, Column: 5 }
+Function:        _ZN7remarks4main17hc92ae132ef1efa8eE
+Args:
+  - Caller:          _ZN7remarks4main17hc92ae132ef1efa8eE
+    DebugLoc:        { File: "src/other.rs", Line: 6, Column: 0 }
+  - ClobberedBy:     call
+    DebugLoc:        { File: /rustc/library/core/src/result.rs, Line: 1, Column: 0 }
+...
+"#;
+
+    #[test]
+    fn infer_crate_root() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let remark_dir = root.join("target/remarks/yaml");
+        std::fs::create_dir_all(&remark_dir).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]").unwrap();
+        std::fs::write(root.join("src/main.rs"), "").unwrap();
+        std::fs::write(remark_dir.join("foo.opt.opt.yaml"), REMARK).unwrap();
+
+        let resolution = infer_source_dir(&remark_dir).unwrap();
+        assert_eq!(resolution.source_dir, root);
+        assert_eq!(resolution.resolved, 1);
+        assert_eq!(resolution.total, 2);
+
+        assert_eq!(
+            check_source_dir(&remark_dir, &remark_dir).unwrap().resolved,
+            0
+        );
+    }
+
+    #[test]
+    fn prefer_workspace_root() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        let member = root.join("crates/foo");
+        std::fs::create_dir_all(&member).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[workspace]\nmembers = []").unwrap();
+        std::fs::write(member.join("Cargo.toml"), "[package]").unwrap();
+
+        let candidates = get_candidates(std::slice::from_ref(&member));
+        assert_eq!(candidates[0], Path::new(root));
+        assert_eq!(candidates[1], member);
+    }
+}
@@ -3,20 +3,36 @@ use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::Context;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use regex::Regex;
 use serde::Deserialize;
 use serde_yaml::Value;
 
+use crate::filter::RemarkFilter;
+use crate::remark::diagnostic::{parse_diagnostics, DIAGNOSTICS_EXTENSION};
+use crate::remark::fingerprint::{
+    load_stored_fingerprints, source_context, FileFingerprints, StoredFingerprints,
+};
 use crate::remark::parse::{MissedRemark, RemarkArg, RemarkArgCallee, RemarkArgCaller};
+use crate::remark::suppress::is_suppressed;
 use crate::utils::callback::LoadCallback;
+use crate::utils::io::normalize_separators;
+use crate::utils::source::SourceCache;
 use crate::utils::timing::time_block_log_debug;
 use crate::RustcSourceRoot;
 
+pub mod diagnostic;
+mod fingerprint;
+pub mod locate;
+mod origin;
 mod parse;
+mod suppress;
+
+pub use fingerprint::{Fingerprint, FINGERPRINTS_FILE_NAME};
+pub use origin::{RemarkOrigin, RemarkStage};
 
 /// We expect that the remark YAML files will have this extension.
 const EXPECTED_EXTENSION: &str = ".opt.yaml";
@@ -24,32 +40,164 @@ const EXPECTED_EXTENSION: &str = ".opt.yaml";
 pub type Line = u32;
 pub type Column = u32;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
 pub struct Location {
     pub file: String,
     pub line: Line,
     pub column: Column,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, serde::Serialize)]
 pub struct Function {
     pub name: String,
     pub location: Option<Location>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, serde::Serialize)]
 pub enum MessagePart {
     String(String),
     AnnotatedString { message: String, location: Location },
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, serde::Serialize)]
 pub struct Remark {
     pub pass: String,
     pub name: String,
     pub function: Function,
     pub message: Vec<MessagePart>,
     pub hotness: Option<i32>,
+    /// Remark file from which the remark was loaded.
+    pub origin: Option<Arc<RemarkOrigin>>,
+    pub fingerprint: Fingerprint,
+    /// The remark was accepted using a `// remark: allow(<kind>)` source comment.
+    pub suppressed: bool,
+}
+
+/// Builds remarks used in unit tests.
+/// By default, the remark has no message and no hotness, and it is located in `foo` at
+/// `src/lib.rs:1:1`. Its fingerprint is computed from its fields, unless it is set explicitly.
+#[cfg(test)]
+pub(crate) struct RemarkBuilder {
+    remark: Remark,
+    fingerprint: Option<Fingerprint>,
+}
+
+#[cfg(test)]
+impl RemarkBuilder {
+    pub(crate) fn new(pass: &str, name: &str) -> Self {
+        Self {
+            remark: Remark {
+                pass: pass.to_string(),
+                name: name.to_string(),
+                function: Function {
+                    name: "foo".to_string(),
+                    location: Some(Location {
+                        file: "src/lib.rs".to_string(),
+                        line: 1,
+                        column: 1,
+                    }),
+                },
+                message: vec![],
+                hotness: None,
+                origin: None,
+                fingerprint: Fingerprint::default(),
+                suppressed: false,
+            },
+            fingerprint: None,
+        }
+    }
+
+    pub(crate) fn function(mut self, name: &str) -> Self {
+        self.remark.function.name = name.to_string();
+        self
+    }
+
+    pub(crate) fn location(mut self, file: &str, line: Line, column: Column) -> Self {
+        self.remark.function.location = Some(Location {
+            file: file.to_string(),
+            line,
+            column,
+        });
+        self
+    }
+
+    pub(crate) fn without_location(mut self) -> Self {
+        self.remark.function.location = None;
+        self
+    }
+
+    pub(crate) fn message(mut self, message: &str) -> Self {
+        self.remark.message = vec![MessagePart::String(message.to_string())];
+        self
+    }
+
+    pub(crate) fn message_parts(mut self, parts: Vec<MessagePart>) -> Self {
+        self.remark.message = parts;
+        self
+    }
+
+    pub(crate) fn hotness(mut self, hotness: Option<i32>) -> Self {
+        self.remark.hotness = hotness;
+        self
+    }
+
+    /// Sets the fingerprint from its hexadecimal representation.
+    pub(crate) fn fingerprint(mut self, fingerprint: &str) -> Self {
+        self.fingerprint = Some(fingerprint.parse().expect("Invalid fingerprint"));
+        self
+    }
+
+    pub(crate) fn build(self) -> Remark {
+        let mut remark = self.remark;
+        remark.fingerprint = self.fingerprint.unwrap_or_else(|| {
+            Fingerprint::compute(
+                &remark.pass,
+                &remark.name,
+                &remark.function.name,
+                &remark.message,
+                "",
+            )
+        });
+        remark
+    }
+}
+
+/// Replaces the `from` prefix of source file paths found in remarks with the `to` path.
+/// The prefix has to match whole path components.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PathMapping {
+    pub from: String,
+    pub to: PathBuf,
+}
+
+impl FromStr for PathMapping {
+    type Err = anyhow::Error;
+
+    /// Parses a mapping in the `FROM=TO` format.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once('=') {
+            Some((from, to)) if !from.is_empty() => Ok(Self {
+                from: from.to_string(),
+                to: PathBuf::from(to),
+            }),
+            _ => Err(anyhow::anyhow!(
+                "Invalid path mapping `{value}`, expected `FROM=TO`"
+            )),
+        }
+    }
+}
+
+/// Remaps `path` using the first mapping whose prefix matches it, or returns `None` if no mapping
+/// matches.
+pub fn remap_path(mappings: &[PathMapping], path: &Path) -> Option<PathBuf> {
+    mappings.iter().find_map(|mapping| {
+        let suffix = path.strip_prefix(&mapping.from).ok()?;
+        Some(if suffix.as_os_str().is_empty() {
+            mapping.to.clone()
+        } else {
+            mapping.to.join(suffix)
+        })
+    })
 }
 
 #[derive(Default)]
@@ -60,16 +208,28 @@ pub struct RemarkLoadOptions {
     pub source_dir: PathBuf,
     /// Remark kinds that should be ignored
     pub filter_kind: Vec<String>,
+    /// Expressions that select which remarks should be loaded
+    pub filter: RemarkFilter,
     /// Root path of rustc toolchain sources
     pub rustc_source_root: Option<RustcSourceRoot>,
+    /// Path prefixes that should be remapped, only the first matching mapping is applied
+    pub path_mappings: Vec<PathMapping>,
 }
 
 pub fn load_remarks_from_file<P: AsRef<Path>>(
     path: P,
     options: &RemarkLoadOptions,
 ) -> anyhow::Result<Vec<Remark>> {
-    let path = path.as_ref();
+    load_file(path.as_ref(), options, &mut vec![])
+}
 
+/// Loads remarks from a remark file. Fingerprints found in `fingerprints` are used instead of
+/// computing them, and computed fingerprints are added to it.
+fn load_file(
+    path: &Path,
+    options: &RemarkLoadOptions,
+    fingerprints: &mut FileFingerprints,
+) -> anyhow::Result<Vec<Remark>> {
     let file =
         File::open(path).with_context(|| format!("Cannot open remark file {}", path.display()))?;
     log::debug!("Parsing {}", path.display());
@@ -80,14 +240,32 @@ pub fn load_remarks_from_file<P: AsRef<Path>>(
     }
 
     let reader = BufReader::new(file);
-
-    let remarks = time_block_log_debug("Parsed remark file", || parse_remarks(reader, options));
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let remarks = if file_name.ends_with(DIAGNOSTICS_EXTENSION) {
+        time_block_log_debug("Parsed diagnostics file", || {
+            parse_diagnostics(reader, options, &file_name, fingerprints)
+        })
+    } else {
+        let origin = Arc::new(RemarkOrigin::from_path(path));
+        time_block_log_debug("Parsed remark file", || {
+            parse_remarks(reader, options, Some(origin), fingerprints)
+        })
+    };
     Ok(remarks)
 }
 
-fn parse_remarks<R: std::io::Read>(reader: R, options: &RemarkLoadOptions) -> Vec<Remark> {
+fn parse_remarks<R: std::io::Read>(
+    reader: R,
+    options: &RemarkLoadOptions,
+    origin: Option<Arc<RemarkOrigin>>,
+    fingerprints: &mut FileFingerprints,
+) -> Vec<Remark> {
     let mut remarks = vec![];
-    for document in serde_yaml::Deserializer::from_reader(reader) {
+    let mut sources = SourceCache::new(&options.source_dir);
+    for (index, document) in serde_yaml::Deserializer::from_reader(reader).enumerate() {
         match parse::Remark::deserialize(document) {
             Ok(remark) => {
                 // TODO: optimize (intern)
@@ -103,19 +281,7 @@ fn parse_remarks<R: std::io::Read>(reader: R, options: &RemarkLoadOptions) -> Ve
                         } = remark;
 
                         if let Some(location) = debug_loc {
-                            if !options.external {
-                                if location.file.starts_with('/') {
-                                    continue;
-                                }
-                                if !options.source_dir.join(location.file.as_ref()).is_file() {
-                                    continue;
-                                }
-                            }
-                            if options
-                                .filter_kind
-                                .iter()
-                                .any(|filter| filter == name.as_ref())
-                            {
+                            if is_ignored(options, &location.file, &name) {
                                 continue;
                             }
 
@@ -128,8 +294,15 @@ fn parse_remarks<R: std::io::Read>(reader: R, options: &RemarkLoadOptions) -> Ve
                                 },
                                 message: construct_message(options, args),
                                 hotness,
+                                origin: origin.clone(),
+                                fingerprint: Fingerprint::default(),
+                                suppressed: false,
                             };
-                            remarks.push(remark);
+                            if let Some(remark) =
+                                finish_remark(remark, index, options, &mut sources, fingerprints)
+                            {
+                                remarks.push(remark);
+                            }
                         }
                     }
                     parse::Remark::Passed {} | parse::Remark::Analysis {} => {}
@@ -143,6 +316,52 @@ fn parse_remarks<R: std::io::Read>(reader: R, options: &RemarkLoadOptions) -> Ve
     remarks
 }
 
+/// Returns true if a remark of kind `name` located in `file` should not be loaded.
+/// This is checked before the rest of the remark is parsed, since most remarks are ignored.
+fn is_ignored(options: &RemarkLoadOptions, file: &str, name: &str) -> bool {
+    if !options.external && (file.starts_with('/') || !options.source_dir.join(file).is_file()) {
+        return true;
+    }
+    options.filter_kind.iter().any(|filter| filter == name)
+}
+
+/// Finds the fingerprint and computes the suppression status of `remark`, and applies the filter
+/// expressions. Returns `None` if the remark is filtered out.
+///
+/// `index` is the position of the remark in its remark file. If `fingerprints` does not contain
+/// the fingerprint of the remark, it is computed from the current sources and stored into it.
+fn finish_remark(
+    mut remark: Remark,
+    index: usize,
+    options: &RemarkLoadOptions,
+    sources: &mut SourceCache,
+    fingerprints: &mut FileFingerprints,
+) -> Option<Remark> {
+    if let Some(ref location) = remark.function.location {
+        remark.fingerprint = match fingerprints.get(index).copied().flatten() {
+            Some(fingerprint) => fingerprint,
+            None => {
+                let fingerprint = Fingerprint::compute(
+                    &remark.pass,
+                    &remark.name,
+                    &remark.function.name,
+                    &remark.message,
+                    &source_context(sources, &location.file, location.line),
+                );
+                if fingerprints.len() <= index {
+                    fingerprints.resize(index + 1, None);
+                }
+                fingerprints[index] = Some(fingerprint);
+                fingerprint
+            }
+        };
+        remark.suppressed = sources
+            .lines(&location.file)
+            .is_some_and(|lines| is_suppressed(lines, location.line, &remark.name));
+    }
+    options.filter.matches(&remark).then_some(remark)
+}
+
 fn construct_message(opts: &RemarkLoadOptions, arguments: Vec<RemarkArg>) -> Vec<MessagePart> {
     let mut parts = vec![];
     let mut buffer = String::new();
@@ -226,24 +445,8 @@ pub fn load_remarks_from_dir<P: AsRef<Path>>(
         .to_path_buf()
         .canonicalize()
         .with_context(|| format!("Cannot find remark directory {}", path.as_ref().display()))?;
-    let files: Vec<PathBuf> = std::fs::read_dir(&dir)
-        .with_context(|| format!("Cannot read remark directory {}", dir.display()))?
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            if !entry.file_type().ok()?.is_file() {
-                return None;
-            }
-            if !entry
-                .file_name()
-                .to_str()
-                .map(|extension| extension.ends_with(EXPECTED_EXTENSION))
-                .unwrap_or(false)
-            {
-                return None;
-            }
-            Some(entry.path())
-        })
-        .collect();
+    let files = find_remark_files(&dir)?;
+    let stored = load_stored_fingerprints(&dir);
 
     log::debug!("Parsing {} file(s) from {}", files.len(), dir.display());
 
@@ -254,7 +457,12 @@ pub fn load_remarks_from_dir<P: AsRef<Path>>(
     let remarks: Vec<(PathBuf, anyhow::Result<Vec<Remark>>)> = files
         .into_par_iter()
         .map(|file| {
-            let remarks = load_remarks_from_file(&file, &options);
+            let mut fingerprints = file
+                .file_name()
+                .and_then(|name| stored.get(name.to_str()?))
+                .cloned()
+                .unwrap_or_default();
+            let remarks = load_file(&file, &options, &mut fingerprints);
             if let Some(callback) = callback {
                 callback.advance();
             }
@@ -281,6 +489,62 @@ pub fn load_remarks_from_dir<P: AsRef<Path>>(
     Ok(remarks)
 }
 
+/// Computes fingerprints of all remarks in `dir` from the current sources, and stores them into
+/// [`FINGERPRINTS_FILE_NAME`] inside `dir`. Remarks loaded from `dir` later use the stored
+/// fingerprints, so that they do not change when the sources are edited.
+pub fn store_fingerprints(dir: &Path, options: RemarkLoadOptions) -> anyhow::Result<()> {
+    // Remarks that are filtered out now can be loaded later with different options
+    let options = RemarkLoadOptions {
+        external: true,
+        filter_kind: vec![],
+        filter: RemarkFilter::default(),
+        ..options
+    };
+    let stored = find_remark_files(dir)?
+        .into_par_iter()
+        .map(|file| {
+            let mut fingerprints = vec![];
+            load_file(&file, &options, &mut fingerprints)?;
+            let name = file
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Ok((name, fingerprints))
+        })
+        .collect::<anyhow::Result<StoredFingerprints>>()?;
+
+    let path = dir.join(FINGERPRINTS_FILE_NAME);
+    let file = File::create(&path)
+        .with_context(|| format!("Cannot create fingerprint file {}", path.display()))?;
+    serde_json::to_writer(std::io::BufWriter::new(file), &stored)?;
+    Ok(())
+}
+
+/// Finds all remark files located directly inside `dir`.
+pub(crate) fn find_remark_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let files = std::fs::read_dir(dir)
+        .with_context(|| format!("Cannot read remark directory {}", dir.display()))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            if !entry.file_type().ok()?.is_file() {
+                return None;
+            }
+            if !entry
+                .file_name()
+                .to_str()
+                .map(|name| {
+                    name.ends_with(EXPECTED_EXTENSION) || name.ends_with(DIAGNOSTICS_EXTENSION)
+                })
+                .unwrap_or(false)
+            {
+                return None;
+            }
+            Some(entry.path())
+        })
+        .collect();
+    Ok(files)
+}
+
 fn parse_debug_loc(options: &RemarkLoadOptions, location: parse::DebugLocation) -> Location {
     let file = normalize_path(options, location.file);
 
@@ -294,43 +558,45 @@ fn parse_debug_loc(options: &RemarkLoadOptions, location: parse::DebugLocation)
 fn normalize_path(options: &RemarkLoadOptions, path: Cow<str>) -> String {
     const RUSTC_PREFIX: &str = "/rustc/";
 
+    let mut path = path.into_owned();
     if let Some(ref rustc_source_root) = options.rustc_source_root {
-        if let Some(path) = path.strip_prefix(RUSTC_PREFIX) {
-            if let Some(index) = path.find('/') {
-                let src_path = &path[index + 1..];
+        if let Some(suffix) = path.strip_prefix(RUSTC_PREFIX) {
+            if let Some(index) = suffix.find('/') {
+                let src_path = &suffix[index + 1..];
                 let src_path = rustc_source_root.0.join(src_path);
-                return src_path.to_str().unwrap().to_string().replace('\\', "/");
+                path = normalize_separators(&src_path);
             }
         }
     }
-    path.into_owned()
+    if let Some(mapped) = remap_path(&options.path_mappings, Path::new(&path)) {
+        path = normalize_separators(&mapped);
+    }
+    path
 }
 
-static HASH_REGEX: OnceLock<Regex> = OnceLock::new();
-
 fn demangle(function: &str) -> String {
-    // Remove hash from the end of legacy dmangled named
-    let regex = HASH_REGEX.get_or_init(|| {
-        Regex::new(r#".*::[a-z0-9]{17}$"#).expect("Could not create regular expression")
-    });
-    let mut demangled = rustc_demangle::demangle(function).to_string();
-    if regex.find(&demangled).is_some() {
-        demangled.drain(demangled.len() - 19..);
-    }
-    demangled
+    // The alternate format omits the hashes of legacy symbols and crate disambiguators of v0
+    // symbols (used by default by recent nightly toolchains), which change between builds
+    format!("{:#}", rustc_demangle::demangle(function))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::remark::{parse_remarks, Remark, RemarkLoadOptions};
+    use crate::filter::RemarkFilter;
+    use crate::remark::{
+        demangle, load_remarks_from_dir, parse_remarks, remap_path, store_fingerprints,
+        PathMapping, Remark, RemarkLoadOptions, FINGERPRINTS_FILE_NAME,
+    };
     use crate::RustcSourceRoot;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     struct Options {
         external: bool,
         filter_kind: Vec<String>,
+        filter: RemarkFilter,
         source_dir: PathBuf,
         rustc_source_root: Option<PathBuf>,
+        path_mappings: Vec<PathMapping>,
     }
 
     impl Options {
@@ -339,6 +605,11 @@ mod tests {
             self
         }
 
+        fn exclude(mut self, expr: &str) -> Self {
+            self.filter.exclude.push(expr.parse().unwrap());
+            self
+        }
+
         fn rustc_source_root(mut self, path: &str) -> Self {
             self.rustc_source_root = Some(PathBuf::from(path));
             self
@@ -348,6 +619,16 @@ mod tests {
             self.external = external;
             self
         }
+
+        fn source_dir(mut self, path: &Path) -> Self {
+            self.source_dir = path.to_path_buf();
+            self
+        }
+
+        fn map_path(mut self, mapping: &str) -> Self {
+            self.path_mappings.push(mapping.parse().unwrap());
+            self
+        }
     }
 
     impl Default for Options {
@@ -355,8 +636,10 @@ mod tests {
             Self {
                 external: true,
                 filter_kind: vec![],
+                filter: Default::default(),
                 source_dir: PathBuf::from("/tmp"),
                 rustc_source_root: None,
+                path_mappings: vec![],
             }
         }
     }
@@ -366,14 +649,18 @@ mod tests {
             let Options {
                 external,
                 filter_kind,
+                filter,
                 source_dir,
                 rustc_source_root,
+                path_mappings,
             } = value;
             Self {
                 external,
                 source_dir,
                 filter_kind,
+                filter,
                 rustc_source_root: rustc_source_root.map(RustcSourceRoot),
+                path_mappings,
             }
         }
     }
@@ -413,6 +700,9 @@ Args:
                     ),
                 ],
                 hotness: None,
+                origin: None,
+                fingerprint: Fingerprint(0d5d5fe640b87270),
+                suppressed: false,
             },
         ]
         "###);
@@ -478,6 +768,9 @@ Args:
                     ),
                 ],
                 hotness: None,
+                origin: None,
+                fingerprint: Fingerprint(0e2a638f11705df0),
+                suppressed: false,
             },
             Remark {
                 pass: "inline",
@@ -509,6 +802,9 @@ Args:
                     ),
                 ],
                 hotness: None,
+                origin: None,
+                fingerprint: Fingerprint(0a330bef61986206),
+                suppressed: false,
             },
         ]
         "###);
@@ -618,6 +914,15 @@ Args:
 ..."#;
 
         assert!(parse(input, Options::default().filter("Foo")).is_empty());
+        assert!(parse(
+            input,
+            Options::default().exclude(r#"file ~ "**/result.rs""#)
+        )
+        .is_empty());
+        assert_eq!(
+            parse(input, Options::default().exclude(r#"pass == "inline""#)).len(),
+            1
+        );
     }
 
     #[test]
@@ -664,6 +969,9 @@ Args:
                 hotness: Some(
                     2,
                 ),
+                origin: None,
+                fingerprint: Fingerprint(1b7acd14de9a31c6),
+                suppressed: false,
             },
         ]
         "###);
@@ -697,12 +1005,122 @@ Args:
                 },
                 message: [],
                 hotness: None,
+                origin: None,
+                fingerprint: Fingerprint(a36b319888602569),
+                suppressed: false,
             },
         ]
         "###);
     }
 
+    #[test]
+    fn parse_path_mapping() {
+        let input = r#"--- !Missed
+Pass:            regalloc
+Name:            LoopSpillReloadCopies
+DebugLoc:        { File: '/rustc/08d00b40aef2017fe6dba3ff7d6476efa0c10888/library/std/src/io/buffered/bufreader/buffer.rs', 
+                   Line: 114, Column: 13 }
+Function:        _ZN3std2io16append_to_string17hcf3f6e91099a64a2E
+Args:
+..."#;
+
+        let remarks = parse(
+            input,
+            Options::default()
+                .rustc_source_root("/foo/bar")
+                .map_path("/foo/bar/lib=/qux")
+                .map_path("/foo/bar/library=/baz")
+                .map_path("/baz/std=/qux"),
+        );
+        assert_eq!(
+            remarks[0].function.location.as_ref().unwrap().file,
+            "/baz/std/src/io/buffered/bufreader/buffer.rs"
+        );
+    }
+
+    #[test]
+    fn remap_first_matching_path() {
+        let mappings: Vec<PathMapping> = ["/build/foo=/a", "/build=/b", "/build=/c"]
+            .iter()
+            .map(|mapping| mapping.parse().unwrap())
+            .collect();
+        assert_eq!(
+            remap_path(&mappings, Path::new("/build/foo/src/lib.rs")),
+            Some(PathBuf::from("/a/src/lib.rs"))
+        );
+        assert_eq!(
+            remap_path(&mappings, Path::new("/build/foobar/lib.rs")),
+            Some(PathBuf::from("/b/foobar/lib.rs"))
+        );
+        assert_eq!(
+            remap_path(&mappings, Path::new("/build")),
+            Some(PathBuf::from("/b"))
+        );
+        assert_eq!(remap_path(&mappings, Path::new("/buildx/lib.rs")), None);
+    }
+
+    #[test]
+    fn demangle_symbols() {
+        assert_eq!(
+            demangle("_ZN3std2io16append_to_string17hcf3f6e91099a64a2E"),
+            "std::io::append_to_string"
+        );
+        assert_eq!(demangle("_RNvCs1234_8diagtest4main"), "diagtest::main");
+        assert_eq!(demangle("main"), "main");
+    }
+
+    #[test]
+    fn load_stored_fingerprints() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let yaml_dir = dir.path().join("yaml");
+        std::fs::create_dir(&yaml_dir).unwrap();
+        std::fs::write(
+            dir.path().join("main.rs"),
+            "fn main() {\n    let a = foo();\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            yaml_dir.join("main.opt.yaml"),
+            r#"--- !Missed
+Pass:            inline
+Name:            NeverInline
+DebugLoc:        { File: main.rs, Line: 2, Column: 13 }
+Function:        main
+Args:
+  - String:          'foo not inlined'
+..."#,
+        )
+        .unwrap();
+        let load = || {
+            load_remarks_from_dir(
+                &yaml_dir,
+                Options::default().source_dir(dir.path()).into(),
+                None,
+            )
+            .unwrap()[0]
+                .fingerprint
+        };
+
+        let original = load();
+        store_fingerprints(&yaml_dir, Options::default().source_dir(dir.path()).into()).unwrap();
+        std::fs::write(
+            dir.path().join("main.rs"),
+            "fn main() {\n    let a = foo();\n    bar(a);\n}\n",
+        )
+        .unwrap();
+        assert_eq!(load(), original);
+
+        std::fs::remove_file(yaml_dir.join(FINGERPRINTS_FILE_NAME)).unwrap();
+        assert_ne!(load(), original);
+    }
+
+    #[test]
+    fn parse_path_mapping_invalid() {
+        assert!("foo".parse::<PathMapping>().is_err());
+        assert!("=/foo".parse::<PathMapping>().is_err());
+    }
+
     fn parse(input: &str, opts: Options) -> Vec<Remark> {
-        parse_remarks(input.as_bytes(), &opts.into())
+        parse_remarks(input.as_bytes(), &opts.into(), None, &mut vec![])
     }
 }
@@ -0,0 +1,158 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::remark::EXPECTED_EXTENSION;
+
+/// LLVM stage during which a remark was emitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemarkStage {
+    /// Optimization pipeline (`*.opt.opt.yaml`).
+    #[serde(rename = "opt")]
+    Optimization,
+    /// Optimization pipeline of a module merged by fat LTO (`*.lto.opt.yaml`).
+    Lto,
+    /// Codegen backend (`*.codegen.opt.yaml`).
+    Codegen,
+    /// Unknown stage of a remark printed as a rustc diagnostic (`*.diagnostics.json`).
+    Diagnostic,
+}
+
+impl RemarkStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemarkStage::Optimization => "opt",
+            RemarkStage::Lto => "lto",
+            RemarkStage::Codegen => "codegen",
+            RemarkStage::Diagnostic => "diagnostic",
+        }
+    }
+}
+
+impl Display for RemarkStage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Describes where does a remark come from, based on the name of its remark file.
+///
+/// Remark files generated by rustc are named `<module>.<stage>.opt.yaml`, where the module
+/// name usually has the form `<crate>.<crate-hash>-cgu.<cgu-index>`. Modules imported by thin LTO
+/// are named `<crate>-<hash>.<crate>.<crate-hash>-cgu.<cgu-index>.rcgu.o`.
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct RemarkOrigin {
+    /// Name of the remark file.
+    pub file: String,
+    pub crate_name: Option<String>,
+    pub crate_hash: Option<String>,
+    /// Index of the codegen unit.
+    pub cgu: Option<u32>,
+    pub stage: Option<RemarkStage>,
+    /// True if the remark was emitted while processing a module merged or imported by LTO.
+    pub lto: bool,
+}
+
+static MODULE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+impl RemarkOrigin {
+    pub fn from_path(path: &Path) -> Self {
+        let file = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self::from_file_name(file)
+    }
+
+    fn from_file_name(file: String) -> Self {
+        let stem = file.strip_suffix(EXPECTED_EXTENSION).unwrap_or(&file);
+        let (module, stage) = match stem.rsplit_once('.') {
+            Some((module, "opt")) => (module, Some(RemarkStage::Optimization)),
+            Some((module, "lto")) => (module, Some(RemarkStage::Lto)),
+            Some((module, "codegen")) => (module, Some(RemarkStage::Codegen)),
+            _ => (stem, None),
+        };
+
+        let regex = MODULE_REGEX.get_or_init(|| {
+            Regex::new(r"^(?:[^.]+\.)?([^.]+)\.([0-9a-f]+)-cgu\.(\d+)(\.rcgu\.o)?$")
+                .expect("Could not create regular expression")
+        });
+        let captures = regex.captures(module);
+        let capture = |index: usize| {
+            captures
+                .as_ref()
+                .and_then(|c| c.get(index))
+                .map(|m| m.as_str())
+        };
+        let crate_name = capture(1).map(|name| name.to_string());
+        let crate_hash = capture(2).map(|hash| hash.to_string());
+        let cgu = capture(3).and_then(|cgu| cgu.parse().ok());
+        let lto = stage == Some(RemarkStage::Lto) || capture(4).is_some();
+
+        Self {
+            file,
+            crate_name,
+            crate_hash,
+            cgu,
+            stage,
+            lto,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remark::origin::{RemarkOrigin, RemarkStage};
+
+    #[test]
+    fn origin_optimization() {
+        let origin = parse("remarks.67ea4a01cbc73fb0-cgu.0.opt.opt.yaml");
+        assert_eq!(origin.crate_name.as_deref(), Some("remarks"));
+        assert_eq!(origin.crate_hash.as_deref(), Some("67ea4a01cbc73fb0"));
+        assert_eq!(origin.cgu, Some(0));
+        assert_eq!(origin.stage, Some(RemarkStage::Optimization));
+        assert!(!origin.lto);
+    }
+
+    #[test]
+    fn origin_codegen() {
+        let origin = parse("remarks.67ea4a01cbc73fb0-cgu.12.codegen.opt.yaml");
+        assert_eq!(origin.cgu, Some(12));
+        assert_eq!(origin.stage, Some(RemarkStage::Codegen));
+        assert!(!origin.lto);
+    }
+
+    #[test]
+    fn origin_fat_lto() {
+        let origin = parse("foo.f59cdfb1b8e8621d-cgu.0.lto.opt.yaml");
+        assert_eq!(origin.crate_name.as_deref(), Some("foo"));
+        assert_eq!(origin.stage, Some(RemarkStage::Lto));
+        assert!(origin.lto);
+    }
+
+    #[test]
+    fn origin_thin_lto() {
+        let origin =
+            parse("alloc-70e0a57a472ca919.alloc.87b0fb19d3271c63-cgu.0.rcgu.o.codegen.opt.yaml");
+        assert_eq!(origin.crate_name.as_deref(), Some("alloc"));
+        assert_eq!(origin.crate_hash.as_deref(), Some("87b0fb19d3271c63"));
+        assert_eq!(origin.stage, Some(RemarkStage::Codegen));
+        assert!(origin.lto);
+    }
+
+    #[test]
+    fn origin_unknown_module() {
+        let origin = parse("37v4yjwjhlguzgkm.opt.opt.yaml");
+        assert_eq!(origin.crate_name, None);
+        assert_eq!(origin.cgu, None);
+        assert_eq!(origin.stage, Some(RemarkStage::Optimization));
+        assert!(!origin.lto);
+    }
+
+    fn parse(file: &str) -> RemarkOrigin {
+        RemarkOrigin::from_file_name(file.to_string())
+    }
+}
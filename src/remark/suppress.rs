@@ -0,0 +1,150 @@
+//! Suppression of accepted remarks using source comments.
+//!
+//! A remark is suppressed by a `// remark: allow(Kind1, Kind2)` comment located on the line of the
+//! remark, on the comment or attribute lines directly above it, or on the signature of the
+//! enclosing function (or directly above it).
+use crate::remark::Line;
+
+const SUPPRESSION_PREFIX: &str = "remark:";
+
+/// Returns true if a remark of kind `name` located at `line` is suppressed.
+pub(crate) fn is_suppressed(lines: &[String], line: Line, name: &str) -> bool {
+    let Some(index) = (line as usize).checked_sub(1) else {
+        return false;
+    };
+    if index >= lines.len() {
+        return false;
+    }
+
+    let allows = |index: usize| {
+        annotated_lines(lines, index).any(|line| allowed_kinds(line).any(|kind| kind == name))
+    };
+    allows(index) || find_enclosing_fn(lines, index).is_some_and(allows)
+}
+
+/// Returns the line at `index`, followed by the comment and attribute lines directly above it.
+fn annotated_lines(lines: &[String], index: usize) -> impl Iterator<Item = &str> {
+    std::iter::once(lines[index].trim()).chain(
+        lines[..index]
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .take_while(|line| line.starts_with("//") || line.starts_with("#[")),
+    )
+}
+
+/// Parses remark kinds from a `// remark: allow(...)` comment located on `line`.
+fn allowed_kinds(line: &str) -> impl Iterator<Item = &str> {
+    line.find("//")
+        .and_then(|index| {
+            let comment = line[index..].trim_start_matches(['/', '!']).trim_start();
+            let allow = comment.strip_prefix(SUPPRESSION_PREFIX)?.trim_start();
+            let kinds = allow.strip_prefix("allow(")?;
+            let end = kinds.find(')')?;
+            Some(&kinds[..end])
+        })
+        .into_iter()
+        .flat_map(|kinds| kinds.split(','))
+        .map(|kind| kind.trim())
+        .filter(|kind| !kind.is_empty())
+}
+
+/// Finds the signature of the function that contains the line at `index`, by looking for the
+/// closest preceding `fn` line that opens a block which is not closed before `index`.
+fn find_enclosing_fn(lines: &[String], index: usize) -> Option<usize> {
+    let mut depth: i64 = 0;
+    for (index, line) in lines[..index].iter().enumerate().rev() {
+        let line = line.trim();
+        if line.starts_with("//") {
+            continue;
+        }
+        depth += line.matches('{').count() as i64 - line.matches('}').count() as i64;
+        if depth > 0 && is_fn_signature(line) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+fn is_fn_signature(line: &str) -> bool {
+    for word in line.split_whitespace() {
+        match word {
+            "fn" => return true,
+            "pub" | "const" | "async" | "unsafe" | "extern" | "default" => {}
+            word if word.starts_with("pub(") || word.starts_with('"') => {}
+            _ => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remark::suppress::{allowed_kinds, is_suppressed};
+
+    #[test]
+    fn parse_allowed_kinds() {
+        assert_eq!(
+            allowed_kinds("// remark: allow(LoadClobbered, NeverInline)").collect::<Vec<_>>(),
+            vec!["LoadClobbered", "NeverInline"]
+        );
+        assert_eq!(
+            allowed_kinds("foo(); //remark:allow(NoDefinition)").collect::<Vec<_>>(),
+            vec!["NoDefinition"]
+        );
+        assert_eq!(allowed_kinds("// remark: deny(Foo)").count(), 0);
+        assert_eq!(allowed_kinds("let url = \"http://remark\";").count(), 0);
+    }
+
+    #[test]
+    fn suppress_on_or_above_line() {
+        let lines = source(
+            r#"
+fn foo() {
+    bar(); // remark: allow(LoadClobbered)
+    // remark: allow(NeverInline)
+    #[allow(unused)]
+    let a = baz();
+    baz();
+}
+"#,
+        );
+        assert!(is_suppressed(&lines, 2, "LoadClobbered"));
+        assert!(!is_suppressed(&lines, 2, "NeverInline"));
+        assert!(is_suppressed(&lines, 5, "NeverInline"));
+        assert!(!is_suppressed(&lines, 6, "NeverInline"));
+        assert!(!is_suppressed(&lines, 100, "NeverInline"));
+    }
+
+    #[test]
+    fn suppress_on_enclosing_fn() {
+        let lines = source(
+            r#"
+// remark: allow(LoadClobbered)
+#[inline]
+pub(crate) fn foo() {
+    if true {
+        bar();
+    }
+}
+
+fn baz() { // remark: allow(NeverInline)
+    bar();
+}
+
+fn qux() {
+    bar();
+}
+"#,
+        );
+        assert!(is_suppressed(&lines, 5, "LoadClobbered"));
+        assert!(!is_suppressed(&lines, 5, "NeverInline"));
+        assert!(is_suppressed(&lines, 10, "NeverInline"));
+        assert!(!is_suppressed(&lines, 14, "LoadClobbered"));
+        assert!(!is_suppressed(&lines, 14, "NeverInline"));
+    }
+
+    fn source(text: &str) -> Vec<String> {
+        text.lines().skip(1).map(|line| line.to_string()).collect()
+    }
+}
@@ -2,7 +2,7 @@ use std::borrow::Cow;
 use std::fmt::Write;
 use std::fs::File;
 use std::io::BufWriter;
-use std::path::{Component, Path, Prefix, MAIN_SEPARATOR};
+use std::path::{Component, Path, PathBuf, Prefix, MAIN_SEPARATOR};
 
 use anyhow::Context;
 use askama::Template;
@@ -11,12 +11,21 @@ use rayon::iter::IntoParallelIterator;
 use rayon::prelude::*;
 use rust_embed::RustEmbed;
 
-use crate::remark::{Line, Location, MessagePart, Remark};
+use crate::diff::{RemarkChange, RemarkDiff};
+use crate::flags::CrateFlags;
+use crate::history::{HistoryEntry, RemarkSummary};
+use crate::matrix::RemarkMatrix;
+use crate::remark::{Line, Location, MessagePart, Remark, RemarkStage};
+use crate::triage::Triage;
 use crate::utils::callback::LoadCallback;
 use crate::utils::data_structures::{Map, Set};
 
 pub const INDEX_FILE_PATH: &str = "index.html";
 const REMARK_LIST_FILE_PATH: &str = "remarks.html";
+const SUPPRESSED_LIST_FILE_PATH: &str = "suppressed.html";
+const TRENDS_FILE_PATH: &str = "trends.html";
+/// All rendered remarks, exported in JSON format.
+pub const REMARK_EXPORT_FILE_PATH: &str = "remarks.json";
 
 /// Directory where sources will be stored.
 /// Relative to the output directory.
@@ -33,6 +42,19 @@ struct RemarkIndexEntry<'a> {
     function: Cow<'a, str>,
     message: String,
     hotness: Option<i32>,
+    /// Hotness of the matching old remark, when rendering a diff.
+    old_hotness: Option<i32>,
+    crate_name: Option<&'a str>,
+    stage: Option<&'static str>,
+    lto: bool,
+    change: Option<&'static str>,
+    /// Configurations in which the remark is present, when rendering a matrix.
+    configurations: Option<String>,
+    /// The remark is only present in some configurations of a matrix.
+    partial: bool,
+    fingerprint: String,
+    triage: Option<&'static str>,
+    triage_note: Option<&'a str>,
 }
 
 #[derive(serde::Serialize, PartialEq, Eq, Hash)]
@@ -42,12 +64,42 @@ struct RemarkSourceEntry<'a> {
     line: Line,
     message: String,
     hotness: Option<i32>,
+    change: Option<&'static str>,
+    fingerprint: String,
+    triage: Option<&'static str>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportedRemark<'a> {
+    #[serde(flatten)]
+    remark: &'a Remark,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    change: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_hotness: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    configurations: Option<&'a [String]>,
+}
+
+/// Number of remarks with a given change, or of a given matrix configuration.
+struct SummaryCount {
+    label: String,
+    count: usize,
 }
 
 #[derive(Template)]
 #[template(path = "remark-list.jinja")]
 pub struct RemarkListTemplate {
     remarks_json: String,
+    /// True if the remarks come from a diff.
+    diff: bool,
+    /// True if the remarks come from a matrix of configurations.
+    matrix: bool,
+    /// True if the list contains remarks suppressed by source comments.
+    suppressed: bool,
+    /// True if some remarks were collected from rustc diagnostics.
+    diagnostics: bool,
+    summary_counts: Vec<SummaryCount>,
 }
 
 #[derive(serde::Serialize)]
@@ -61,6 +113,24 @@ struct SourceFileLink<'a> {
 #[template(path = "index.jinja")]
 pub struct IndexTemplate<'a> {
     source_links: Vec<SourceFileLink<'a>>,
+    crate_flags: Vec<CrateFlags>,
+    /// True if some remarks were collected from rustc diagnostics.
+    diagnostics: bool,
+}
+
+#[derive(serde::Serialize)]
+struct TrendEntry<'a> {
+    run: &'a str,
+    date: &'a str,
+    git_commit: Option<&'a str>,
+    summary: &'a RemarkSummary,
+}
+
+#[derive(Template)]
+#[template(path = "trends.jinja")]
+pub struct TrendsTemplate {
+    run_count: usize,
+    history_json: String,
 }
 
 #[derive(Template)]
@@ -71,13 +141,161 @@ pub struct SourceFileTemplate<'a> {
     file_content: String,
 }
 
+/// Additional information that should be included in the rendered website.
+#[derive(Default)]
+pub struct RenderOptions {
+    /// Compiler flags used to compile the crates that have generated the remarks.
+    pub crate_flags: Vec<CrateFlags>,
+    /// History of previous runs, including the current one, sorted by date.
+    pub history: Vec<HistoryEntry>,
+    /// If set, an existing website in the output directory is updated in place, and only the
+    /// pages of these source files (and of files without a page) are rendered again.
+    pub rerender_files: Option<Set<String>>,
+    /// Triage state of the remarks.
+    pub triage: Triage,
+}
+
+/// Remark that should be rendered, together with its change status if a diff is rendered, or
+/// with its configurations if a matrix is rendered.
+struct RenderedRemark {
+    remark: Remark,
+    change: Option<RemarkChange>,
+    old_hotness: Option<i32>,
+    configurations: Option<Vec<String>>,
+    partial: bool,
+}
+
+enum RenderMode {
+    Remarks,
+    Diff,
+    Matrix {
+        configurations: Vec<String>,
+        counts: Vec<usize>,
+    },
+}
+
 pub fn render_remarks(
     remarks: Vec<Remark>,
     source_dir: &Path,
     output_dir: &Path,
+    options: RenderOptions,
+    callback: Option<&(dyn LoadCallback + Sync)>,
+) -> anyhow::Result<()> {
+    let remarks = remarks
+        .into_iter()
+        .map(|remark| RenderedRemark {
+            remark,
+            change: None,
+            old_hotness: None,
+            configurations: None,
+            partial: false,
+        })
+        .collect();
+    render(
+        remarks,
+        RenderMode::Remarks,
+        source_dir,
+        output_dir,
+        options,
+        callback,
+    )
+}
+
+/// Renders a website with the remarks of a diff.
+///
+/// `source_dir` should contain the sources of the new remarks. Removed remarks are only shown in
+/// the remark list, since their locations refer to the old sources.
+pub fn render_diff(
+    diff: RemarkDiff,
+    source_dir: &Path,
+    output_dir: &Path,
+    options: RenderOptions,
     callback: Option<&(dyn LoadCallback + Sync)>,
 ) -> anyhow::Result<()> {
-    let _ = std::fs::remove_dir_all(output_dir);
+    let remarks = diff
+        .entries
+        .into_iter()
+        .map(|entry| RenderedRemark {
+            remark: entry.remark,
+            change: Some(entry.change),
+            old_hotness: entry.old_hotness,
+            configurations: None,
+            partial: false,
+        })
+        .collect();
+    render(
+        remarks,
+        RenderMode::Diff,
+        source_dir,
+        output_dir,
+        options,
+        callback,
+    )
+}
+
+/// Renders a website with the remarks of all configurations of a matrix, which shows in which
+/// configurations is each remark present.
+pub fn render_matrix(
+    matrix: RemarkMatrix,
+    source_dir: &Path,
+    output_dir: &Path,
+    options: RenderOptions,
+    callback: Option<&(dyn LoadCallback + Sync)>,
+) -> anyhow::Result<()> {
+    let RemarkMatrix {
+        configurations,
+        counts,
+        entries,
+    } = matrix;
+    let remarks = entries
+        .into_iter()
+        .map(|entry| RenderedRemark {
+            remark: entry.remark,
+            change: None,
+            old_hotness: None,
+            partial: entry.configurations.len() < configurations.len(),
+            configurations: Some(
+                entry
+                    .configurations
+                    .iter()
+                    .map(|&index| configurations[index].clone())
+                    .collect(),
+            ),
+        })
+        .collect();
+    render(
+        remarks,
+        RenderMode::Matrix {
+            configurations,
+            counts,
+        },
+        source_dir,
+        output_dir,
+        options,
+        callback,
+    )
+}
+
+fn render(
+    remarks: Vec<RenderedRemark>,
+    mode: RenderMode,
+    source_dir: &Path,
+    output_dir: &Path,
+    options: RenderOptions,
+    callback: Option<&(dyn LoadCallback + Sync)>,
+) -> anyhow::Result<()> {
+    let RenderOptions {
+        crate_flags,
+        history,
+        rerender_files,
+        triage,
+    } = options;
+
+    // Incremental rendering is only possible if the website has already been rendered
+    let rerender_files = rerender_files.filter(|_| output_dir.join(INDEX_FILE_PATH).is_file());
+    if rerender_files.is_none() {
+        let _ = std::fs::remove_dir_all(output_dir);
+    }
     std::fs::create_dir_all(output_dir).context("Cannot create output directory")?;
 
     // Copy all static assets to the output directory
@@ -96,25 +314,76 @@ pub fn render_remarks(
     let remark_entries = remarks
         .iter()
         .map(|r| {
+            let RenderedRemark {
+                remark,
+                change,
+                old_hotness,
+                configurations,
+                partial,
+            } = r;
             let Remark {
                 pass: _,
                 name,
                 function,
                 message,
                 hotness,
-            } = r;
-
+                origin,
+                fingerprint,
+                suppressed,
+            } = remark;
+
+            let change = change.map(|change| change.as_str());
+            let triage_entry = triage.get(remark);
+            let triage_status = triage_entry.map(|entry| entry.status.as_str());
+            // Locations of removed remarks refer to the old sources, which are not rendered
+            let removed = r.change == Some(RemarkChange::Removed);
             let entry = RemarkIndexEntry {
                 name,
                 location: function.location.as_ref().map(|location| {
                     let mut buffer = String::new();
-                    render_remark_link(&mut buffer, location, Some(SRC_DIR_NAME), None);
+                    if removed {
+                        encode_safe_to_string(
+                            format!("{}:{}:{}", location.file, location.line, location.column),
+                            &mut buffer,
+                        );
+                    } else {
+                        render_remark_link(&mut buffer, location, Some(SRC_DIR_NAME), None);
+                    }
                     buffer
                 }),
                 function: encode_safe(&function.name),
-                message: format_message(message, Some(SRC_DIR_NAME)),
+                message: if removed {
+                    format_message_text(message)
+                } else {
+                    format_message(message, Some(SRC_DIR_NAME))
+                },
                 hotness: *hotness,
+                old_hotness: *old_hotness,
+                crate_name: origin
+                    .as_ref()
+                    .and_then(|origin| origin.crate_name.as_deref()),
+                stage: origin
+                    .as_ref()
+                    .and_then(|origin| origin.stage)
+                    .map(|stage| stage.as_str()),
+                lto: origin.as_ref().map(|origin| origin.lto).unwrap_or(false),
+                change,
+                configurations: configurations.as_ref().map(|names| names.join(", ")),
+                partial: *partial,
+                fingerprint: fingerprint.to_string(),
+                triage: triage_status,
+                triage_note: triage_entry.and_then(|entry| entry.note.as_deref()),
             };
+            if removed {
+                return entry;
+            }
+            if *suppressed {
+                // Suppressed remarks are hidden from source files, but their links should work
+                if let Some(ref location) = function.location {
+                    file_to_remarks.entry(&location.file).or_default();
+                }
+                return entry;
+            }
             if let Some(ref location) = function.location {
                 file_to_remarks
                     .entry(&location.file)
@@ -126,11 +395,14 @@ pub fn render_remarks(
                         // Inside the file, the link should be relative to the src directory
                         message: format_message(message, None),
                         hotness: *hotness,
+                        change,
+                        fingerprint: fingerprint.to_string(),
+                        triage: triage_status,
                     });
             }
             // We also need to create file mappings for all referenced files, not just for files
             // with a remark.
-            for msg_part in &r.message {
+            for msg_part in message {
                 if let MessagePart::AnnotatedString { location, .. } = msg_part {
                     file_to_remarks.entry(&location.file).or_default();
                 }
@@ -139,11 +411,83 @@ pub fn render_remarks(
         })
         .collect::<Vec<_>>();
 
-    let serialized_remarks = serde_json::to_string(&remark_entries)?;
-    let remark_list_page = RemarkListTemplate {
-        remarks_json: serialized_remarks,
+    let diagnostics = remarks.iter().any(|r| {
+        r.remark
+            .origin
+            .as_ref()
+            .is_some_and(|origin| origin.stage == Some(RemarkStage::Diagnostic))
+    });
+
+    let (remark_entries, suppressed_entries): (Vec<_>, Vec<_>) = remark_entries
+        .into_iter()
+        .zip(&remarks)
+        .partition(|(_, r)| !r.remark.suppressed);
+
+    let mut summary_counts = match mode {
+        RenderMode::Remarks => vec![],
+        RenderMode::Diff => RemarkChange::ALL
+            .iter()
+            .map(|change| SummaryCount {
+                label: change.as_str().to_string(),
+                count: remarks
+                    .iter()
+                    .filter(|r| r.change == Some(*change) && !r.remark.suppressed)
+                    .count(),
+            })
+            .collect(),
+        RenderMode::Matrix {
+            ref configurations,
+            ref counts,
+        } => configurations
+            .iter()
+            .zip(counts)
+            .map(|(name, count)| SummaryCount {
+                label: name.clone(),
+                count: *count,
+            })
+            .chain(std::iter::once(SummaryCount {
+                label: "only in some configurations".to_string(),
+                count: remarks
+                    .iter()
+                    .filter(|r| r.partial && !r.remark.suppressed)
+                    .count(),
+            }))
+            .collect(),
     };
-    render_to_file(&remark_list_page, &output_dir.join(REMARK_LIST_FILE_PATH))?;
+    let exported: Vec<ExportedRemark> = remarks
+        .iter()
+        .map(|r| ExportedRemark {
+            remark: &r.remark,
+            change: r.change.map(|change| change.as_str()),
+            old_hotness: r.change.and(r.old_hotness),
+            configurations: r.configurations.as_deref(),
+        })
+        .collect();
+    let export_path = output_dir.join(REMARK_EXPORT_FILE_PATH);
+    let export_file = File::create(&export_path)
+        .with_context(|| format!("Cannot create {}", export_path.display()))?;
+    serde_json::to_writer(BufWriter::new(export_file), &exported)
+        .context("Cannot export remarks")?;
+
+    for (entries, suppressed, path) in [
+        (remark_entries, false, REMARK_LIST_FILE_PATH),
+        (suppressed_entries, true, SUPPRESSED_LIST_FILE_PATH),
+    ] {
+        let entries: Vec<RemarkIndexEntry> = entries.into_iter().map(|(entry, _)| entry).collect();
+        let remark_list_page = RemarkListTemplate {
+            remarks_json: serde_json::to_string(&entries)?,
+            diff: matches!(mode, RenderMode::Diff),
+            matrix: matches!(mode, RenderMode::Matrix { .. }),
+            suppressed,
+            diagnostics,
+            summary_counts: if suppressed {
+                vec![]
+            } else {
+                std::mem::take(&mut summary_counts)
+            },
+        };
+        render_to_file(&remark_list_page, &output_dir.join(path))?;
+    }
 
     let mut source_links: Vec<SourceFileLink> = file_to_remarks
         .iter()
@@ -162,9 +506,40 @@ pub fn render_remarks(
     // Sort by relative files first, then in descending order by remark count
     source_links.sort_by_key(|link| (link.name.starts_with('/'), -(link.remark_count as i64)));
 
-    let index_page = IndexTemplate { source_links };
+    let index_page = IndexTemplate {
+        source_links,
+        crate_flags,
+        diagnostics,
+    };
     render_to_file(&index_page, &output_dir.join(INDEX_FILE_PATH))?;
 
+    let trend_entries: Vec<TrendEntry> = history
+        .iter()
+        .map(|entry| TrendEntry {
+            run: &entry.run,
+            date: &entry.date,
+            git_commit: entry.git_commit.as_deref(),
+            summary: &entry.summary,
+        })
+        .collect();
+    let trends_page = TrendsTemplate {
+        run_count: trend_entries.len(),
+        history_json: serde_json::to_string(&trend_entries)?,
+    };
+    render_to_file(&trends_page, &output_dir.join(TRENDS_FILE_PATH))?;
+
+    if let Some(ref files) = rerender_files {
+        // Pages of files that no longer contain remarks are removed
+        for file in files {
+            if !file_to_remarks.contains_key(file.as_str()) {
+                let _ = std::fs::remove_file(source_page_path(output_dir, file));
+            }
+        }
+        file_to_remarks.retain(|file, _| {
+            files.contains(*file) || !source_page_path(output_dir, file).is_file()
+        });
+    }
+
     if let Some(callback) = callback {
         callback.start(file_to_remarks.len() as u64);
     }
@@ -182,15 +557,13 @@ pub fn render_remarks(
             }
 
             // TODO: deduplicate links to "self" (the same source file)
-            let mut buffer = String::new();
-            path_to_relative_url(&mut buffer, Some(SRC_DIR_NAME), source_file);
-            let output_path = output_dir.join(buffer);
+            let output_path = source_page_path(output_dir, source_file);
             let source_file_page = SourceFileTemplate {
                 path: source_file,
                 remarks,
                 file_content,
             };
-            render_to_file(&source_file_page, Path::new(&output_path))
+            render_to_file(&source_file_page, &output_path)
                 .with_context(|| anyhow::anyhow!("Failed to render {source_file}"))?;
             Ok(())
         })
@@ -208,6 +581,13 @@ pub fn render_remarks(
     Ok(())
 }
 
+/// Returns the path of the rendered page of `source_file`.
+fn source_page_path(output_dir: &Path, source_file: &str) -> PathBuf {
+    let mut buffer = String::new();
+    path_to_relative_url(&mut buffer, Some(SRC_DIR_NAME), source_file);
+    output_dir.join(buffer)
+}
+
 fn format_message(parts: &[MessagePart], prefix: Option<&str>) -> String {
     let mut buffer = String::with_capacity(32);
     for part in parts {
@@ -223,6 +603,20 @@ fn format_message(parts: &[MessagePart], prefix: Option<&str>) -> String {
     buffer
 }
 
+/// Formats the message without links to source files.
+fn format_message_text(parts: &[MessagePart]) -> String {
+    let mut buffer = String::with_capacity(32);
+    for part in parts {
+        match part {
+            MessagePart::String(string) => encode_safe_to_string(string, &mut buffer),
+            MessagePart::AnnotatedString { message, .. } => {
+                encode_safe_to_string(message, &mut buffer)
+            }
+        };
+    }
+    buffer
+}
+
 fn render_remark_link(
     buffer: &mut String,
     location: &Location,
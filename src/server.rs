@@ -0,0 +1,522 @@
+//! Local HTTP server of a rendered website.
+//!
+//! Besides the static files of the website, the server exposes a JSON API over the loaded
+//! remarks (under `/api`). When the report is regenerated, its remarks are loaded again and open
+//! pages are reloaded.
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::history::RemarkSummary;
+use crate::query::{RemarkQuery, RemarkSort};
+use crate::remark::{Fingerprint, Remark};
+use crate::render::INDEX_FILE_PATH;
+use crate::triage::{Triage, TriageStatus};
+
+/// Script injected into served HTML pages, which reloads the page when the report changes.
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>
+(function () {
+    let version = null;
+    setInterval(async () => {
+        try {
+            const response = await fetch("/api/version");
+            const current = (await response.json()).version;
+            if (version !== null && current !== version) {
+                location.reload();
+            }
+            version = current;
+        } catch (e) {}
+    }, 1000);
+})();
+</script>"#;
+
+/// How often is the report checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Rendered website together with the remarks that it contains.
+pub struct Report {
+    pub web_dir: PathBuf,
+    pub remarks: Vec<Remark>,
+    /// Triage file of the remarks, which can be updated through the API.
+    pub triage_file: Option<PathBuf>,
+}
+
+/// Request that changes the triage state of a remark.
+#[derive(serde::Deserialize)]
+struct TriageUpdate {
+    fingerprint: Fingerprint,
+    /// The remark is removed from the triage state if the status is missing.
+    status: Option<TriageStatus>,
+    note: Option<String>,
+}
+
+/// Provides the served report.
+pub trait ReportSource: Send + 'static {
+    /// Returns a value that changes whenever the report is regenerated.
+    fn revision(&self) -> anyhow::Result<String>;
+
+    fn load(&self) -> anyhow::Result<Report>;
+}
+
+struct ServerState {
+    report: Report,
+    /// Incremented whenever the report is reloaded.
+    version: u64,
+}
+
+/// Serves the report of `source` on `address` until the process is terminated.
+/// `on_start` is called with the URL of the server once it is listening.
+pub fn serve<S: ReportSource>(
+    source: S,
+    address: &str,
+    on_start: impl FnOnce(&str),
+) -> anyhow::Result<()> {
+    let revision = source.revision()?;
+    let state = Arc::new(RwLock::new(ServerState {
+        report: source.load()?,
+        version: 0,
+    }));
+
+    let server = Server::http(address)
+        .map_err(|error| anyhow::anyhow!("Cannot listen on {address}: {error}"))?;
+    let (url, hosts) = match server.server_addr().to_ip() {
+        Some(address) => (
+            format!("http://{address}"),
+            vec![address.to_string(), format!("localhost:{}", address.port())],
+        ),
+        None => (format!("http://{address}"), vec![address.to_string()]),
+    };
+
+    let reload_state = state.clone();
+    std::thread::spawn(move || watch_report(source, revision, reload_state));
+
+    on_start(&url);
+    for request in server.incoming_requests() {
+        let state = state.read().unwrap();
+        if let Err(error) = handle_request(request, &state, &hosts) {
+            log::warn!("Cannot respond to request: {error:?}");
+        }
+    }
+    Ok(())
+}
+
+/// Reloads the report whenever its revision changes.
+fn watch_report<S: ReportSource>(source: S, mut revision: String, state: Arc<RwLock<ServerState>>) {
+    loop {
+        std::thread::sleep(RELOAD_INTERVAL);
+        let current = match source.revision() {
+            Ok(current) => current,
+            Err(error) => {
+                log::debug!("Cannot find report revision: {error:?}");
+                continue;
+            }
+        };
+        if current == revision {
+            continue;
+        }
+        match source.load() {
+            Ok(report) => {
+                let mut state = state.write().unwrap();
+                state.report = report;
+                state.version += 1;
+                revision = current;
+                log::info!("Report reloaded ({} remark(s))", state.report.remarks.len());
+            }
+            Err(error) => log::warn!("Cannot reload report: {error:?}"),
+        }
+    }
+}
+
+/// Handles a single request. `hosts` are the addresses under which the server is reachable.
+fn handle_request(
+    mut request: Request,
+    state: &ServerState,
+    hosts: &[String],
+) -> anyhow::Result<()> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let path = decode_component(path);
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv(name))
+            .map(|header| header.value.as_str().to_string())
+    };
+
+    if let Err(error) = check_host(header("Host").as_deref(), hosts) {
+        let response = json_response(403, &json!({ "error": error }));
+        return Ok(request.respond(response)?);
+    }
+    if *request.method() == Method::Post && path == "/api/triage" {
+        if let Err(error) = check_local_request(
+            header("Content-Type").as_deref(),
+            header("Origin").as_deref(),
+            hosts,
+        ) {
+            let response = json_response(403, &json!({ "error": error }));
+            return Ok(request.respond(response)?);
+        }
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body)?;
+        let response = match update_triage(&body, state) {
+            Ok(value) => json_response(200, &value),
+            Err(error) => json_response(400, &json!({ "error": format!("{error:#}") })),
+        };
+        return Ok(request.respond(response)?);
+    }
+    if *request.method() != Method::Get {
+        return Ok(request.respond(Response::empty(405))?);
+    }
+
+    let response = match path.strip_prefix("/api/") {
+        Some(endpoint) => match handle_api(endpoint, &parse_query(query), state) {
+            Ok(Some(value)) => json_response(200, &value),
+            Ok(None) => json_response(404, &json!({ "error": "Unknown endpoint" })),
+            Err(error) => json_response(400, &json!({ "error": format!("{error:#}") })),
+        },
+        None => static_response(&state.report.web_dir, &path)?,
+    };
+    Ok(request.respond(response)?)
+}
+
+/// Handles a request to the JSON API. Returns `None` if the endpoint does not exist.
+fn handle_api(
+    endpoint: &str,
+    query: &[(String, String)],
+    state: &ServerState,
+) -> anyhow::Result<Option<serde_json::Value>> {
+    let remarks = &state.report.remarks;
+    let value = match endpoint {
+        "version" => json!({ "version": state.version }),
+        "stats" => serde_json::to_value(RemarkSummary::from_remarks(remarks))?,
+        "remarks" => query_remarks(remarks, query)?,
+        "triage" => serde_json::to_value(Triage::load(triage_file(state)?)?.remarks)?,
+        "files" => {
+            let summary = RemarkSummary::from_remarks(remarks);
+            let files: Vec<_> = summary
+                .files
+                .into_iter()
+                .map(|(file, counts)| {
+                    json!({ "file": file, "count": counts.count, "hotness": counts.hotness })
+                })
+                .collect();
+            json!(files)
+        }
+        endpoint => match endpoint.strip_prefix("files/") {
+            Some(file) => {
+                let query = RemarkQuery {
+                    file: Some(file.to_string()),
+                    sort: RemarkSort::Location,
+                    ..Default::default()
+                };
+                serde_json::to_value(query.apply(remarks).remarks)?
+            }
+            None => return Ok(None),
+        },
+    };
+    Ok(Some(value))
+}
+
+/// Checks that a request was sent to one of the addresses of the server, so that pages which
+/// reach the server through a different host name (DNS rebinding) cannot read the remarks.
+fn check_host(host: Option<&str>, hosts: &[String]) -> Result<(), String> {
+    if !host.is_some_and(|host| hosts.iter().any(|allowed| allowed == host)) {
+        return Err("The request has an unknown `Host`".to_string());
+    }
+    Ok(())
+}
+
+/// Checks that a request which modifies files was sent by a page served by this server.
+///
+/// Requiring a JSON content type makes browsers send a CORS preflight for cross-origin requests,
+/// which the server never answers. The `Origin` check also rejects requests from other pages
+/// that are sent without a preflight.
+fn check_local_request(
+    content_type: Option<&str>,
+    origin: Option<&str>,
+    hosts: &[String],
+) -> Result<(), String> {
+    let is_json = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+    if !is_json {
+        return Err("The request has to use `Content-Type: application/json`".to_string());
+    }
+    if let Some(origin) = origin {
+        let local = origin
+            .strip_prefix("http://")
+            .is_some_and(|origin| hosts.iter().any(|allowed| allowed == origin));
+        if !local {
+            return Err(format!("Requests from `{origin}` are not allowed"));
+        }
+    }
+    Ok(())
+}
+
+/// Changes the triage state of a remark, and returns its new state.
+fn update_triage(body: &str, state: &ServerState) -> anyhow::Result<serde_json::Value> {
+    let update: TriageUpdate = serde_json::from_str(body).context("Invalid triage request")?;
+    let remark = state
+        .report
+        .remarks
+        .iter()
+        .find(|remark| remark.fingerprint == update.fingerprint)
+        .ok_or_else(|| anyhow::anyhow!("Remark {} not found", update.fingerprint))?;
+
+    let path = triage_file(state)?;
+    let mut triage = Triage::load(path)?;
+    triage.set(remark, update.status, update.note);
+    triage.store(path)?;
+    Ok(serde_json::to_value(triage.get(remark))?)
+}
+
+fn triage_file(state: &ServerState) -> anyhow::Result<&Path> {
+    state
+        .report
+        .triage_file
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Triage is not available for this report"))
+}
+
+/// Lists remarks that match the query.
+///
+/// Supported parameters are `filter` (a filter expression), `file`, `sort` (`hotness`,
+/// `location`, `kind` or `function`), `offset`
+/// and `limit`.
+fn query_remarks(
+    remarks: &[Remark],
+    query: &[(String, String)],
+) -> anyhow::Result<serde_json::Value> {
+    let mut remark_query = RemarkQuery::default();
+    for (key, value) in query {
+        match key.as_str() {
+            "filter" => remark_query.filters.push(value.parse()?),
+            "file" => remark_query.file = Some(value.clone()),
+            "sort" => {
+                remark_query.sort = RemarkSort::from_str(value, false).map_err(|_| {
+                    anyhow::anyhow!(
+                        "Unknown sort order `{value}`, expected `hotness`, `location`, `kind` or `function`"
+                    )
+                })?
+            }
+            "offset" => remark_query.offset = value.parse().context("Invalid offset")?,
+            "limit" => remark_query.limit = Some(value.parse().context("Invalid limit")?),
+            _ => return Err(anyhow::anyhow!("Unknown parameter `{key}`")),
+        }
+    }
+    Ok(serde_json::to_value(remark_query.apply(remarks))?)
+}
+
+fn json_response(status: u16, value: &serde_json::Value) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(value.to_string())
+        .with_status_code(status)
+        .with_header(content_type("application/json"))
+}
+
+/// Serves a file of the website. Live reload is injected into HTML pages.
+fn static_response(web_dir: &Path, path: &str) -> anyhow::Result<Response<Cursor<Vec<u8>>>> {
+    let relative = Path::new(path.trim_start_matches('/'));
+    // Only files inside the website are served
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Ok(Response::from_string("Not found").with_status_code(404));
+    }
+    let mut file_path = web_dir.join(relative);
+    if path.ends_with('/') || file_path.is_dir() {
+        file_path = file_path.join(INDEX_FILE_PATH);
+    }
+    let Ok(mut data) = std::fs::read(&file_path) else {
+        return Ok(Response::from_string("Not found").with_status_code(404));
+    };
+
+    let extension = file_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mime = match extension.as_str() {
+        "html" => {
+            let html = String::from_utf8_lossy(&data);
+            data = match html.rfind("</body>") {
+                Some(index) => format!("{}{LIVE_RELOAD_SCRIPT}{}", &html[..index], &html[index..]),
+                None => format!("{html}{LIVE_RELOAD_SCRIPT}"),
+            }
+            .into_bytes();
+            "text/html; charset=utf-8"
+        }
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        _ => "application/octet-stream",
+    };
+    Ok(Response::from_data(data).with_header(content_type(mime)))
+}
+
+fn content_type(mime: &str) -> Header {
+    Header::from_bytes(&b"Content-Type"[..], mime.as_bytes()).expect("Invalid header")
+}
+
+/// Parses the query string of an URL into decoded key-value pairs.
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (
+                decode_component(&key.replace('+', " ")),
+                decode_component(&value.replace('+', " ")),
+            )
+        })
+        .collect()
+}
+
+/// Decodes percent-encoded characters of an URL component.
+fn decode_component(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if byte == b'%' {
+            let hex = bytes
+                .get(index + 1..index + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = hex {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(byte);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::remark::{Remark, RemarkBuilder};
+    use crate::server::{
+        check_host, check_local_request, handle_api, parse_query, update_triage, Report,
+        ServerState,
+    };
+
+    #[test]
+    fn parse_query_string() {
+        assert_eq!(
+            parse_query("filter=pass+%3D%3D+%22gvn%22&limit=5&file=src%2Fmain.rs"),
+            vec![
+                ("filter".to_string(), "pass == \"gvn\"".to_string()),
+                ("limit".to_string(), "5".to_string()),
+                ("file".to_string(), "src/main.rs".to_string()),
+            ]
+        );
+        assert!(parse_query("").is_empty());
+    }
+
+    #[test]
+    fn reject_foreign_requests() {
+        let hosts = vec!["127.0.0.1:8000".to_string(), "localhost:8000".to_string()];
+        let json = Some("application/json");
+        assert!(check_local_request(json, None, &hosts).is_ok());
+        assert!(check_local_request(
+            Some("application/json; charset=utf-8"),
+            Some("http://127.0.0.1:8000"),
+            &hosts
+        )
+        .is_ok());
+
+        // Simple cross-origin requests
+        assert!(check_local_request(None, None, &hosts).is_err());
+        assert!(check_local_request(Some("text/plain"), None, &hosts).is_err());
+        assert!(check_local_request(json, Some("https://example.com"), &hosts).is_err());
+
+        assert!(check_host(Some("localhost:8000"), &hosts).is_ok());
+        assert!(check_host(Some("127.0.0.1:8000"), &hosts).is_ok());
+        // DNS rebinding
+        assert!(check_host(Some("attacker.com:8000"), &hosts).is_err());
+        assert!(check_host(None, &hosts).is_err());
+    }
+
+    #[test]
+    fn query_api() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = ServerState {
+            report: Report {
+                web_dir: PathBuf::new(),
+                triage_file: Some(dir.path().join("remark-triage.toml")),
+                remarks: vec![
+                    remark("inline", "src/main.rs", 3, None),
+                    remark("gvn", "src/lib.rs", 10, Some(5)),
+                    remark("gvn", "src/main.rs", 1, Some(20)),
+                ],
+            },
+            version: 2,
+        };
+        let query = |endpoint: &str, query: &str| {
+            handle_api(endpoint, &parse_query(query), &state)
+                .unwrap()
+                .unwrap()
+        };
+
+        assert_eq!(query("version", "")["version"], 2);
+        assert_eq!(query("stats", "")["total"]["count"], 3);
+
+        let remarks = query(
+            "remarks",
+            "filter=pass+%3D%3D+%22gvn%22&sort=hotness&limit=1",
+        );
+        assert_eq!(remarks["total"], 2);
+        assert_eq!(remarks["remarks"].as_array().unwrap().len(), 1);
+        assert_eq!(remarks["remarks"][0]["hotness"], 20);
+
+        let files = query("files", "");
+        assert_eq!(files[0]["file"], "src/lib.rs");
+        assert_eq!(files[1]["count"], 2);
+
+        let remarks = query("files/src/main.rs", "");
+        assert_eq!(remarks[0]["function"]["location"]["line"], 1);
+        assert_eq!(remarks[1]["pass"], "inline");
+
+        assert!(handle_api("remarks", &parse_query("limit=x"), &state).is_err());
+
+        let fingerprint = state.report.remarks[1].fingerprint;
+        let entry = update_triage(
+            &format!(
+                r#"{{"fingerprint": "{fingerprint}", "status": "todo", "note": "vectorize"}}"#
+            ),
+            &state,
+        )
+        .unwrap();
+        assert_eq!(entry["status"], "todo");
+        let triage = query("triage", "");
+        assert_eq!(triage[fingerprint.to_string()]["note"], "vectorize");
+        update_triage(&format!(r#"{{"fingerprint": "{fingerprint}"}}"#), &state).unwrap();
+        assert_eq!(query("triage", ""), serde_json::json!({}));
+        assert!(update_triage(r#"{"fingerprint": "ff", "status": "todo"}"#, &state).is_err());
+        assert!(handle_api("unknown", &[], &state).unwrap().is_none());
+    }
+
+    fn remark(pass: &str, file: &str, line: u32, hotness: Option<i32>) -> Remark {
+        RemarkBuilder::new(pass, "Missed")
+            .location(file, line, 1)
+            .message("missed")
+            .hotness(hotness)
+            .fingerprint(&format!("{line:x}"))
+            .build()
+    }
+}
@@ -0,0 +1,107 @@
+//! Plain text tables with aligned columns, used to list remarks in the terminal.
+//!
+//! ```text
+//! Location          Remark             Hotness  Function  Message
+//! src/main.rs:7:13  gvn/LoadClobbered       20  main      load of type i64 not eliminated
+//! ```
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// Table whose columns are padded to the width of their widest cell.
+/// The last column is not padded, so that long values (e.g. messages) do not produce trailing
+/// whitespace.
+pub struct Table {
+    header: Vec<(String, Align)>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(header: &[(&str, Align)]) -> Self {
+        Self {
+            header: header
+                .iter()
+                .map(|(title, align)| (title.to_string(), *align))
+                .collect(),
+            rows: vec![],
+        }
+    }
+
+    /// Adds a row with one cell per column.
+    pub fn push_row(&mut self, row: Vec<String>) {
+        assert_eq!(row.len(), self.header.len());
+        self.rows.push(row);
+    }
+
+    pub fn format_header(&self) -> String {
+        let titles: Vec<String> = self.header.iter().map(|(title, _)| title.clone()).collect();
+        self.format_line(&titles, &self.widths())
+    }
+
+    pub fn format_rows(&self) -> Vec<String> {
+        let widths = self.widths();
+        self.rows
+            .iter()
+            .map(|row| self.format_line(row, &widths))
+            .collect()
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        (0..self.header.len())
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .map(|row| &row[column])
+                    .chain([&self.header[column].0])
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    fn format_line(&self, cells: &[String], widths: &[usize]) -> String {
+        let last = cells.len().saturating_sub(1);
+        cells
+            .iter()
+            .zip(widths)
+            .zip(&self.header)
+            .enumerate()
+            .map(|(column, ((cell, &width), (_, align)))| match align {
+                _ if column == last => cell.clone(),
+                Align::Left => format!("{cell:<width$}"),
+                Align::Right => format!("{cell:>width$}"),
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::table::{Align, Table};
+
+    #[test]
+    fn align_columns() {
+        let mut table = Table::new(&[
+            ("Name", Align::Left),
+            ("Count", Align::Right),
+            ("Note", Align::Left),
+        ]);
+        table.push_row(vec!["foo".to_string(), "7".to_string(), "x".to_string()]);
+        table.push_row(vec![
+            "barbaz".to_string(),
+            "123456".to_string(),
+            "longer note".to_string(),
+        ]);
+
+        assert_eq!(table.format_header(), "Name     Count  Note");
+        assert_eq!(
+            table.format_rows(),
+            vec!["foo          7  x", "barbaz  123456  longer note"]
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//! Formatting of remarks as rustc-style diagnostics, with source snippets.
+//!
+//! ```text
+//! note[gvn::LoadClobbered]: load of type i64 not eliminated because it is clobbered by call
+//!  --> src/main.rs:7:13
+//!   |
+//! 6 |     foo(&mut x);
+//!   |     - call
+//! 7 |     let y = x;
+//!   |             ^
+//!   = function: crate::main
+//! ```
+use colored::Colorize;
+
+use crate::query::message_text;
+use crate::remark::{Line, Location, MessagePart, Remark};
+use crate::utils::source::SourceCache;
+
+/// Location in the snippet, marked with `^` (primary) or `-` (secondary), optionally with a label.
+struct Annotation<'a> {
+    location: &'a Location,
+    label: Option<&'a str>,
+    primary: bool,
+}
+
+/// Formats `remark` as a rustc-style diagnostic. Message parts annotated with a source location
+/// become secondary labels of the snippet.
+pub fn format_diagnostic(remark: &Remark, sources: &mut SourceCache) -> String {
+    let message = message_text(remark);
+    let mut output = format!(
+        "{}{}\n",
+        format!("note[{}::{}]", remark.pass, remark.name)
+            .green()
+            .bold(),
+        format!(": {message}").bold()
+    );
+
+    let mut annotations = vec![];
+    if let Some(ref location) = remark.function.location {
+        annotations.push(Annotation {
+            location,
+            label: None,
+            primary: true,
+        });
+    }
+    let mut notes = vec![];
+    for part in &remark.message {
+        let MessagePart::AnnotatedString { message, location } = part else {
+            continue;
+        };
+        if sources.line(&location.file, location.line).is_none() {
+            notes.push(format!(
+                "{message} at {}:{}:{}",
+                location.file, location.line, location.column
+            ));
+        } else if let Some(primary) = annotations
+            .iter_mut()
+            .find(|annotation| annotation.primary && annotation.location == location)
+        {
+            primary.label = Some(message);
+        } else {
+            annotations.push(Annotation {
+                location,
+                label: Some(message),
+                primary: false,
+            });
+        }
+    }
+
+    let width = annotations
+        .iter()
+        .map(|annotation| annotation.location.line.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let gutter = " ".repeat(width);
+    let bar = "|".blue().bold();
+
+    // The primary file is printed first, followed by the other annotated files
+    let mut files: Vec<&str> = vec![];
+    for annotation in &annotations {
+        if !files.contains(&annotation.location.file.as_str()) {
+            files.push(&annotation.location.file);
+        }
+    }
+    for (index, file) in files.into_iter().enumerate() {
+        let mut file_annotations: Vec<&Annotation> = annotations
+            .iter()
+            .filter(|annotation| annotation.location.file == file)
+            .collect();
+        file_annotations.sort_by_key(|annotation| {
+            (
+                annotation.location.line,
+                annotation.location.column,
+                !annotation.primary,
+            )
+        });
+        let first = file_annotations
+            .iter()
+            .find(|annotation| annotation.primary)
+            .unwrap_or(&file_annotations[0])
+            .location;
+        let arrow = if index == 0 { "-->" } else { ":::" };
+        output.push_str(&format!(
+            "{gutter}{} {}:{}:{}\n",
+            arrow.blue().bold(),
+            first.file,
+            first.line,
+            first.column
+        ));
+        if sources.line(file, first.line).is_none() {
+            continue;
+        }
+        output.push_str(&format!("{gutter} {bar}\n"));
+
+        let mut previous_line: Option<Line> = None;
+        for annotation in file_annotations {
+            let location = annotation.location;
+            let code = sources.line(file, location.line).unwrap_or_default();
+            if previous_line != Some(location.line) {
+                if previous_line.is_some_and(|previous| location.line > previous + 1) {
+                    output.push_str(&format!("{}\n", "...".blue().bold()));
+                }
+                output.push_str(&format!(
+                    "{} {bar} {code}\n",
+                    format!("{:>width$}", location.line).blue().bold()
+                ));
+                previous_line = Some(location.line);
+            }
+            let mut marker = if annotation.primary { "^" } else { "-" }.to_string();
+            if let Some(label) = annotation.label {
+                marker = format!("{marker} {label}");
+            }
+            let marker = if annotation.primary {
+                marker.yellow().bold()
+            } else {
+                marker.blue().bold()
+            };
+            let padding = marker_padding(code, location.column);
+            output.push_str(&format!("{gutter} {bar} {padding}{marker}\n"));
+        }
+    }
+
+    output.push_str(&format!(
+        "{gutter} {} function: {}\n",
+        "=".blue().bold(),
+        remark.function.name
+    ));
+    if let Some(hotness) = remark.hotness {
+        output.push_str(&format!(
+            "{gutter} {} hotness: {hotness}\n",
+            "=".blue().bold()
+        ));
+    }
+    for note in notes {
+        output.push_str(&format!("{gutter} {} note: {note}\n", "=".blue().bold()));
+    }
+    output
+}
+
+/// Returns whitespace that aligns a marker below the 1-based `column` of `code`. Tabs are kept,
+/// so that the marker is aligned in the terminal. If the column is unknown (`0`), the marker is
+/// placed below the first non-whitespace character.
+fn marker_padding(code: &str, column: u32) -> String {
+    let offset = match column {
+        0 => code.chars().take_while(|c| c.is_whitespace()).count(),
+        column => column as usize - 1,
+    };
+    code.chars()
+        .chain(std::iter::repeat(' '))
+        .take(offset)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use crate::remark::{Location, MessagePart, RemarkBuilder};
+    use crate::terminal::{format_diagnostic, marker_padding, SourceCache};
+
+    #[test]
+    fn format_remark() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/main.rs"),
+            "fn main() {\n    foo(&mut x);\n\n    let y = x;\n}\n",
+        )
+        .unwrap();
+
+        let remark = RemarkBuilder::new("gvn", "LoadClobbered")
+            .function("crate::main")
+            .location("src/main.rs", 4, 13)
+            .message_parts(vec![
+                MessagePart::String(
+                    "load of type i64 not eliminated because it is clobbered by ".to_string(),
+                ),
+                MessagePart::AnnotatedString {
+                    message: "call".to_string(),
+                    location: location(2, 5),
+                },
+                MessagePart::String(" to ".to_string()),
+                MessagePart::AnnotatedString {
+                    message: "foo".to_string(),
+                    location: Location {
+                        file: "/rustc/library/foo.rs".to_string(),
+                        line: 1,
+                        column: 1,
+                    },
+                },
+            ])
+            .hotness(Some(100))
+            .build();
+        let mut sources = SourceCache::new(dir.path());
+        assert_eq!(
+            strip_colors(&format_diagnostic(&remark, &mut sources)),
+            r#"note[gvn::LoadClobbered]: load of type i64 not eliminated because it is clobbered by call to foo
+ --> src/main.rs:4:13
+  |
+2 |     foo(&mut x);
+  |     - call
+...
+4 |     let y = x;
+  |             ^
+  = function: crate::main
+  = hotness: 100
+  = note: foo at /rustc/library/foo.rs:1:1
+"#
+        );
+    }
+
+    #[test]
+    fn padding() {
+        assert_eq!(marker_padding("    foo();", 5), "    ");
+        assert_eq!(marker_padding("\tfoo();", 2), "\t");
+        assert_eq!(marker_padding("  foo();", 0), "  ");
+        assert_eq!(marker_padding("x", 4), "   ");
+    }
+
+    /// Removes ANSI color codes, which are only emitted if the output is a terminal.
+    fn strip_colors(text: &str) -> String {
+        Regex::new("\x1b\\[[0-9;]*m")
+            .unwrap()
+            .replace_all(text, "")
+            .into_owned()
+    }
+
+    fn location(line: u32, column: u32) -> Location {
+        Location {
+            file: "src/main.rs".to_string(),
+            line,
+            column,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! Triage state of remarks, stored in `remark-triage.toml` in the workspace root.
+//!
+//! Remarks are identified by their fingerprints, so the state is kept when a remark moves to a
+//! different line. The remark kind, function and file are only stored to make the file readable.
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::remark::{Fingerprint, Remark};
+
+pub const TRIAGE_FILE_NAME: &str = "remark-triage.toml";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum TriageStatus {
+    /// The remark was looked at, and it does not need any action.
+    Reviewed,
+    /// The remark was looked at, and it will not be resolved.
+    WontFix,
+    /// The remark should be resolved.
+    Todo,
+}
+
+impl TriageStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TriageStatus::Reviewed => "reviewed",
+            TriageStatus::WontFix => "wont-fix",
+            TriageStatus::Todo => "todo",
+        }
+    }
+}
+
+impl Display for TriageStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Triage state of a single remark.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TriageEntry {
+    pub status: TriageStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub kind: String,
+    pub function: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Triage state of all triaged remarks.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Triage {
+    #[serde(default)]
+    pub remarks: BTreeMap<Fingerprint, TriageEntry>,
+}
+
+impl Triage {
+    /// Loads the triage file at `path`. A missing file contains no triaged remarks.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(error) => {
+                return Err(error).with_context(|| format!("Cannot read {}", path.display()))
+            }
+        };
+        toml::from_str(&content).with_context(|| format!("Cannot parse {}", path.display()))
+    }
+
+    pub fn store(&self, path: &Path) -> anyhow::Result<()> {
+        let content = toml::to_string_pretty(self).context("Cannot serialize triage state")?;
+        std::fs::write(path, content).with_context(|| format!("Cannot write {}", path.display()))
+    }
+
+    pub fn get(&self, remark: &Remark) -> Option<&TriageEntry> {
+        self.remarks.get(&remark.fingerprint)
+    }
+
+    /// Sets the status of `remark`, or removes it from the triage state if `status` is `None`.
+    pub fn set(&mut self, remark: &Remark, status: Option<TriageStatus>, note: Option<String>) {
+        match status {
+            Some(status) => {
+                self.remarks.insert(
+                    remark.fingerprint,
+                    TriageEntry {
+                        status,
+                        note,
+                        kind: remark.name.clone(),
+                        function: remark.function.name.clone(),
+                        file: remark
+                            .function
+                            .location
+                            .as_ref()
+                            .map(|location| location.file.clone()),
+                    },
+                );
+            }
+            None => {
+                self.remarks.remove(&remark.fingerprint);
+            }
+        }
+    }
+}
+
+/// Loads the triage state of the workspace in `dir`. If it cannot be loaded, a warning is logged
+/// and no remarks are triaged.
+pub fn load_triage(dir: &Path) -> Triage {
+    match Triage::load(&dir.join(TRIAGE_FILE_NAME)) {
+        Ok(triage) => triage,
+        Err(error) => {
+            log::warn!("Cannot load triage state: {error:?}");
+            Triage::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remark::RemarkBuilder;
+    use crate::triage::{Triage, TriageStatus};
+
+    #[test]
+    fn store_triage() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("remark-triage.toml");
+        assert!(Triage::load(&path).unwrap().remarks.is_empty());
+
+        let first = RemarkBuilder::new("gvn", "LoadClobbered")
+            .fingerprint("1")
+            .build();
+        let second = RemarkBuilder::new("gvn", "LoadClobbered")
+            .fingerprint("2")
+            .build();
+        let mut triage = Triage::default();
+        triage.set(
+            &first,
+            Some(TriageStatus::WontFix),
+            Some("cold".to_string()),
+        );
+        triage.set(&second, Some(TriageStatus::Todo), None);
+        triage.store(&path).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("[remarks.0000000000000001]"));
+        assert!(content.contains("status = \"wont-fix\""));
+
+        let mut loaded = Triage::load(&path).unwrap();
+        assert_eq!(loaded, triage);
+        assert_eq!(loaded.get(&first).unwrap().note.as_deref(), Some("cold"));
+
+        loaded.set(&first, None, None);
+        assert!(loaded.get(&first).is_none());
+        assert_eq!(loaded.get(&second).unwrap().status, TriageStatus::Todo);
+    }
+}
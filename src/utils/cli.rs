@@ -5,3 +5,12 @@ use std::path::Path;
 pub fn cli_format_path<P: AsRef<Path>>(path: P) -> ColoredString {
     path.as_ref().display().to_string().yellow()
 }
+
+/// Returns the value of a `--<flag>`/`--no-<flag>` pair, or `None` if neither flag was passed.
+pub fn flag_value(enabled: bool, disabled: bool) -> Option<bool> {
+    match (enabled, disabled) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
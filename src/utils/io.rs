@@ -11,3 +11,9 @@ pub fn clear_directory(path: &Path) -> std::io::Result<PathBuf> {
     std::fs::remove_dir_all(path)?;
     ensure_directory(path)
 }
+
+/// Formats `path` with `/` separators, which are used by the paths of remark locations on all
+/// platforms.
+pub fn normalize_separators(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
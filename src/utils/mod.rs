@@ -6,6 +6,7 @@ pub mod callback;
 pub mod cli;
 pub mod data_structures;
 pub mod io;
+pub mod source;
 pub mod timing;
 
 pub fn open_result(dir: &Path, open: bool) -> anyhow::Result<()> {
@@ -0,0 +1,45 @@
+use std::path::{Path, PathBuf};
+
+use crate::remark::Line;
+use crate::utils::data_structures::Map;
+
+/// Lines of source files referenced by remarks, loaded lazily from the source directory.
+pub struct SourceCache {
+    source_dir: PathBuf,
+    files: Map<String, Option<Vec<String>>>,
+}
+
+impl SourceCache {
+    pub fn new(source_dir: &Path) -> Self {
+        Self {
+            source_dir: source_dir.to_path_buf(),
+            files: Default::default(),
+        }
+    }
+
+    /// Returns the lines of `file`, or `None` if it cannot be read.
+    /// Relative paths are resolved against the source directory.
+    pub fn lines(&mut self, file: &str) -> Option<&[String]> {
+        let source_dir = &self.source_dir;
+        self.files
+            .entry(file.to_string())
+            .or_insert_with(|| {
+                let path = match Path::new(file) {
+                    path if path.is_absolute() => path.to_path_buf(),
+                    path => source_dir.join(path),
+                };
+                std::fs::read_to_string(path)
+                    .ok()
+                    .map(|content| content.lines().map(|line| line.to_string()).collect())
+            })
+            .as_deref()
+    }
+
+    /// Returns a single (1-based) line of `file` without trailing whitespace, or `None` if it
+    /// cannot be read.
+    pub fn line(&mut self, file: &str, line: Line) -> Option<&str> {
+        self.lines(file)?
+            .get((line as usize).checked_sub(1)?)
+            .map(|line| line.trim_end())
+    }
+}
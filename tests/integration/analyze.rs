@@ -43,3 +43,24 @@ fn create_source_file() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn infer_source_dir() -> anyhow::Result<()> {
+    let data_dir = get_test_data_path("remarks-similarity-join");
+    let dir = tempfile::TempDir::new()?;
+    let output_dir = "output";
+
+    analyze_remarks(
+        dir.path(),
+        &[
+            "--output-dir",
+            output_dir,
+            data_dir.join("yaml").to_str().unwrap(),
+        ],
+    )?
+    .assert_ok();
+    let dir = HTMLDir::new(&dir.path().join(output_dir));
+    dir.check_source("src_main.rs.html");
+
+    Ok(())
+}
@@ -1,5 +1,14 @@
-use crate::utils::{cargo_remark, init_cargo_project, OutputExt};
-use cargo_remark::remark::{load_remarks_from_dir, Location, Remark, RemarkLoadOptions};
+use crate::utils::{
+    analyze_remarks, cargo_remark, cargo_remark_with_default_toolchain, init_cargo_project,
+    spawn_cargo_remark, CargoProject, HTMLDir, OutputExt,
+};
+use cargo_remark::flags::load_crate_flags;
+use cargo_remark::history::load_history;
+use cargo_remark::remark::{
+    load_remarks_from_dir, Location, Remark, RemarkLoadOptions, RemarkStage,
+};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
 use std::path::Path;
 
 const INLINE_NEVER_SOURCE: &str = r#"
@@ -20,6 +29,408 @@ fn test_build_filter() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_filter_expression() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(
+        &project.dir,
+        &[
+            "build",
+            "--filter",
+            "",
+            "--exclude",
+            r#"kind == "NeverInline" && function ~ "main""#,
+        ],
+    )?
+    .assert_ok();
+    let export = std::fs::read_to_string(project.output_dir().join("web/remarks.json"))?;
+    assert!(!export.contains("NeverInline"));
+
+    // Invalid expressions are rejected
+    cargo_remark(&project.dir, &["build", "--include", "hotness >"])?.assert_error();
+    Ok(())
+}
+
+#[test]
+fn test_remark_passes() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(
+        &project.dir,
+        &["build", "--filter", "", "--passes", "gvn,licm"],
+    )?
+    .assert_ok();
+    assert!(!has_never_inline(&project)?);
+
+    let flags = load_crate_flags(&project.flags_dir())?;
+    assert!(flags[0].flags.contains(&"-Cremark=gvn".to_string()));
+    assert!(!flags[0].flags.contains(&"-Cremark=all".to_string()));
+    Ok(())
+}
+
+#[test]
+fn test_select_toolchain() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["+nightly", "build", "--filter", ""])?.assert_ok();
+    assert!(has_never_inline(&project)?);
+
+    cargo_remark(
+        &project.dir,
+        &["build", "--toolchain", "non-existent-toolchain"],
+    )?
+    .assert_error();
+    Ok(())
+}
+
+#[test]
+fn test_fallback_from_default_stable_toolchain() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    // The stable toolchain was not chosen explicitly, so an installed nightly is used instead
+    cargo_remark_with_default_toolchain(&project.dir, &["build", "--filter", ""], "stable")?
+        .assert_ok();
+    assert!(!project
+        .remark_dir()
+        .join("remarks.diagnostics.json")
+        .is_file());
+    assert!(has_never_inline(&project)?);
+    Ok(())
+}
+
+#[test]
+fn test_collect_remarks_from_diagnostics() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build", "--diagnostics", "--filter", ""])?.assert_ok();
+
+    assert!(project
+        .remark_dir()
+        .join("remarks.diagnostics.json")
+        .is_file());
+    assert!(has_never_inline(&project)?);
+
+    let export: serde_json::Value = serde_json::from_slice(&std::fs::read(
+        project.output_dir().join("web/remarks.json"),
+    )?)?;
+    let exported = export
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|remark| remark["name"] == "NeverInline")
+        .unwrap();
+    assert_eq!(exported["origin"]["stage"], "diagnostic");
+    assert_eq!(exported["function"]["location"]["file"], "src/main.rs");
+    Ok(())
+}
+
+#[test]
+fn test_wrap_with_diagnostics() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file(
+        "src/main.rs",
+        &INLINE_NEVER_SOURCE.replace("foo();", r#"foo(); println!("{{\"name\": \"foo\"}}");"#),
+    );
+    // JSON printed by the program is not consumed as a Cargo message
+    let output = cargo_remark(
+        &project.dir,
+        &[
+            "wrap",
+            "--diagnostics",
+            "--filter",
+            "",
+            "--",
+            "run",
+            "--release",
+        ],
+    )?
+    .assert_ok();
+    assert!(output.stdout().contains(r#"{"name": "foo"}"#));
+    assert!(has_never_inline(&project)?);
+
+    // The message format cannot be passed to external subcommands
+    cargo_remark(
+        &project.dir,
+        &["wrap", "--diagnostics", "--", "pgo", "optimize"],
+    )?
+    .assert_error();
+    Ok(())
+}
+
+#[test]
+fn test_matrix() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(
+        &project.dir,
+        &[
+            "matrix",
+            "--filter",
+            "",
+            "-c",
+            "base=",
+            "-c",
+            "o0=-Copt-level=0",
+        ],
+    )?
+    .assert_ok();
+
+    let matrix_dir = project.path("target/remarks/matrix");
+    assert!(matrix_dir.join("base/build").is_dir());
+    assert!(matrix_dir.join("o0/build").is_dir());
+    let export: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(matrix_dir.join("web/remarks.json"))?)?;
+    assert!(export.as_array().unwrap().iter().any(|remark| {
+        remark["name"] == "NeverInline" && remark["configurations"] == serde_json::json!(["base"])
+    }));
+
+    // A single configuration cannot be compared
+    cargo_remark(&project.dir, &["matrix", "-c", "base="])?.assert_error();
+    Ok(())
+}
+
+#[test]
+fn test_pgo_workload_errors() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+
+    // The workload has to be specified
+    cargo_remark(&project.dir, &["pgo"])?.assert_error();
+
+    // The workload does not execute the instrumented binary, so no profiles are gathered
+    cargo_remark(&project.dir, &["pgo", "--", "true"])?.assert_error();
+    let pgo_dir = project.path("target/remarks/pgo");
+    assert!(pgo_dir.join("build/release").is_dir());
+    assert!(pgo_dir.join("profiles").is_dir());
+    assert!(!pgo_dir.join("merged.profdata").exists());
+
+    cargo_remark(&project.dir, &["pgo", "--", "false"])?.assert_error();
+    Ok(())
+}
+
+#[test]
+fn test_pgo_respect_config_rustflags() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE).file(
+        ".cargo/config.toml",
+        r#"[build]
+rustflags = ["-Cforce-frame-pointers=yes"]
+"#,
+    );
+    // The workload does not gather profiles, but the crate is built with instrumentation
+    cargo_remark(&project.dir, &["pgo", "--", "true"])?.assert_error();
+
+    let flags = load_crate_flags(&project.path("target/remarks/pgo/flags"))?;
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].crate_name, "foo");
+    assert!(flags[0]
+        .flags
+        .iter()
+        .any(|f| f == "-Cforce-frame-pointers=yes"));
+    assert!(flags[0]
+        .flags
+        .iter()
+        .any(|f| f.starts_with("-Cprofile-generate=")));
+    Ok(())
+}
+
+#[test]
+fn test_serve() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+
+    let mut server = spawn_cargo_remark(&project.dir, &["serve", "--port", "0", "--filter", ""])?;
+    // The output has to stay open, otherwise the server fails when it prints to it
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line)?;
+    let address: String = line
+        .split("http://")
+        .nth(1)
+        .unwrap_or_default()
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ':')
+        .collect();
+    let stats = http_get(&address, "/api/stats");
+    let index = http_get(&address, "/");
+    let remarks = http_get(
+        &address,
+        "/api/remarks?filter=kind+%3D%3D+%22NeverInline%22",
+    );
+    // Pages that reach the server through a different host name cannot read it
+    let foreign_index = http_request(&address, "attacker.com", "/");
+    let foreign_remarks = http_request(&address, "attacker.com", "/api/remarks");
+    server.kill()?;
+    server.wait()?;
+
+    let stats = stats?;
+    assert!(stats.starts_with("HTTP/1.0 200"), "{stats}");
+    assert!(stats.contains("NeverInline"));
+    let index = index?;
+    assert!(index.contains("/api/version"));
+    let remarks = remarks?;
+    assert!(remarks.contains(r#""name":"NeverInline""#));
+    for response in [foreign_index?, foreign_remarks?] {
+        assert!(response.starts_with("HTTP/1.0 403"), "{response}");
+        assert!(!response.contains("NeverInline"));
+    }
+    Ok(())
+}
+
+fn http_get(address: &str, path: &str) -> anyhow::Result<String> {
+    http_request(address, address, path)
+}
+
+fn http_request(address: &str, host: &str, path: &str) -> anyhow::Result<String> {
+    let mut stream = TcpStream::connect(address)?;
+    write!(stream, "GET {path} HTTP/1.0\r\nHost: {host}\r\n\r\n")?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+#[test]
+fn test_triage() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+
+    let export: serde_json::Value = serde_json::from_slice(&std::fs::read(
+        project.output_dir().join("web/remarks.json"),
+    )?)?;
+    let fingerprint = export
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|remark| remark["name"] == "NeverInline")
+        .unwrap()["fingerprint"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    cargo_remark(
+        &project.dir,
+        &[
+            "triage",
+            "set",
+            &fingerprint,
+            "--status",
+            "todo",
+            "--note",
+            "hot",
+        ],
+    )?
+    .assert_ok();
+    let triage = std::fs::read_to_string(project.path("remark-triage.toml"))?;
+    assert!(triage.contains(&format!("[remarks.{fingerprint}]")));
+    assert!(triage.contains("status = \"todo\""));
+    let list = cargo_remark(&project.dir, &["triage", "list", "--status", "todo"])?.assert_ok();
+    assert!(list.stdout().contains(&fingerprint));
+
+    // The triage status is shown in the remark list
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let remark_list = std::fs::read_to_string(project.output_dir().join("web/remarks.html"))?;
+    assert!(remark_list.contains(r#""triage":"todo","triage_note":"hot""#));
+
+    cargo_remark(
+        &project.dir,
+        &["triage", "clear", "--include", "kind == \"NeverInline\""],
+    )?
+    .assert_ok();
+    let list = cargo_remark(&project.dir, &["triage", "list"])?.assert_ok();
+    assert!(list.stdout().is_empty());
+
+    cargo_remark(
+        &project.dir,
+        &["triage", "set", "0123456789abcdef", "--status", "reviewed"],
+    )?
+    .assert_error();
+    Ok(())
+}
+
+#[test]
+fn test_terminal_commands() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+
+    let output = cargo_remark(
+        &project.dir,
+        &["list", "--filter", "", "--sort", "location"],
+    )?
+    .assert_ok();
+    assert!(output.stdout().contains("inline/NeverInline"));
+
+    let output = cargo_remark(
+        &project.dir,
+        &[
+            "list",
+            "--filter",
+            "",
+            "--include",
+            "kind == \"NeverInline\"",
+            "--json",
+        ],
+    )?
+    .assert_ok();
+    let page: serde_json::Value = serde_json::from_str(&output.stdout())?;
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["remarks"][0]["function"]["location"]["line"], 6);
+
+    let output = cargo_remark(&project.dir, &["stats", "--filter", "", "--json"])?.assert_ok();
+    let stats: serde_json::Value = serde_json::from_str(&output.stdout())?;
+    assert_eq!(stats["kinds"]["NeverInline"]["count"], 1);
+    assert!(stats["functions"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .any(|function| function.ends_with("main")));
+
+    let output =
+        cargo_remark(&project.dir, &["show", "src/main.rs:6", "--filter", ""])?.assert_ok();
+    assert!(output.stdout().contains("src/main.rs:6 foo();"));
+    assert!(output.stdout().contains("inline/NeverInline"));
+
+    let output = cargo_remark(
+        &project.dir,
+        &["show", "src/main.rs:2", "--filter", "", "--json"],
+    )?
+    .assert_ok();
+    assert_eq!(output.stdout().trim(), "[]");
+    Ok(())
+}
+
+#[test]
+fn test_human_format() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    let output = cargo_remark(
+        &project.dir,
+        &["build", "--filter", "", "--format", "human"],
+    )?
+    .assert_ok();
+    let stdout = output.stdout();
+    assert!(stdout.contains("note[inline::NeverInline]: 'foo::foo' not inlined into 'foo::main'"));
+    assert!(stdout.contains(
+        r#" --> src/main.rs:6:5
+  |
+3 | fn foo() {}
+  | - foo::foo
+...
+5 | fn main() {
+  | - foo::main
+6 |     foo();
+  |     ^
+  = function: foo::main
+"#
+    ));
+    assert!(stdout.contains("remark(s) generated"));
+    assert!(project.output_dir().join("web/index.html").is_file());
+    Ok(())
+}
+
 #[test]
 fn test_generate_remarks() -> anyhow::Result<()> {
     let mut project = init_cargo_project()?;
@@ -27,6 +438,16 @@ fn test_generate_remarks() -> anyhow::Result<()> {
     cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
 
     project.default_out_dir().check_index();
+    let export: serde_json::Value = serde_json::from_slice(&std::fs::read(
+        project.output_dir().join("web/remarks.json"),
+    )?)?;
+    let exported = export
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|remark| remark["name"] == "NeverInline")
+        .unwrap();
+    assert_eq!(exported["fingerprint"].as_str().unwrap().len(), 16);
 
     let remark_dir = project.remark_dir();
     assert!(remark_dir.is_dir());
@@ -36,7 +457,9 @@ fn test_generate_remarks() -> anyhow::Result<()> {
             external: false,
             source_dir: project.dir.clone(),
             filter_kind: vec![],
+            filter: Default::default(),
             rustc_source_root: None,
+            path_mappings: vec![],
         },
         None,
     )?;
@@ -46,7 +469,11 @@ fn test_generate_remarks() -> anyhow::Result<()> {
         .find(|remark| remark.name == "NeverInline")
         .unwrap();
     assert_eq!(remark.pass, "inline");
+    let origin = remark.origin.as_ref().unwrap();
+    assert_eq!(origin.crate_name.as_deref(), Some("foo"));
+    assert_eq!(origin.stage, Some(RemarkStage::Optimization));
     assert_eq!(remark.function.name, "foo::main");
+    assert_eq!(remark.fingerprint.to_string(), exported["fingerprint"]);
 
     // Windows doesn't seem to load the debug location correctly
     #[cfg(unix)]
@@ -64,6 +491,489 @@ fn test_generate_remarks() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_suppressed_remarks() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file(
+        "src/main.rs",
+        &INLINE_NEVER_SOURCE.replace("foo();", "foo(); // remark: allow(NeverInline)"),
+    );
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+
+    let remarks = load_remarks_from_dir(
+        project.remark_dir(),
+        RemarkLoadOptions {
+            source_dir: project.dir.clone(),
+            ..Default::default()
+        },
+        None,
+    )?;
+    let remark = remarks
+        .iter()
+        .find(|remark| remark.name == "NeverInline")
+        .unwrap();
+    assert!(remark.suppressed);
+    assert!(project.output_dir().join("web/suppressed.html").is_file());
+    Ok(())
+}
+
+#[test]
+fn test_dedicated_target_dir() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build"])?.assert_ok();
+    assert!(project.path("target/remarks/build/release").is_dir());
+    assert!(!project.path("target/release").exists());
+
+    cargo_remark(&project.dir, &["build", "--shared-target-dir"])?.assert_ok();
+    assert!(project.path("target/release").is_dir());
+    Ok(())
+}
+
+#[test]
+fn test_fresh_remarks_for_each_run() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let first_run = project.output_dir();
+
+    // The crate is up to date, but it has to be rebuilt to generate remarks again
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let second_run = project.output_dir();
+    assert_ne!(first_run, second_run);
+    assert_eq!(project.run_dirs("release").len(), 2);
+    assert!(has_never_inline(&project)?);
+
+    project.file("src/main.rs", "fn main() {}");
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    assert!(!has_never_inline(&project)?);
+    assert!(first_run.join("yaml").read_dir()?.next().is_some());
+
+    // Old runs are removed
+    project.file("remark.toml", "keep-runs = 2");
+    cargo_remark(&project.dir, &["build"])?.assert_ok();
+    assert_eq!(project.run_dirs("release").len(), 2);
+    assert!(!first_run.exists());
+    assert!(project.output_dir().is_dir());
+    Ok(())
+}
+
+#[test]
+fn test_run_history() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    project.file("src/main.rs", "fn main() {}");
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+
+    let history = load_history(&project.path("target/remarks/history"))?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].profile, "release");
+    assert!(history[0].summary.kinds.contains_key("NeverInline"));
+    assert!(!history[1].summary.kinds.contains_key("NeverInline"));
+    assert_eq!(history[1].flags[0].crate_name, "foo");
+    project.default_out_dir().check_trends();
+    Ok(())
+}
+
+#[test]
+fn test_diff_runs() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file(
+        "src/main.rs",
+        r#"
+#[inline(never)]
+fn foo() {}
+
+fn main() {
+    foo();
+}
+
+#[inline(never)]
+fn bar() {}
+"#,
+    );
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let old_run = project.output_dir();
+
+    project.file(
+        "src/main.rs",
+        r#"
+#[inline(never)]
+fn foo() { bar() }
+
+fn main() {
+    foo();
+}
+
+#[inline(never)]
+fn bar() {}
+"#,
+    );
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let new_run = project.output_dir();
+
+    let output = cargo_remark(
+        &project.dir,
+        &[
+            "diff",
+            "--filter",
+            "",
+            old_run.to_str().unwrap(),
+            new_run.to_str().unwrap(),
+        ],
+    )?
+    .assert_ok();
+    let stdout = output.stdout();
+    assert!(stdout.contains("1 added, 0 removed"));
+    assert!(stdout.contains("inline/NeverInline"));
+
+    let diff_dir = HTMLDir::new(&project.path("target/remarks/diff"));
+    diff_dir.check_index();
+    diff_dir.check_source("src_main.rs.html");
+    Ok(())
+}
+
+#[test]
+fn test_diff_runs_shifted_lines() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let old_run = project.output_dir();
+
+    project.file(
+        "src/main.rs",
+        &format!("// First comment\n// Second comment\n// Third comment\n{INLINE_NEVER_SOURCE}"),
+    );
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let new_run = project.output_dir();
+
+    let output = cargo_remark(
+        &project.dir,
+        &[
+            "diff",
+            "--filter",
+            "",
+            old_run.to_str().unwrap(),
+            new_run.to_str().unwrap(),
+        ],
+    )?
+    .assert_ok();
+    assert!(output
+        .stdout()
+        .contains("0 added, 0 removed, 0 with changed hotness"));
+    Ok(())
+}
+
+const CHECK_SOURCE: &str = r#"
+#[inline(never)]
+fn foo() {}
+
+fn main() {
+    foo();
+    other();
+}
+
+#[inline(never)]
+fn bar() {}
+
+#[inline(never)]
+fn other() {
+    BODY
+}
+"#;
+
+#[test]
+fn test_check_baseline() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", &CHECK_SOURCE.replace("BODY", ""));
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    cargo_remark(
+        &project.dir,
+        &[
+            "check",
+            "--filter",
+            "",
+            "--baseline",
+            "baseline.json",
+            "--update-baseline",
+        ],
+    )?
+    .assert_ok();
+    assert!(project.path("baseline.json").is_file());
+    cargo_remark(
+        &project.dir,
+        &["check", "--filter", "", "--baseline", "baseline.json"],
+    )?
+    .assert_ok();
+
+    // Remarks of unrelated code keep their identity
+    project.file("src/main.rs", &CHECK_SOURCE.replace("BODY", "bar();"));
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let output = cargo_remark(
+        &project.dir,
+        &["check", "--filter", "", "--baseline", "baseline.json"],
+    )?
+    .assert_error();
+    assert!(output
+        .stdout()
+        .contains("1 new remark(s), 1 of them fail the check"));
+    assert!(output.stdout().contains("inline/NeverInline"));
+
+    // New remarks of other kinds do not fail the check
+    cargo_remark(
+        &project.dir,
+        &[
+            "check",
+            "--filter",
+            "",
+            "--baseline",
+            "baseline.json",
+            "--kind",
+            "LoadClobbered",
+        ],
+    )?
+    .assert_ok();
+    Ok(())
+}
+
+#[test]
+fn test_config() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    let manifest = std::fs::read_to_string(project.path("Cargo.toml"))?;
+    project.file(
+        "Cargo.toml",
+        &format!(
+            r#"{manifest}
+[package.metadata.remark]
+filter = ["LoadClobbered"]
+"#
+        ),
+    );
+    project.file("remark.toml", "external = false\n");
+
+    let output = cargo_remark(&project.dir, &["config"])?.assert_ok();
+    assert!(output.stdout().contains("[package.metadata.remark]"));
+    assert!(output.stdout().contains("\"LoadClobbered\""));
+
+    // The configured filter replaces the default filter, which would hide `NeverInline`
+    cargo_remark(&project.dir, &["build"])?.assert_ok();
+    assert!(has_never_inline(&project)?);
+    let export = std::fs::read_to_string(project.output_dir().join("web/remarks.json"))?;
+    assert!(export.contains("NeverInline"));
+    Ok(())
+}
+
+#[test]
+fn test_custom_profile() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    let manifest = std::fs::read_to_string(project.path("Cargo.toml"))?;
+    project.file(
+        "Cargo.toml",
+        &format!(
+            r#"{manifest}
+[profile.profiling]
+inherits = "release"
+debug = true
+"#
+        ),
+    );
+    cargo_remark(&project.dir, &["build", "--", "--profile", "profiling"])?.assert_ok();
+    assert!(project.path("target/remarks/build/profiling").is_dir());
+    let output_dir = project.profile_output_dir("profiling");
+    assert!(output_dir.join("yaml").is_dir());
+    HTMLDir::new(&output_dir.join("web")).check_index();
+    assert!(project.run_dirs("release").is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_manifest_path() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    let parent = project.dir.parent().unwrap().to_path_buf();
+    cargo_remark(
+        &parent,
+        &["build", "--", "--manifest-path", "foo/Cargo.toml"],
+    )?
+    .assert_ok();
+    project.default_out_dir().check_index();
+    assert!(!parent.join("target").exists());
+    Ok(())
+}
+
+#[test]
+fn test_respect_config_rustflags() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE).file(
+        ".cargo/config.toml",
+        r#"[build]
+rustflags = ["-Cforce-frame-pointers=yes"]
+"#,
+    );
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    let remarks = load_remarks_from_dir(
+        project.remark_dir(),
+        RemarkLoadOptions {
+            source_dir: project.dir.clone(),
+            ..Default::default()
+        },
+        None,
+    )?;
+    assert!(remarks.iter().any(|remark| remark.name == "NeverInline"));
+
+    let flags = load_crate_flags(&project.flags_dir())?;
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].crate_name, "foo");
+    for flag in [
+        "-Cforce-frame-pointers=yes",
+        "-Cremark=all",
+        "-Cdebuginfo=1",
+    ] {
+        assert!(flags[0].flags.iter().any(|f| f == flag));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_remarks_only_for_workspace() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    add_dependency(&mut project);
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+
+    let remarks = load_remarks_from_dir(
+        project.remark_dir(),
+        RemarkLoadOptions {
+            external: true,
+            source_dir: project.dir.clone(),
+            ..Default::default()
+        },
+        None,
+    )?;
+    assert!(remarks.iter().any(|r| crate_name(r) == Some("foo")));
+    assert!(!remarks.iter().any(|r| crate_name(r) == Some("bar")));
+    Ok(())
+}
+
+#[test]
+fn test_remark_dependency() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    add_dependency(&mut project);
+    cargo_remark(
+        &project.dir,
+        &["build", "--filter", "", "--remark-package", "bar"],
+    )?
+    .assert_ok();
+
+    let remarks = load_remarks_from_dir(
+        project.remark_dir(),
+        RemarkLoadOptions {
+            external: true,
+            source_dir: project.dir.clone(),
+            ..Default::default()
+        },
+        None,
+    )?;
+    assert!(remarks.iter().any(|r| crate_name(r) == Some("bar")));
+    Ok(())
+}
+
+/// Adds a dependency named `bar`, which is located outside of the workspace.
+fn add_dependency(project: &mut CargoProject) {
+    project
+        .file(
+            "../bar/Cargo.toml",
+            r#"[package]
+name = "bar"
+version = "0.1.0"
+edition = "2021"
+"#,
+        )
+        .file(
+            "../bar/src/lib.rs",
+            r#"
+#[inline(never)]
+fn bar() {}
+
+pub fn baz() {
+    bar();
+}
+"#,
+        )
+        .file(
+            "src/main.rs",
+            r#"
+fn main() {
+    bar::baz();
+}
+"#,
+        );
+    let manifest = std::fs::read_to_string(project.path("Cargo.toml")).unwrap();
+    project.file(
+        "Cargo.toml",
+        &format!("{manifest}bar = {{ path = \"../bar\" }}\n"),
+    );
+}
+
+fn crate_name(remark: &Remark) -> Option<&str> {
+    remark.origin.as_ref()?.crate_name.as_deref()
+}
+
+#[test]
+fn test_pack_analyze_archive() -> anyhow::Result<()> {
+    let mut project = init_cargo_project()?;
+    project.file("src/main.rs", INLINE_NEVER_SOURCE);
+    cargo_remark(&project.dir, &["build", "--filter", ""])?.assert_ok();
+    cargo_remark(
+        &project.dir,
+        &["pack", "--filter", "", "-o", "remarks.tar.gz"],
+    )?
+    .assert_ok();
+
+    // Render the archive from a different directory, without access to the project sources
+    let dir = tempfile::TempDir::new()?;
+    let archive = dir.path().join("remarks.tar.gz");
+    std::fs::rename(project.path("remarks.tar.gz"), &archive)?;
+    drop(project);
+
+    analyze_remarks(
+        dir.path(),
+        &["--output-dir", "out", archive.to_str().unwrap()],
+    )?
+    .assert_ok();
+    let out = HTMLDir::new(&dir.path().join("out"));
+    out.check_index();
+    out.check_source("src_main.rs.html");
+    let export = std::fs::read_to_string(dir.path().join("out/remarks.json"))?;
+    assert!(export.contains("NeverInline"));
+
+    // Filters passed on the command line are applied to the packed remarks
+    analyze_remarks(
+        dir.path(),
+        &[
+            "--output-dir",
+            "filtered",
+            "--filter",
+            "NeverInline",
+            archive.to_str().unwrap(),
+        ],
+    )?
+    .assert_ok();
+    let export = std::fs::read_to_string(dir.path().join("filtered/remarks.json"))?;
+    assert!(!export.contains("NeverInline"));
+
+    Ok(())
+}
+
+#[test]
+fn test_pack_without_remarks() -> anyhow::Result<()> {
+    let project = init_cargo_project()?;
+    cargo_remark(&project.dir, &["pack"])?.assert_error();
+    Ok(())
+}
+
 fn normalize_location(location: Option<&Location>) -> Option<Location> {
     location.map(|l| Location {
         file: l.file.replace('\\', "/"),
@@ -79,9 +989,23 @@ fn load_remarks(path: &Path, filter: Vec<String>) -> Vec<Remark> {
             external: false,
             source_dir: path.to_path_buf(),
             filter_kind: filter,
+            filter: Default::default(),
             rustc_source_root: None,
+            path_mappings: vec![],
         },
         None,
     )
     .expect("Cannot load remarks")
 }
+
+fn has_never_inline(project: &CargoProject) -> anyhow::Result<bool> {
+    let remarks = load_remarks_from_dir(
+        project.remark_dir(),
+        RemarkLoadOptions {
+            source_dir: project.dir.clone(),
+            ..Default::default()
+        },
+        None,
+    )?;
+    Ok(remarks.iter().any(|remark| remark.name == "NeverInline"))
+}
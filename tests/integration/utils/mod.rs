@@ -1,8 +1,14 @@
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output, Stdio};
+use std::process::{Child, Command, Output, Stdio};
 use tempfile::TempDir;
 
 pub fn cargo_remark(dir: &Path, args: &[&str]) -> anyhow::Result<Output> {
+    let child = spawn_cargo_remark(dir, args)?;
+    Ok(child.wait_with_output()?)
+}
+
+/// Starts `cargo remark` without waiting for it to finish.
+pub fn spawn_cargo_remark(dir: &Path, args: &[&str]) -> anyhow::Result<Child> {
     let mut command = Command::new("cargo");
     command.arg("remark");
     for arg in args {
@@ -16,6 +22,30 @@ pub fn cargo_remark(dir: &Path, args: &[&str]) -> anyhow::Result<Output> {
     let path = format!("{}:{}", get_target_dir().display(), path);
 
     command.env("PATH", path);
+    // The toolchain selected for the test process should not be treated as an explicit choice
+    command.env_remove("RUSTUP_TOOLCHAIN");
+    command.env_remove("RUSTUP_TOOLCHAIN_SOURCE");
+
+    Ok(command.spawn()?)
+}
+
+/// Runs `cargo remark` with the environment that the rustup cargo proxy sets when `toolchain` is
+/// the default toolchain.
+pub fn cargo_remark_with_default_toolchain(
+    dir: &Path,
+    args: &[&str],
+    toolchain: &str,
+) -> anyhow::Result<Output> {
+    let mut command = Command::new(get_target_dir().join("cargo-remark"));
+    command.arg("remark");
+    for arg in args {
+        command.arg(arg);
+    }
+    command.current_dir(dir);
+    command.stdin(Stdio::null());
+    command.stdout(Stdio::piped());
+    command.env("RUSTUP_TOOLCHAIN", toolchain);
+    command.env("RUSTUP_TOOLCHAIN_SOURCE", "default");
 
     let child = command.spawn()?;
     Ok(child.wait_with_output()?)
@@ -82,6 +112,19 @@ fn get_target_dir() -> PathBuf {
     target_dir
 }
 
+pub fn get_host_triple() -> String {
+    let output = Command::new("rustc")
+        .arg("-vV")
+        .output()
+        .expect("Cannot run rustc");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .expect("Cannot find host triple")
+        .trim()
+        .to_string()
+}
+
 pub fn get_test_data_path<P: AsRef<Path>>(path: P) -> PathBuf {
     PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("tests")
@@ -104,6 +147,10 @@ impl HTMLDir {
         self.check_exists("index.html");
     }
 
+    pub fn check_trends(&self) {
+        self.check_exists("trends.html");
+    }
+
     pub fn check_source(&self, file: &str) {
         self.check_exists(Path::new("src").join(file));
     }
@@ -128,16 +175,49 @@ impl CargoProject {
 
     pub fn file<P: AsRef<Path>>(&mut self, path: P, code: &str) -> &mut Self {
         let path = self.path(path.as_ref());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Could not create project directory");
+        }
         std::fs::write(path, code).expect("Could not write project file");
         self
     }
 
+    /// Directory of the latest run of the default (`release`) profile and the host target.
+    pub fn output_dir(&self) -> PathBuf {
+        self.profile_output_dir("release")
+    }
+
+    pub fn profile_output_dir(&self, profile: &str) -> PathBuf {
+        let dir = self.runs_parent_dir(profile);
+        let latest = std::fs::read_to_string(dir.join("latest")).expect("Cannot find latest run");
+        dir.join("runs").join(latest.trim())
+    }
+
+    /// Returns all run directories of the given profile and the host target.
+    pub fn run_dirs(&self, profile: &str) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = std::fs::read_dir(self.runs_parent_dir(profile).join("runs"))
+            .map(|entries| entries.map(|entry| entry.unwrap().path()).collect())
+            .unwrap_or_default();
+        dirs.sort();
+        dirs
+    }
+
+    fn runs_parent_dir(&self, profile: &str) -> PathBuf {
+        self.path("target/remarks")
+            .join(profile)
+            .join(get_host_triple())
+    }
+
     pub fn remark_dir(&self) -> PathBuf {
-        self.path("target/remarks/yaml")
+        self.output_dir().join("yaml")
+    }
+
+    pub fn flags_dir(&self) -> PathBuf {
+        self.output_dir().join("flags")
     }
 
     pub fn default_out_dir(&self) -> HTMLDir {
-        self.out_dir(&self.path("target/remarks/web"))
+        self.out_dir(&self.output_dir().join("web"))
     }
 
     fn out_dir(&self, path: &Path) -> HTMLDir {